@@ -0,0 +1,611 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::parser::*;
+
+// The two terminals come first in every manager
+pub const FALSE: usize = 0;
+pub const TRUE: usize = 1;
+
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
+pub enum BddError {
+    NotPropositional(Box<LogExpr>),
+    Unwritable(Box<String>),
+}
+
+impl Display for BddError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BddError::NotPropositional(expr) => {
+                write!(f, "{} is not a propositional formula", expr)
+            }
+            BddError::Unwritable(path) => write!(f, "cannot write {}", path),
+        }
+    }
+}
+
+// Models counted below a node stand for 2^skipped models once the levels
+// skipped over are free
+fn scale(models: u128, skipped: usize) -> Option<u128> {
+    match models {
+        0 => Some(0),
+        _ => models.checked_mul(2u128.checked_pow(u32::try_from(skipped).ok()?)?),
+    }
+}
+
+/// How the atoms of a formula are ordered before building its diagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BddOrder {
+    // Depth-first order of first appearance
+    Appearance,
+    // Most frequent atoms nearest the root
+    Frequency,
+    // Frequency order improved by sifting each atom to its best level
+    Sift,
+}
+
+impl Display for BddOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BddOrder::Appearance => write!(f, "appearance"),
+            BddOrder::Frequency => write!(f, "frequency"),
+            BddOrder::Sift => write!(f, "sift"),
+        }
+    }
+}
+
+impl BddOrder {
+    pub fn from_str(s: &str) -> Option<BddOrder> {
+        match s {
+            "appearance" => Some(BddOrder::Appearance),
+            "frequency" => Some(BddOrder::Frequency),
+            "sift" => Some(BddOrder::Sift),
+            _ => None,
+        }
+    }
+
+    /// The level of every atom, from the root down
+    pub fn order(&self, exprs: &[&LogExpr]) -> Result<Vec<String>, BddError> {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for expr in exprs {
+            count_atoms(expr, &mut counts)?;
+        }
+        if *self != BddOrder::Appearance {
+            // Stable, so ties keep their order of appearance
+            counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        }
+        let order = counts.into_iter().map(|(atom, _)| atom).collect::<Vec<_>>();
+        match self {
+            BddOrder::Sift => sift(exprs, order),
+            _ => Ok(order),
+        }
+    }
+}
+
+fn count_atoms(expr: &LogExpr, counts: &mut Vec<(String, usize)>) -> Result<(), BddError> {
+    match expr {
+        LogExpr::Atom(token) | LogExpr::Var(token) => {
+            match counts.iter_mut().find(|(atom, _)| **atom == *token.text) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((token.text.to_string(), 1)),
+            }
+            Ok(())
+        }
+        LogExpr::True | LogExpr::False => Ok(()),
+        LogExpr::UnaryOp(op, inner) if **op == Operator::Not => count_atoms(inner, counts),
+        LogExpr::BinaryOp(op, exprs)
+            if matches!(**op, Operator::And | Operator::Or | Operator::Imp) =>
+        {
+            exprs.iter().try_for_each(|e| count_atoms(e, counts))
+        }
+        _ => Err(BddError::NotPropositional(Box::new(expr.clone()))),
+    }
+}
+
+// Moves each atom, most frequent first, to the level where the diagrams
+// are smallest, rebuilding them for every candidate level
+fn sift(exprs: &[&LogExpr], mut order: Vec<String>) -> Result<Vec<String>, BddError> {
+    let size = |order: &[String]| -> Result<usize, BddError> {
+        let mut bdd = Bdd::new(order.to_vec());
+        let mut roots = Vec::new();
+        for expr in exprs {
+            roots.push(bdd.build(expr)?);
+        }
+        Ok(bdd.size(&roots))
+    };
+    let mut best = size(&order)?;
+    for atom in order.clone() {
+        let from = order.iter().position(|a| *a == atom).unwrap_or(0);
+        let mut candidate = order.clone();
+        candidate.remove(from);
+        let mut best_level = from;
+        for level in 0..order.len() {
+            let mut moved = candidate.clone();
+            moved.insert(level, atom.clone());
+            let moved_size = size(&moved)?;
+            if moved_size < best {
+                best = moved_size;
+                best_level = level;
+            }
+        }
+        candidate.insert(best_level, atom);
+        order = candidate;
+    }
+    Ok(order)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BddNode {
+    pub level: usize,
+    pub low: usize,
+    pub high: usize,
+}
+
+/// A manager for reduced ordered binary decision diagrams over a fixed
+/// atom order. Nodes are shared through the unique table, so two formulas
+/// are equivalent exactly when they build to the same node.
+#[derive(Debug, Clone)]
+pub struct Bdd {
+    pub order: Vec<String>,
+    pub nodes: Vec<BddNode>,
+    unique: HashMap<BddNode, usize>,
+    computed: HashMap<(usize, usize, usize), usize>,
+}
+
+impl Bdd {
+    pub fn new(order: Vec<String>) -> Bdd {
+        let terminal = BddNode {
+            level: order.len(),
+            low: 0,
+            high: 0,
+        };
+        Bdd {
+            order,
+            nodes: vec![terminal, terminal],
+            unique: HashMap::new(),
+            computed: HashMap::new(),
+        }
+    }
+
+    fn level(&self, node: usize) -> usize {
+        self.nodes[node].level
+    }
+
+    fn make(&mut self, level: usize, low: usize, high: usize) -> usize {
+        if low == high {
+            return low;
+        }
+        let node = BddNode { level, low, high };
+        if let Some(id) = self.unique.get(&node) {
+            return *id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        self.unique.insert(node, id);
+        id
+    }
+
+    // Both cofactors of a node with respect to the atom at `level`
+    fn cofactors(&self, node: usize, level: usize) -> (usize, usize) {
+        match self.nodes[node] {
+            BddNode {
+                level: l,
+                low,
+                high,
+            } if l == level => (low, high),
+            _ => (node, node),
+        }
+    }
+
+    /// If `f` then `g` else `h`, the one operation every connective uses
+    pub fn ite(&mut self, f: usize, g: usize, h: usize) -> usize {
+        match (f, g, h) {
+            (TRUE, _, _) => return g,
+            (FALSE, _, _) => return h,
+            (_, TRUE, FALSE) => return f,
+            _ if g == h => return g,
+            _ => {}
+        }
+        if let Some(id) = self.computed.get(&(f, g, h)) {
+            return *id;
+        }
+        let level = self.level(f).min(self.level(g)).min(self.level(h));
+        let (f0, f1) = self.cofactors(f, level);
+        let (g0, g1) = self.cofactors(g, level);
+        let (h0, h1) = self.cofactors(h, level);
+        let low = self.ite(f0, g0, h0);
+        let high = self.ite(f1, g1, h1);
+        let id = self.make(level, low, high);
+        self.computed.insert((f, g, h), id);
+        id
+    }
+
+    pub fn not(&mut self, f: usize) -> usize {
+        self.ite(f, FALSE, TRUE)
+    }
+
+    pub fn build(&mut self, expr: &LogExpr) -> Result<usize, BddError> {
+        match expr {
+            LogExpr::True => Ok(TRUE),
+            LogExpr::False => Ok(FALSE),
+            LogExpr::Atom(token) | LogExpr::Var(token) => {
+                match self.order.iter().position(|a| **a == *token.text) {
+                    Some(level) => Ok(self.make(level, FALSE, TRUE)),
+                    None => Err(BddError::NotPropositional(Box::new(expr.clone()))),
+                }
+            }
+            LogExpr::UnaryOp(op, inner) if **op == Operator::Not => {
+                let f = self.build(inner)?;
+                Ok(self.not(f))
+            }
+            LogExpr::BinaryOp(op, exprs) => match **op {
+                Operator::And => exprs.iter().try_fold(TRUE, |acc, e| {
+                    let f = self.build(e)?;
+                    Ok(self.ite(acc, f, FALSE))
+                }),
+                Operator::Or => exprs.iter().try_fold(FALSE, |acc, e| {
+                    let f = self.build(e)?;
+                    Ok(self.ite(acc, TRUE, f))
+                }),
+                Operator::Imp => match LogExpr::imp_parts(exprs) {
+                    Some((lhs, rhs)) => {
+                        let (f, g) = (self.build(&lhs)?, self.build(&rhs)?);
+                        Ok(self.ite(f, g, TRUE))
+                    }
+                    None => match exprs.first() {
+                        Some(e) => self.build(e),
+                        None => Ok(TRUE),
+                    },
+                },
+                _ => Err(BddError::NotPropositional(Box::new(expr.clone()))),
+            },
+            _ => Err(BddError::NotPropositional(Box::new(expr.clone()))),
+        }
+    }
+
+    // The nodes below the roots, terminals included, in the order found
+    fn reachable(&self, roots: &[usize]) -> Vec<usize> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut found = Vec::new();
+        let mut stack = roots.to_vec();
+        while let Some(node) = stack.pop() {
+            if seen[node] {
+                continue;
+            }
+            seen[node] = true;
+            found.push(node);
+            if node > TRUE {
+                stack.push(self.nodes[node].high);
+                stack.push(self.nodes[node].low);
+            }
+        }
+        found
+    }
+
+    /// Nodes shared by the diagrams of the roots, terminals included
+    pub fn size(&self, roots: &[usize]) -> usize {
+        self.reachable(roots).len()
+    }
+
+    /// Satisfying assignments over all the atoms of the order, if they fit
+    /// in a u128
+    pub fn count_models(&self, root: usize) -> Option<u128> {
+        let mut memo: HashMap<usize, Option<u128>> = HashMap::new();
+        let top = self.order.len();
+        // Models over the atoms from the node's level down, or None once the
+        // count no longer fits
+        fn count(bdd: &Bdd, node: usize, memo: &mut HashMap<usize, Option<u128>>) -> Option<u128> {
+            match node {
+                FALSE => Some(0),
+                TRUE => Some(1),
+                _ => {
+                    if let Some(n) = memo.get(&node) {
+                        return *n;
+                    }
+                    let BddNode { level, low, high } = bdd.nodes[node];
+                    let below = |child: usize, memo: &mut HashMap<usize, Option<u128>>| {
+                        scale(count(bdd, child, memo)?, bdd.level(child) - level - 1)
+                    };
+                    let n = below(low, memo).and_then(|l| l.checked_add(below(high, memo)?));
+                    memo.insert(node, n);
+                    n
+                }
+            }
+        }
+        scale(count(self, root, &mut memo)?, self.level(root).min(top))
+    }
+
+    /// The share of all assignments that are models, which stays in range
+    /// where the count itself overflows
+    pub fn model_fraction(&self, root: usize) -> f64 {
+        fn fraction(bdd: &Bdd, node: usize, memo: &mut HashMap<usize, f64>) -> f64 {
+            match node {
+                FALSE => 0.0,
+                TRUE => 1.0,
+                _ => {
+                    if let Some(x) = memo.get(&node) {
+                        return *x;
+                    }
+                    let BddNode { low, high, .. } = bdd.nodes[node];
+                    let x = (fraction(bdd, low, memo) + fraction(bdd, high, memo)) / 2.0;
+                    memo.insert(node, x);
+                    x
+                }
+            }
+        }
+        fraction(self, root, &mut HashMap::new())
+    }
+
+    /// One satisfying path, as the atoms it fixes
+    pub fn any_model(&self, root: usize) -> Option<Vec<(String, bool)>> {
+        if root == FALSE {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut node = root;
+        while node > TRUE {
+            let BddNode { level, low, high } = self.nodes[node];
+            let value = low == FALSE;
+            path.push((self.order[level].clone(), value));
+            node = if value { high } else { low };
+        }
+        Some(path)
+    }
+
+    /// Every path to the true terminal, as the levels it fixes. The paths
+    /// are disjoint cubes whose union is the formula's set of models.
+    pub fn paths(&self, root: usize) -> Vec<Vec<(usize, bool)>> {
+        let mut paths = Vec::new();
+        let mut stack = vec![(root, Vec::new())];
+        while let Some((node, path)) = stack.pop() {
+            match node {
+                FALSE => {}
+                TRUE => paths.push(path),
+                _ => {
+                    let BddNode { level, low, high } = self.nodes[node];
+                    let mut high_path = path.clone();
+                    high_path.push((level, true));
+                    stack.push((high, high_path));
+                    let mut low_path = path;
+                    low_path.push((level, false));
+                    stack.push((low, low_path));
+                }
+            }
+        }
+        paths
+    }
+
+    /// The diagrams of the roots in Graphviz dot syntax, low edges dashed
+    pub fn dot(&self, roots: &[(String, usize)]) -> String {
+        let ids = roots.iter().map(|(_, id)| *id).collect::<Vec<_>>();
+        let mut lines = vec!["digraph bdd {".to_string()];
+        lines.push("  0 [shape=box, label=\"0\"];".to_string());
+        lines.push("  1 [shape=box, label=\"1\"];".to_string());
+        for node in self.reachable(&ids) {
+            if node <= TRUE {
+                continue;
+            }
+            let BddNode { level, low, high } = self.nodes[node];
+            lines.push(format!("  {} [label=\"{}\"];", node, self.order[level]));
+            lines.push(format!("  {} -> {} [style=dashed];", node, low));
+            lines.push(format!("  {} -> {};", node, high));
+        }
+        for (i, (name, id)) in roots.iter().enumerate() {
+            let name = name.replace('"', "\\\"");
+            lines.push(format!("  r{} [shape=plaintext, label=\"{}\"];", i, name));
+            lines.push(format!("  r{} -> {};", i, id));
+        }
+        lines.push("}".to_string());
+        lines.join("\n") + "\n"
+    }
+}
+
+/// A formula's diagram with the figures the REPL reports
+#[derive(Debug, Clone)]
+pub struct BddReport {
+    pub expr: Box<LogExpr>,
+    pub strategy: BddOrder,
+    pub bdd: Bdd,
+    pub root: usize,
+}
+
+impl BddReport {
+    pub fn new(expr: &LogExpr, strategy: BddOrder) -> Result<BddReport, BddError> {
+        let mut bdd = Bdd::new(strategy.order(&[expr])?);
+        let root = bdd.build(expr)?;
+        Ok(BddReport {
+            expr: Box::new(expr.clone()),
+            strategy,
+            bdd,
+            root,
+        })
+    }
+}
+
+impl Display for BddReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bdd = &self.bdd;
+        writeln!(f, "\tBDD for {}", self.expr)?;
+        writeln!(
+            f,
+            "\t\torder ({}): {}",
+            self.strategy,
+            bdd.order.join(" < ")
+        )?;
+        writeln!(f, "\t\tnodes: {}", bdd.size(&[self.root]))?;
+        let n = bdd.order.len();
+        let total = 1u128
+            .checked_shl(n as u32)
+            .map_or(format!("2^{}", n), |total| total.to_string());
+        let log = n as f64 + bdd.model_fraction(self.root).log2();
+        match bdd.count_models(self.root) {
+            Some(models) => write!(f, "\t\tmodels: {} of {}", models, total)?,
+            None if self.root == TRUE => write!(f, "\t\tmodels: {} of {}", total, total)?,
+            None => write!(f, "\t\tmodels: about 2^{:.2} of {}", log, total)?,
+        }
+        match self.root {
+            TRUE => write!(f, "\n\t\tThe formula is valid."),
+            FALSE => write!(f, "\n\t\tThe formula is unsatisfiable."),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Equivalence of two formulas, decided by building both in one manager
+#[derive(Debug, Clone)]
+pub struct BddEquivalence {
+    pub lhs: Box<LogExpr>,
+    pub rhs: Box<LogExpr>,
+    pub roots: (usize, usize),
+    pub difference: Option<Vec<(String, bool)>>,
+}
+
+impl BddEquivalence {
+    pub fn new(lhs: &LogExpr, rhs: &LogExpr) -> Result<BddEquivalence, BddError> {
+        let mut bdd = Bdd::new(BddOrder::Frequency.order(&[lhs, rhs])?);
+        let (f, g) = (bdd.build(lhs)?, bdd.build(rhs)?);
+        let not_g = bdd.not(g);
+        let xor = bdd.ite(f, not_g, g);
+        Ok(BddEquivalence {
+            lhs: Box::new(lhs.clone()),
+            rhs: Box::new(rhs.clone()),
+            roots: (f, g),
+            difference: bdd.any_model(xor),
+        })
+    }
+}
+
+impl Display for BddEquivalence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.difference {
+            None => write!(
+                f,
+                "\t{} and {} are equivalent: both build to node {}.",
+                self.lhs, self.rhs, self.roots.0
+            ),
+            Some(assignment) => {
+                let assignment = assignment
+                    .iter()
+                    .map(|(atom, value)| format!("{} = {}", atom, value))
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "\t{} and {} are not equivalent (nodes {} and {}); they differ at {}",
+                    self.lhs,
+                    self.rhs,
+                    self.roots.0,
+                    self.roots.1,
+                    assignment.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// Writes the diagram of a formula to a Graphviz file
+pub fn write_dot(file_path: &str, expr: &LogExpr, strategy: BddOrder) -> Result<usize, BddError> {
+    let report = BddReport::new(expr, strategy)?;
+    let dot = report.bdd.dot(&[(expr.to_string(), report.root)]);
+    std::fs::write(file_path, dot)
+        .map_err(|_| BddError::Unwritable(Box::new(file_path.to_string())))?;
+    Ok(report.bdd.size(&[report.root]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn order(atoms: &[&str]) -> Vec<String> {
+        atoms.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn size(atoms: &[String], s: &str) -> usize {
+        let mut bdd = Bdd::new(atoms.to_vec());
+        let root = bdd.build(&expr(s)).unwrap();
+        bdd.size(&[root])
+    }
+
+    // n atoms a0 to a(n - 1), joined by the connective
+    fn many(op: &str, n: usize) -> Box<LogExpr> {
+        let atoms = (0..n).map(|i| format!("a{}", i)).collect::<Vec<_>>();
+        expr(&format!("({} {})", op, atoms.join(" ")))
+    }
+
+    #[test]
+    fn models_are_counted_over_every_atom() {
+        let mut bdd = Bdd::new(order(&["p", "q", "r"]));
+        let cases = [
+            ("(or p q)", 6),
+            ("(and p r)", 2),
+            ("(imp p q)", 6),
+            ("r", 4),
+        ];
+        for (formula, models) in cases {
+            let root = bdd.build(&expr(formula)).unwrap();
+            assert_eq!(bdd.count_models(root), Some(models), "{}", formula);
+        }
+        assert_eq!(bdd.count_models(TRUE), Some(8));
+        assert_eq!(bdd.count_models(FALSE), Some(0));
+    }
+
+    #[test]
+    fn counts_past_u128_are_estimated() {
+        let report = BddReport::new(&many("or", 130), BddOrder::Appearance).unwrap();
+        assert_eq!(report.bdd.count_models(report.root), None);
+        assert!(report
+            .to_string()
+            .contains("models: about 2^130.00 of 2^130"));
+        let report = BddReport::new(&many("and", 130), BddOrder::Appearance).unwrap();
+        assert_eq!(report.bdd.count_models(report.root), Some(1));
+        let mut bdd = Bdd::new((0..128).map(|i| format!("a{}", i)).collect());
+        let root = bdd.build(&expr("a7")).unwrap();
+        assert_eq!(bdd.count_models(root), Some(1 << 127));
+        assert_eq!(bdd.count_models(TRUE), None);
+        assert_eq!(bdd.model_fraction(root), 0.5);
+    }
+
+    #[test]
+    fn equivalent_formulas_share_a_node() {
+        let equivalence =
+            BddEquivalence::new(&expr("(not (and p q))"), &expr("(or (not p) (not q))")).unwrap();
+        assert!(equivalence.difference.is_none());
+        assert_eq!(equivalence.roots.0, equivalence.roots.1);
+        let equivalence = BddEquivalence::new(&expr("(imp p q)"), &expr("(imp q p)")).unwrap();
+        let mut difference = equivalence.difference.unwrap();
+        difference.sort();
+        assert!(difference[0].1 != difference[1].1);
+    }
+
+    #[test]
+    fn orders_decide_the_size() {
+        let formula = "(or (and a1 b1) (and a2 b2) (and a3 b3))";
+        let interleaved = order(&["a1", "b1", "a2", "b2", "a3", "b3"]);
+        let separated = order(&["a1", "a2", "a3", "b1", "b2", "b3"]);
+        assert_eq!(size(&interleaved, formula), 8);
+        assert_eq!(size(&separated, formula), 16);
+        let sifted = sift(&[&expr(formula)], separated).unwrap();
+        assert_eq!(size(&sifted, formula), 8);
+        let frequency = BddOrder::Frequency
+            .order(&[&expr("(and (or q p) (or p r) p)")])
+            .unwrap();
+        assert_eq!(frequency, ["p", "q", "r"]);
+    }
+
+    #[test]
+    fn non_propositional_formulas_are_refused() {
+        assert!(matches!(
+            BddReport::new(&expr("(box p)"), BddOrder::Sift),
+            Err(BddError::NotPropositional(_))
+        ));
+        assert!(matches!(
+            Bdd::new(order(&["p"])).build(&expr("q")),
+            Err(BddError::NotPropositional(_))
+        ));
+    }
+}
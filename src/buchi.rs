@@ -0,0 +1,524 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt::Display;
+
+use crate::ltl::*;
+use crate::parser::*;
+use crate::resolution::*;
+
+/// A state of the automaton: the formulas that hold now, including the
+/// literals that fix the letter read, and the obligations left for the next
+/// state
+#[derive(Debug, Clone)]
+#[allow(clippy::vec_box)]
+pub struct BuchiState {
+    pub old: Vec<Box<LogExpr>>,
+    pub next: Vec<Box<LogExpr>>,
+}
+
+impl BuchiState {
+    fn key(&self) -> (Vec<String>, Vec<String>) {
+        let sorted = |exprs: &[Box<LogExpr>]| {
+            let mut names = exprs.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+            names.sort();
+            names.dedup();
+            names
+        };
+        (sorted(&self.old), sorted(&self.next))
+    }
+
+    /// The atoms the state requires to be true; the rest are taken as false
+    pub fn atoms(&self) -> HashSet<String> {
+        self.old
+            .iter()
+            .filter_map(|e| match &**e {
+                LogExpr::Atom(token) => Some(token.text.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A generalized Büchi automaton built by the tableau construction of Gerth,
+/// Peled, Vardi and Wolper. Every until (and eventually) subformula gives an
+/// acceptance set of the states that do not owe it or that fulfil it.
+#[derive(Debug, Clone)]
+pub struct Buchi {
+    pub formula: Box<LogExpr>,
+    pub states: Vec<BuchiState>,
+    pub initial: Vec<usize>,
+    pub edges: Vec<Vec<usize>>,
+    pub accepting: Vec<(Box<LogExpr>, HashSet<usize>)>,
+}
+
+#[allow(clippy::vec_box)]
+struct Pending {
+    new: Vec<Box<LogExpr>>,
+    old: Vec<Box<LogExpr>>,
+    next: Vec<Box<LogExpr>>,
+}
+
+impl Pending {
+    fn with(&self, now: &[&LogExpr], later: &[&LogExpr]) -> Pending {
+        let mut pending = Pending {
+            new: self.new.clone(),
+            old: self.old.clone(),
+            next: self.next.clone(),
+        };
+        pending
+            .new
+            .extend(now.iter().map(|e| Box::new((*e).clone())));
+        pending
+            .next
+            .extend(later.iter().map(|e| Box::new((*e).clone())));
+        pending
+    }
+}
+
+// Fully expands a set of obligations into the states that can discharge them
+fn expand(formulas: &[Box<LogExpr>]) -> Result<Vec<BuchiState>, TraceError> {
+    let mut done = Vec::new();
+    let mut stack = vec![Pending {
+        new: formulas.to_vec(),
+        old: Vec::new(),
+        next: Vec::new(),
+    }];
+    while let Some(mut node) = stack.pop() {
+        let formula = match node.new.pop() {
+            Some(formula) => formula,
+            None => {
+                done.push(BuchiState {
+                    old: node.old,
+                    next: node.next,
+                });
+                continue;
+            }
+        };
+        if node.old.contains(&formula) {
+            stack.push(node);
+            continue;
+        }
+        match &*formula {
+            LogExpr::True => stack.push(node),
+            LogExpr::False => {}
+            LogExpr::Atom(_) => {
+                if !node.old.contains(&formula.negate()) {
+                    node.old.push(formula);
+                    stack.push(node);
+                }
+            }
+            LogExpr::UnaryOp(op, inner) => {
+                node.old.push(formula.clone());
+                match (&**op, &**inner) {
+                    (Operator::Not, LogExpr::Atom(_)) => {
+                        if !node.old.contains(inner) {
+                            stack.push(node);
+                        }
+                    }
+                    (Operator::Next, _) => stack.push(node.with(&[], &[inner])),
+                    (Operator::Eventually, _) => {
+                        stack.push(node.with(&[inner], &[]));
+                        stack.push(node.with(&[], &[&formula]));
+                    }
+                    (Operator::Always, _) => stack.push(node.with(&[inner], &[&formula])),
+                    _ => return Err(TraceError::NotTemporal(formula.clone())),
+                }
+            }
+            LogExpr::BinaryOp(op, exprs) => {
+                node.old.push(formula.clone());
+                match (&**op, exprs.as_slice()) {
+                    (Operator::And, _) => {
+                        node.new.extend(exprs.iter().cloned());
+                        stack.push(node);
+                    }
+                    (Operator::Or, _) => {
+                        for e in exprs {
+                            stack.push(node.with(&[e], &[]));
+                        }
+                    }
+                    (Operator::Until, [lhs, rhs]) => {
+                        stack.push(node.with(&[rhs], &[]));
+                        stack.push(node.with(&[lhs], &[&formula]));
+                    }
+                    (Operator::Release, [lhs, rhs]) => {
+                        stack.push(node.with(&[lhs, rhs], &[]));
+                        stack.push(node.with(&[rhs], &[&formula]));
+                    }
+                    _ => return Err(TraceError::NotTemporal(formula.clone())),
+                }
+            }
+            _ => return Err(TraceError::NotTemporal(formula.clone())),
+        }
+    }
+    Ok(done)
+}
+
+// The eventualities of a formula in negation normal form, each paired with
+// the subformula that fulfils it
+fn eventualities(expr: &LogExpr, found: &mut Vec<(Box<LogExpr>, Box<LogExpr>)>) {
+    match expr {
+        LogExpr::UnaryOp(op, inner) => {
+            if **op == Operator::Eventually && !found.iter().any(|(e, _)| **e == *expr) {
+                found.push((Box::new(expr.clone()), inner.clone()));
+            }
+            eventualities(inner, found);
+        }
+        LogExpr::BinaryOp(op, exprs) => {
+            if let (Operator::Until, [_, rhs]) = (&**op, exprs.as_slice()) {
+                if !found.iter().any(|(e, _)| **e == *expr) {
+                    found.push((Box::new(expr.clone()), rhs.clone()));
+                }
+            }
+            exprs.iter().for_each(|e| eventualities(e, found));
+        }
+        _ => {}
+    }
+}
+
+impl Buchi {
+    pub fn new(expr: &LogExpr) -> Result<Buchi, TraceError> {
+        let formula = nnf(expr, true);
+        let mut buchi = Buchi {
+            formula: formula.clone(),
+            states: Vec::new(),
+            initial: Vec::new(),
+            edges: Vec::new(),
+            accepting: Vec::new(),
+        };
+        let mut index = HashMap::new();
+        let mut queue = VecDeque::new();
+        for state in expand(std::slice::from_ref(&formula))? {
+            let id = buchi.intern(state, &mut index, &mut queue);
+            if !buchi.initial.contains(&id) {
+                buchi.initial.push(id);
+            }
+        }
+        while let Some(id) = queue.pop_front() {
+            for state in expand(&buchi.states[id].next.clone())? {
+                let to = buchi.intern(state, &mut index, &mut queue);
+                if !buchi.edges[id].contains(&to) {
+                    buchi.edges[id].push(to);
+                }
+            }
+        }
+        let mut found = Vec::new();
+        eventualities(&formula, &mut found);
+        for (eventuality, fulfilment) in found {
+            let states = (0..buchi.states.len())
+                .filter(|id| {
+                    let old = &buchi.states[*id].old;
+                    !old.contains(&eventuality) || old.contains(&fulfilment)
+                })
+                .collect();
+            buchi.accepting.push((eventuality, states));
+        }
+        Ok(buchi)
+    }
+
+    fn intern(
+        &mut self,
+        state: BuchiState,
+        index: &mut HashMap<(Vec<String>, Vec<String>), usize>,
+        queue: &mut VecDeque<usize>,
+    ) -> usize {
+        let key = state.key();
+        if let Some(id) = index.get(&key) {
+            return *id;
+        }
+        let id = self.states.len();
+        self.states.push(state);
+        self.edges.push(Vec::new());
+        index.insert(key, id);
+        queue.push_back(id);
+        id
+    }
+
+    // Strongly connected components, by Tarjan's algorithm
+    fn components(&self) -> Vec<Vec<usize>> {
+        struct Search<'a> {
+            edges: &'a [Vec<usize>],
+            counter: usize,
+            number: Vec<Option<usize>>,
+            low: Vec<usize>,
+            stack: Vec<usize>,
+            on_stack: Vec<bool>,
+            components: Vec<Vec<usize>>,
+        }
+
+        fn visit(search: &mut Search, v: usize) {
+            search.number[v] = Some(search.counter);
+            search.low[v] = search.counter;
+            search.counter += 1;
+            search.stack.push(v);
+            search.on_stack[v] = true;
+            for &w in search.edges[v].iter() {
+                match search.number[w] {
+                    None => {
+                        visit(search, w);
+                        search.low[v] = search.low[v].min(search.low[w]);
+                    }
+                    Some(n) if search.on_stack[w] => search.low[v] = search.low[v].min(n),
+                    Some(_) => {}
+                }
+            }
+            if Some(search.low[v]) == search.number[v] {
+                let mut component = Vec::new();
+                while let Some(w) = search.stack.pop() {
+                    search.on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                search.components.push(component);
+            }
+        }
+
+        let n = self.states.len();
+        let mut search = Search {
+            edges: &self.edges,
+            counter: 0,
+            number: vec![None; n],
+            low: vec![0; n],
+            stack: Vec::new(),
+            on_stack: vec![false; n],
+            components: Vec::new(),
+        };
+        for v in 0..n {
+            if search.number[v].is_none() {
+                visit(&mut search, v);
+            }
+        }
+        search.components
+    }
+
+    // A shortest path of at least one step from one of `from` to a state
+    // satisfying `goal`, through states allowed by `within`; the path starts
+    // with the state it leaves from
+    fn path(
+        &self,
+        from: &[usize],
+        goal: impl Fn(usize) -> bool,
+        within: impl Fn(usize) -> bool,
+    ) -> Option<Vec<usize>> {
+        let mut parent: HashMap<usize, usize> = HashMap::new();
+        let mut queue = from.iter().cloned().collect::<VecDeque<_>>();
+        while let Some(v) = queue.pop_front() {
+            for &to in &self.edges[v] {
+                if !within(to) || parent.contains_key(&to) {
+                    continue;
+                }
+                parent.insert(to, v);
+                if goal(to) {
+                    let mut path = vec![to, v];
+                    while !from.contains(&path[path.len() - 1]) {
+                        path.push(parent[&path[path.len() - 1]]);
+                    }
+                    path.reverse();
+                    return Some(path);
+                }
+                queue.push_back(to);
+            }
+        }
+        None
+    }
+
+    /// An accepting run shaped as a lasso, read back as the trace of atoms it
+    /// accepts; `None` when the language is empty
+    pub fn witness(&self) -> Option<Trace> {
+        let component = self.components().into_iter().find(|component| {
+            let looping = component.len() > 1 || self.edges[component[0]].contains(&component[0]);
+            looping
+                && self
+                    .accepting
+                    .iter()
+                    .all(|(_, states)| component.iter().any(|s| states.contains(s)))
+        })?;
+        let inside = |s: usize| component.contains(&s);
+        let root = component[0];
+        let mut prefix = match self.initial.contains(&root) {
+            true => Vec::new(),
+            false => self.path(&self.initial, |s| s == root, |_| true)?,
+        };
+        prefix.pop();
+        let loop_start = prefix.len();
+        prefix.push(root);
+        let mut current = root;
+        for (_, states) in &self.accepting {
+            if states.contains(&current) {
+                continue;
+            }
+            let segment = self.path(&[current], |s| states.contains(&s), inside)?;
+            current = *segment.last()?;
+            prefix.extend(&segment[1..]);
+        }
+        let segment = self.path(&[current], |s| s == root, inside)?;
+        prefix.extend(&segment[1..segment.len() - 1]);
+        Some(Trace {
+            states: prefix.iter().map(|s| self.states[*s].atoms()).collect(),
+            loop_start: Some(loop_start),
+        })
+    }
+}
+
+impl Display for Buchi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = |ids: &mut dyn Iterator<Item = usize>| {
+            let mut ids = ids.collect::<Vec<_>>();
+            ids.sort();
+            ids.iter().map(|id| format!("q{}", id)).collect::<Vec<_>>()
+        };
+        writeln!(f, "\tBüchi automaton for {}", self.formula)?;
+        writeln!(
+            f,
+            "\t\tinitial: {{{}}}",
+            names(&mut self.initial.iter().cloned()).join(", ")
+        )?;
+        for (id, state) in self.states.iter().enumerate() {
+            let mut old = state.old.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+            old.sort();
+            writeln!(
+                f,
+                "\t\tq{}: {{{}}} -> {{{}}}",
+                id,
+                old.join(", "),
+                names(&mut self.edges[id].iter().cloned()).join(", ")
+            )?;
+        }
+        for (i, (eventuality, states)) in self.accepting.iter().enumerate() {
+            writeln!(
+                f,
+                "\t\tF{} {}: {{{}}}",
+                i,
+                eventuality,
+                names(&mut states.iter().cloned()).join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Satisfiability or validity of an LTL formula, decided by the emptiness of
+/// the automaton for the formula or for its negation
+#[derive(Debug, Clone)]
+pub struct LtlCheck {
+    pub goal: Box<LogExpr>,
+    pub validity: bool,
+    pub size: usize,
+    pub witness: Option<Trace>,
+}
+
+impl LtlCheck {
+    pub fn new(goal: &LogExpr, validity: bool) -> Result<LtlCheck, TraceError> {
+        let buchi = match validity {
+            true => Buchi::new(&goal.negate())?,
+            false => Buchi::new(goal)?,
+        };
+        Ok(LtlCheck {
+            goal: Box::new(goal.clone()),
+            validity,
+            size: buchi.states.len(),
+            witness: buchi.witness(),
+        })
+    }
+}
+
+impl Display for LtlCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.validity {
+            true => writeln!(
+                f,
+                "\tBüchi automaton for the negation: {} states",
+                self.size
+            )?,
+            false => writeln!(f, "\tBüchi automaton: {} states", self.size)?,
+        }
+        match (&self.witness, self.validity) {
+            (None, false) => write!(f, "\t{} is unsatisfiable.", self.goal),
+            (None, true) => write!(f, "\t{} is valid.", self.goal),
+            (Some(trace), false) => {
+                writeln!(f, "\t{} is satisfiable. Witness:", self.goal)?;
+                write!(f, "{}", trace.to_string().trim_end())
+            }
+            (Some(trace), true) => {
+                writeln!(f, "\t{} is not valid. Counterexample:", self.goal)?;
+                write!(f, "{}", trace.to_string().trim_end())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        let mut lexer = Lexer::new(s, None);
+        lexer.temporal = true;
+        LogExpr::parse(&mut lexer).unwrap()
+    }
+
+    // The witness or counterexample, checked at its first state
+    fn check(goal: &str, validity: bool) -> Option<Trace> {
+        let check = LtlCheck::new(&expr(goal), validity).unwrap();
+        if let Some(trace) = &check.witness {
+            assert_eq!(trace.eval(&expr(goal)).unwrap()[0], !validity, "{}", trace);
+        }
+        check.witness
+    }
+
+    #[test]
+    fn satisfiable_formulas_have_lasso_witnesses() {
+        for goal in [
+            "p",
+            "(and (G (F p)) (G (F (not p))))",
+            "(U p q)",
+            "(and (X p) (not p))",
+            "(G (imp p (X (not p))))",
+        ] {
+            assert!(check(goal, false).is_some(), "{}", goal);
+        }
+    }
+
+    #[test]
+    fn contradictions_have_empty_languages() {
+        for goal in [
+            "(and p (not p))",
+            "(and (G p) (F (not p)))",
+            "(and (U p q) (G (not q)))",
+            "(and (X p) (X (not p)))",
+        ] {
+            assert!(check(goal, false).is_none(), "{}", goal);
+        }
+    }
+
+    #[test]
+    fn validity_is_emptiness_of_the_negation() {
+        for goal in [
+            "(imp (G p) (F p))",
+            "(imp (G p) (X p))",
+            "(imp (U p q) (F q))",
+            "(imp (G (F (G p))) (F (G p)))",
+            "(or (G (F p)) (F (G (not p))))",
+        ] {
+            assert!(check(goal, true).is_none(), "{}", goal);
+        }
+        for goal in [
+            "(imp (F p) (G p))",
+            "(imp (F p) p)",
+            "(imp (G (F p)) (F (G p)))",
+        ] {
+            assert!(check(goal, true).is_some(), "{}", goal);
+        }
+    }
+
+    #[test]
+    fn modal_formulas_are_refused() {
+        assert!(matches!(
+            LtlCheck::new(&expr("(box p)"), false),
+            Err(TraceError::NotTemporal(_))
+        ));
+    }
+}
@@ -0,0 +1,827 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::parser::*;
+
+#[derive(Debug, Clone)]
+pub enum CircuitError {
+    NotPropositional(Box<LogExpr>),
+}
+
+impl Display for CircuitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitError::NotPropositional(expr) => {
+                write!(f, "{} is not a propositional formula", expr)
+            }
+        }
+    }
+}
+
+/// Which gates a circuit is shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitForm {
+    Gates,
+    Nand,
+    Nor,
+    Aig,
+}
+
+impl Display for CircuitForm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitForm::Gates => write!(f, "gates"),
+            CircuitForm::Nand => write!(f, "nand"),
+            CircuitForm::Nor => write!(f, "nor"),
+            CircuitForm::Aig => write!(f, "aig"),
+        }
+    }
+}
+
+impl CircuitForm {
+    pub fn from_str(s: &str) -> Option<CircuitForm> {
+        match s {
+            "gates" => Some(CircuitForm::Gates),
+            "nand" => Some(CircuitForm::Nand),
+            "nor" => Some(CircuitForm::Nor),
+            "aig" => Some(CircuitForm::Aig),
+            _ => None,
+        }
+    }
+}
+
+/// A gate reads the gates before it, so the gate list is in topological
+/// order. Inputs of the commutative gates are kept sorted.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[allow(clippy::box_collection)]
+pub enum Gate {
+    Input(Box<String>),
+    Constant(bool),
+    Not(usize),
+    And(Vec<usize>),
+    Or(Vec<usize>),
+    Nand(Vec<usize>),
+    Nor(Vec<usize>),
+}
+
+impl Gate {
+    fn name(&self) -> &'static str {
+        match self {
+            Gate::Input(_) => "input",
+            Gate::Constant(_) => "constant",
+            Gate::Not(_) => "not",
+            Gate::And(_) => "and",
+            Gate::Or(_) => "or",
+            Gate::Nand(_) => "nand",
+            Gate::Nor(_) => "nor",
+        }
+    }
+
+    fn inputs(&self) -> Vec<usize> {
+        match self {
+            Gate::Input(_) | Gate::Constant(_) => Vec::new(),
+            Gate::Not(x) => vec![*x],
+            Gate::And(xs) | Gate::Or(xs) | Gate::Nand(xs) | Gate::Nor(xs) => xs.clone(),
+        }
+    }
+}
+
+/// A circuit as a DAG in which equal gates are built once, so shared
+/// subterms of the formula become shared gates
+#[derive(Debug, Clone)]
+pub struct Circuit {
+    pub gates: Vec<Gate>,
+    unique: HashMap<Gate, usize>,
+    pub output: usize,
+}
+
+impl Circuit {
+    fn new() -> Circuit {
+        Circuit {
+            gates: Vec::new(),
+            unique: HashMap::new(),
+            output: 0,
+        }
+    }
+
+    fn add(&mut self, gate: Gate) -> usize {
+        let gate = match gate {
+            Gate::And(xs) => Gate::And(normalized(xs)),
+            Gate::Or(xs) => Gate::Or(normalized(xs)),
+            Gate::Nand(xs) => Gate::Nand(normalized(xs)),
+            Gate::Nor(xs) => Gate::Nor(normalized(xs)),
+            gate => gate,
+        };
+        if let Some(id) = self.unique.get(&gate) {
+            return *id;
+        }
+        self.gates.push(gate.clone());
+        self.unique.insert(gate, self.gates.len() - 1);
+        self.gates.len() - 1
+    }
+
+    fn constant(&self, id: usize) -> Option<bool> {
+        match self.gates[id] {
+            Gate::Constant(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn not(&mut self, x: usize) -> usize {
+        match self.gates[x] {
+            Gate::Not(y) => y,
+            Gate::Constant(value) => self.add(Gate::Constant(!value)),
+            _ => self.add(Gate::Not(x)),
+        }
+    }
+
+    // A conjunction, or with `dual` a disjunction, with constants folded
+    fn junction(&mut self, xs: Vec<usize>, dual: bool) -> usize {
+        let (unit, zero) = (!dual, dual);
+        if xs.iter().any(|x| self.constant(*x) == Some(zero)) {
+            return self.add(Gate::Constant(zero));
+        }
+        let xs = normalized(
+            xs.into_iter()
+                .filter(|x| self.constant(*x) != Some(unit))
+                .collect(),
+        );
+        match xs.len() {
+            0 => self.add(Gate::Constant(unit)),
+            1 => xs[0],
+            _ if dual => self.add(Gate::Or(xs)),
+            _ => self.add(Gate::And(xs)),
+        }
+    }
+
+    pub fn from_expr(expr: &LogExpr) -> Result<Circuit, CircuitError> {
+        let mut circuit = Circuit::new();
+        circuit.output = circuit.build(expr)?;
+        Ok(circuit)
+    }
+
+    fn build(&mut self, expr: &LogExpr) -> Result<usize, CircuitError> {
+        match expr {
+            LogExpr::True => Ok(self.add(Gate::Constant(true))),
+            LogExpr::False => Ok(self.add(Gate::Constant(false))),
+            LogExpr::Atom(token) | LogExpr::Var(token) => {
+                Ok(self.add(Gate::Input(token.text.clone())))
+            }
+            LogExpr::UnaryOp(op, inner) if **op == Operator::Not => {
+                let x = self.build(inner)?;
+                Ok(self.not(x))
+            }
+            LogExpr::BinaryOp(op, exprs) => match **op {
+                Operator::And | Operator::Or => {
+                    let xs = exprs
+                        .iter()
+                        .map(|e| self.build(e))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(self.junction(xs, **op == Operator::Or))
+                }
+                Operator::Imp => match LogExpr::imp_parts(exprs) {
+                    Some((lhs, rhs)) => {
+                        let x = self.build(&lhs)?;
+                        let not_x = self.not(x);
+                        let y = self.build(&rhs)?;
+                        Ok(self.junction(vec![not_x, y], true))
+                    }
+                    None => match exprs.first() {
+                        Some(e) => self.build(e),
+                        None => Ok(self.add(Gate::Constant(true))),
+                    },
+                },
+                _ => Err(CircuitError::NotPropositional(Box::new(expr.clone()))),
+            },
+            _ => Err(CircuitError::NotPropositional(Box::new(expr.clone()))),
+        }
+    }
+
+    // A NAND, or with `dual` a NOR, reading a single inverter's input
+    // straight through instead of inverting twice
+    fn universal(&mut self, xs: Vec<usize>, dual: bool) -> usize {
+        if let [x] = xs[..] {
+            match &self.gates[x] {
+                Gate::Nand(ys) if !dual && ys.len() == 1 => return ys[0],
+                Gate::Nor(ys) if dual && ys.len() == 1 => return ys[0],
+                _ => {}
+            }
+        }
+        match dual {
+            false => self.add(Gate::Nand(xs)),
+            true => self.add(Gate::Nor(xs)),
+        }
+    }
+
+    /// The same circuit built from NAND gates only, or NOR gates with `dual`.
+    /// A one-input NAND or NOR is the gate with its inputs tied, an inverter.
+    pub fn universal_form(&self, dual: bool) -> Circuit {
+        let mut circuit = Circuit::new();
+        let mut map = vec![0; self.gates.len()];
+        for id in self.reachable() {
+            let xs = self.gates[id]
+                .inputs()
+                .iter()
+                .map(|x| map[*x])
+                .collect::<Vec<_>>();
+            map[id] = match &self.gates[id] {
+                Gate::Input(_) | Gate::Constant(_) => circuit.add(self.gates[id].clone()),
+                Gate::Not(_) => circuit.universal(xs, dual),
+                // The gate of the same polarity, inverted
+                Gate::And(_) | Gate::Or(_) if matches!(self.gates[id], Gate::Or(_)) == dual => {
+                    let inverted = circuit.universal(xs, dual);
+                    circuit.universal(vec![inverted], dual)
+                }
+                // De Morgan: the other polarity over inverted inputs
+                Gate::And(_) | Gate::Or(_) => {
+                    let inverted = xs
+                        .into_iter()
+                        .map(|x| circuit.universal(vec![x], dual))
+                        .collect();
+                    circuit.universal(inverted, dual)
+                }
+                Gate::Nand(_) => circuit.add(Gate::Nand(xs)),
+                Gate::Nor(_) => circuit.add(Gate::Nor(xs)),
+            };
+        }
+        circuit.output = map[self.output];
+        circuit
+    }
+
+    // The gates the output depends on, in topological order
+    fn reachable(&self) -> Vec<usize> {
+        let mut needed = vec![false; self.gates.len()];
+        needed[self.output] = true;
+        for id in (0..self.gates.len()).rev() {
+            if needed[id] {
+                for x in self.gates[id].inputs() {
+                    needed[x] = true;
+                }
+            }
+        }
+        (0..self.gates.len()).filter(|id| needed[*id]).collect()
+    }
+
+    fn is_gate(&self, id: usize) -> bool {
+        !matches!(self.gates[id], Gate::Input(_) | Gate::Constant(_))
+    }
+
+    pub fn gate_count(&self) -> usize {
+        self.reachable()
+            .into_iter()
+            .filter(|id| self.is_gate(*id))
+            .count()
+    }
+
+    /// Gates on the longest path from an input to the output
+    pub fn depth(&self) -> usize {
+        let mut depth = vec![0; self.gates.len()];
+        for id in self.reachable() {
+            if self.is_gate(id) {
+                depth[id] = 1 + self.gates[id]
+                    .inputs()
+                    .iter()
+                    .map(|x| depth[*x])
+                    .max()
+                    .unwrap_or(0);
+            }
+        }
+        depth[self.output]
+    }
+
+    /// Gates read by more than one other gate
+    pub fn shared(&self) -> usize {
+        let mut fanout = vec![0; self.gates.len()];
+        for id in self.reachable() {
+            for x in self.gates[id].inputs() {
+                fanout[x] += 1;
+            }
+        }
+        (0..self.gates.len())
+            .filter(|id| self.is_gate(*id) && fanout[*id] > 1)
+            .count()
+    }
+
+    fn summary(&self) -> String {
+        let mut kinds: Vec<(&str, usize)> = Vec::new();
+        for id in self.reachable().into_iter().filter(|id| self.is_gate(*id)) {
+            let name = self.gates[id].name();
+            match kinds.iter_mut().find(|(kind, _)| *kind == name) {
+                Some((_, count)) => *count += 1,
+                None => kinds.push((name, 1)),
+            }
+        }
+        let kinds = kinds
+            .iter()
+            .map(|(kind, count)| format!("{}: {}", kind, count))
+            .collect::<Vec<_>>();
+        format!(
+            "gates: {} ({}), depth: {}, shared: {}",
+            self.gate_count(),
+            kinds.join(", "),
+            self.depth(),
+            self.shared()
+        )
+    }
+}
+
+fn normalized(mut xs: Vec<usize>) -> Vec<usize> {
+    xs.sort();
+    xs.dedup();
+    xs
+}
+
+impl Display for Circuit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names: HashMap<usize, String> = HashMap::new();
+        let mut count = 0;
+        for id in self.reachable() {
+            let name = match &self.gates[id] {
+                Gate::Input(name) => name.to_string(),
+                Gate::Constant(value) => value.to_string(),
+                gate => {
+                    count += 1;
+                    let name = format!("g{}", count);
+                    let inputs = gate
+                        .inputs()
+                        .iter()
+                        .map(|x| names[x].clone())
+                        .collect::<Vec<_>>();
+                    writeln!(f, "\t\t{} = ({} {})", name, gate.name(), inputs.join(" "))?;
+                    name
+                }
+            };
+            names.insert(id, name);
+        }
+        writeln!(f, "\t\toutput: {}", names[&self.output])?;
+        write!(f, "\t\t{}", self.summary())
+    }
+}
+
+// AIG literals are twice the node plus one when complemented; node 0 is
+// the constant false, so literal 1 is true
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AigNode {
+    False,
+    Input(usize),
+    And(usize, usize),
+}
+
+/// An and-inverter graph: two-input ANDs with complemented edges, hashed
+/// structurally so equal nodes are built once
+#[derive(Debug, Clone)]
+pub struct Aig {
+    pub inputs: Vec<String>,
+    nodes: Vec<AigNode>,
+    unique: HashMap<(usize, usize), usize>,
+    pub output: usize,
+    rewrite: bool,
+}
+
+impl Aig {
+    fn new(inputs: Vec<String>, rewrite: bool) -> Aig {
+        let mut nodes = vec![AigNode::False];
+        nodes.extend((0..inputs.len()).map(AigNode::Input));
+        Aig {
+            inputs,
+            nodes,
+            unique: HashMap::new(),
+            output: 0,
+            rewrite,
+        }
+    }
+
+    pub fn from_expr(expr: &LogExpr) -> Result<Aig, CircuitError> {
+        let mut inputs = Vec::new();
+        collect_inputs(expr, &mut inputs);
+        let mut aig = Aig::new(inputs, false);
+        aig.output = aig.build(expr)?;
+        Ok(aig)
+    }
+
+    fn build(&mut self, expr: &LogExpr) -> Result<usize, CircuitError> {
+        match expr {
+            LogExpr::True => Ok(1),
+            LogExpr::False => Ok(0),
+            LogExpr::Atom(token) | LogExpr::Var(token) => {
+                let i = self.inputs.iter().position(|name| **name == *token.text);
+                Ok(2 * (1 + i.unwrap_or(0)))
+            }
+            LogExpr::UnaryOp(op, inner) if **op == Operator::Not => Ok(self.build(inner)? ^ 1),
+            LogExpr::BinaryOp(op, exprs) => match **op {
+                Operator::And | Operator::Or => {
+                    let flip = (**op == Operator::Or) as usize;
+                    let xs = exprs
+                        .iter()
+                        .map(|e| Ok(self.build(e)? ^ flip))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(self.balanced(&xs) ^ flip)
+                }
+                Operator::Imp => match LogExpr::imp_parts(exprs) {
+                    Some((lhs, rhs)) => {
+                        let (x, y) = (self.build(&lhs)?, self.build(&rhs)?);
+                        Ok(self.and(x, y ^ 1) ^ 1)
+                    }
+                    None => match exprs.first() {
+                        Some(e) => self.build(e),
+                        None => Ok(1),
+                    },
+                },
+                _ => Err(CircuitError::NotPropositional(Box::new(expr.clone()))),
+            },
+            _ => Err(CircuitError::NotPropositional(Box::new(expr.clone()))),
+        }
+    }
+
+    // A conjunction as a balanced tree, to keep the depth logarithmic
+    fn balanced(&mut self, xs: &[usize]) -> usize {
+        match xs {
+            [] => 1,
+            [x] => *x,
+            _ => {
+                let (lhs, rhs) = xs.split_at(xs.len() / 2);
+                let (x, y) = (self.balanced(lhs), self.balanced(rhs));
+                self.and(x, y)
+            }
+        }
+    }
+
+    // The inputs of a node literal that is an uncomplemented AND
+    fn conjuncts(&self, x: usize) -> Option<(usize, usize)> {
+        match self.nodes[x / 2] {
+            AigNode::And(a, b) if x.is_multiple_of(2) => Some((a, b)),
+            _ => None,
+        }
+    }
+
+    // The inputs of a node literal that is a complemented AND
+    fn disjuncts(&self, x: usize) -> Option<(usize, usize)> {
+        match self.nodes[x / 2] {
+            AigNode::And(a, b) if x % 2 == 1 => Some((a, b)),
+            _ => None,
+        }
+    }
+
+    // The two-level rules of local AIG minimization, trying `x` as the
+    // larger side; `None` when no rule applies
+    fn rewritten(&mut self, x: usize, y: usize) -> Option<usize> {
+        if let Some((a, b)) = self.conjuncts(x) {
+            // Contradiction and idempotence
+            if y == a ^ 1 || y == b ^ 1 {
+                return Some(0);
+            }
+            if y == a || y == b {
+                return Some(x);
+            }
+            if let Some((c, d)) = self.conjuncts(y) {
+                if [c, d].iter().any(|l| *l == a ^ 1 || *l == b ^ 1) {
+                    return Some(0);
+                }
+            }
+        }
+        if let Some((a, b)) = self.disjuncts(x) {
+            // Subsumption and substitution
+            if y == a ^ 1 || y == b ^ 1 {
+                return Some(y);
+            }
+            if y == a {
+                return Some(self.and(y, b ^ 1));
+            }
+            if y == b {
+                return Some(self.and(y, a ^ 1));
+            }
+            // Resolution: (a | b) & (a | !b) over complemented inputs
+            if let Some((c, d)) = self.disjuncts(y) {
+                for (p, q, r, s) in [(a, b, c, d), (a, b, d, c), (b, a, c, d), (b, a, d, c)] {
+                    if p == r && q == s ^ 1 {
+                        return Some(p ^ 1);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn and(&mut self, x: usize, y: usize) -> usize {
+        let (x, y) = (x.max(y), x.min(y));
+        match (x, y) {
+            (_, 0) => return 0,
+            (_, 1) => return x,
+            _ if x == y => return x,
+            _ if x == y ^ 1 => return 0,
+            _ => {}
+        }
+        if self.rewrite {
+            if let Some(z) = self.rewritten(x, y).or_else(|| self.rewritten(y, x)) {
+                return z;
+            }
+        }
+        if let Some(node) = self.unique.get(&(x, y)) {
+            return 2 * node;
+        }
+        self.nodes.push(AigNode::And(x, y));
+        self.unique.insert((x, y), self.nodes.len() - 1);
+        2 * (self.nodes.len() - 1)
+    }
+
+    /// Rebuilds the graph with the rewriting rules on, again and again
+    /// while it keeps shrinking. Returns the graph and the passes it took.
+    pub fn optimized(&self) -> (Aig, usize) {
+        let mut best = self.clone();
+        let mut passes = 0;
+        loop {
+            let mut aig = Aig::new(best.inputs.clone(), true);
+            let mut map = vec![0; best.nodes.len()];
+            for node in best.reachable() {
+                map[node] = match best.nodes[node] {
+                    AigNode::False => 0,
+                    AigNode::Input(i) => 2 * (1 + i),
+                    AigNode::And(x, y) => {
+                        let lit = |l: usize| map[l / 2] ^ (l % 2);
+                        aig.and(lit(x), lit(y))
+                    }
+                };
+            }
+            aig.output = map[best.output / 2] ^ (best.output % 2);
+            passes += 1;
+            if aig.size() >= best.size() {
+                return (best, passes);
+            }
+            best = aig;
+        }
+    }
+
+    fn reachable(&self) -> Vec<usize> {
+        let mut needed = vec![false; self.nodes.len()];
+        needed[self.output / 2] = true;
+        for node in (0..self.nodes.len()).rev() {
+            if let (true, AigNode::And(x, y)) = (needed[node], self.nodes[node]) {
+                needed[x / 2] = true;
+                needed[y / 2] = true;
+            }
+        }
+        (0..self.nodes.len()).filter(|node| needed[*node]).collect()
+    }
+
+    /// AND nodes the output depends on
+    pub fn size(&self) -> usize {
+        self.reachable()
+            .into_iter()
+            .filter(|node| matches!(self.nodes[*node], AigNode::And(..)))
+            .count()
+    }
+
+    pub fn depth(&self) -> usize {
+        let mut depth = vec![0; self.nodes.len()];
+        for node in self.reachable() {
+            if let AigNode::And(x, y) = self.nodes[node] {
+                depth[node] = 1 + depth[x / 2].max(depth[y / 2]);
+            }
+        }
+        depth[self.output / 2]
+    }
+}
+
+fn collect_inputs(expr: &LogExpr, inputs: &mut Vec<String>) {
+    match expr {
+        LogExpr::Atom(token) | LogExpr::Var(token) if !inputs.contains(&token.text) => {
+            inputs.push(token.text.to_string());
+        }
+        LogExpr::UnaryOp(_, inner) => collect_inputs(inner, inputs),
+        LogExpr::BinaryOp(_, exprs) => exprs.iter().for_each(|e| collect_inputs(e, inputs)),
+        _ => {}
+    }
+}
+
+impl Display for Aig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut names: HashMap<usize, String> = HashMap::new();
+        let mut count = 0;
+        let literal = |names: &HashMap<usize, String>, l: usize| match (self.nodes[l / 2], l % 2) {
+            (AigNode::False, 0) => "false".to_string(),
+            (AigNode::False, _) => "true".to_string(),
+            (_, 0) => names[&(l / 2)].clone(),
+            (_, _) => format!("(not {})", names[&(l / 2)]),
+        };
+        for node in self.reachable() {
+            let name = match self.nodes[node] {
+                AigNode::False => "false".to_string(),
+                AigNode::Input(i) => self.inputs[i].clone(),
+                AigNode::And(x, y) => {
+                    count += 1;
+                    let name = format!("n{}", count);
+                    writeln!(
+                        f,
+                        "\t\t{} = (and {} {})",
+                        name,
+                        literal(&names, y),
+                        literal(&names, x)
+                    )?;
+                    name
+                }
+            };
+            names.insert(node, name);
+        }
+        writeln!(f, "\t\toutput: {}", literal(&names, self.output))?;
+        write!(f, "\t\tand nodes: {}, depth: {}", self.size(), self.depth())
+    }
+}
+
+/// A formula's circuit in the chosen form, with the sizes of the others
+#[derive(Debug, Clone)]
+pub struct CircuitReport {
+    pub form: CircuitForm,
+    pub expr: Box<LogExpr>,
+    pub circuit: Circuit,
+    pub aig: Aig,
+}
+
+impl CircuitReport {
+    pub fn new(form: CircuitForm, expr: &LogExpr) -> Result<CircuitReport, CircuitError> {
+        Ok(CircuitReport {
+            form,
+            expr: Box::new(expr.clone()),
+            circuit: Circuit::from_expr(expr)?,
+            aig: Aig::from_expr(expr)?,
+        })
+    }
+}
+
+impl Display for CircuitReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (optimized, passes) = self.aig.optimized();
+        match self.form {
+            CircuitForm::Gates => {
+                writeln!(f, "\tCircuit for {}:", self.expr)?;
+                writeln!(f, "{}", self.circuit)?;
+                for (name, dual) in [("NAND-only", false), ("NOR-only", true)] {
+                    let universal = self.circuit.universal_form(dual);
+                    writeln!(
+                        f,
+                        "\t\t{}: {} gates, depth {}",
+                        name,
+                        universal.gate_count(),
+                        universal.depth()
+                    )?;
+                }
+                write!(
+                    f,
+                    "\t\tAIG: {} and nodes, depth {}; rewritten: {} and nodes, depth {}",
+                    self.aig.size(),
+                    self.aig.depth(),
+                    optimized.size(),
+                    optimized.depth()
+                )
+            }
+            CircuitForm::Nand | CircuitForm::Nor => {
+                let dual = self.form == CircuitForm::Nor;
+                let name = if dual { "NOR-only" } else { "NAND-only" };
+                writeln!(f, "\t{} circuit for {}:", name, self.expr)?;
+                write!(f, "{}", self.circuit.universal_form(dual))
+            }
+            CircuitForm::Aig => {
+                writeln!(f, "\tAnd-inverter graph for {}:", self.expr)?;
+                writeln!(
+                    f,
+                    "\t\tbuilt: {} and nodes, depth {}; rewriting passes: {}",
+                    self.aig.size(),
+                    self.aig.depth(),
+                    passes
+                )?;
+                write!(f, "{}", optimized)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+    use crate::manyvalued::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn simulate(circuit: &Circuit, inputs: &HashMap<String, bool>) -> bool {
+        let mut values: Vec<bool> = Vec::new();
+        for gate in &circuit.gates {
+            let xs = gate.inputs().iter().map(|x| values[*x]).collect::<Vec<_>>();
+            values.push(match gate {
+                Gate::Input(name) => inputs[name.as_str()],
+                Gate::Constant(value) => *value,
+                Gate::Not(_) => !xs[0],
+                Gate::And(_) => xs.iter().all(|x| *x),
+                Gate::Or(_) => xs.iter().any(|x| *x),
+                Gate::Nand(_) => !xs.iter().all(|x| *x),
+                Gate::Nor(_) => !xs.iter().any(|x| *x),
+            });
+        }
+        values[circuit.output]
+    }
+
+    fn simulate_aig(aig: &Aig, inputs: &HashMap<String, bool>) -> bool {
+        let mut values: Vec<bool> = Vec::new();
+        for node in &aig.nodes {
+            let literal = |values: &[bool], l: usize| values[l / 2] ^ (l % 2 == 1);
+            values.push(match node {
+                AigNode::False => false,
+                AigNode::Input(i) => inputs[&aig.inputs[*i]],
+                AigNode::And(x, y) => literal(&values, *x) && literal(&values, *y),
+            });
+        }
+        values[aig.output / 2] ^ (aig.output % 2 == 1)
+    }
+
+    const FORMULAS: [&str; 6] = [
+        "(or (and a b) (not (and a b)))",
+        "(imp (and p q) (or r (not p)))",
+        "(and (or a b) (or a (not b)))",
+        "(or (and a (not b)) (and (not a) b) c)",
+        "(not (and p (or q (imp r true))))",
+        "(and a b c d e g h i)",
+    ];
+
+    #[test]
+    fn every_form_computes_the_formula() {
+        for formula in FORMULAS {
+            let formula = expr(formula);
+            let report = CircuitReport::new(CircuitForm::Gates, &formula).unwrap();
+            let forms = [
+                report.circuit.clone(),
+                report.circuit.universal_form(false),
+                report.circuit.universal_form(true),
+            ];
+            let (optimized, _) = report.aig.optimized();
+            let atoms = report.aig.inputs.clone();
+            for row in 0..1u32 << atoms.len() {
+                let inputs = atoms
+                    .iter()
+                    .enumerate()
+                    .map(|(i, atom)| (atom.clone(), row >> i & 1 == 1))
+                    .collect::<HashMap<_, _>>();
+                let values = inputs
+                    .iter()
+                    .map(|(atom, value)| (atom.clone(), Value(*value as usize)))
+                    .collect();
+                let expected = Semantics::Classical.eval(&formula, &values).unwrap() == Value(1);
+                for circuit in &forms {
+                    assert_eq!(simulate(circuit, &inputs), expected, "{}", formula);
+                }
+                assert_eq!(simulate_aig(&report.aig, &inputs), expected, "{}", formula);
+                assert_eq!(simulate_aig(&optimized, &inputs), expected, "{}", formula);
+            }
+        }
+    }
+
+    #[test]
+    fn universal_forms_use_one_kind_of_gate() {
+        let circuit = Circuit::from_expr(&expr(FORMULAS[1])).unwrap();
+        let nand = circuit.universal_form(false);
+        assert!(nand.reachable().iter().all(|id| {
+            matches!(
+                nand.gates[*id],
+                Gate::Nand(_) | Gate::Input(_) | Gate::Constant(_)
+            )
+        }));
+        let nor = circuit.universal_form(true);
+        assert!(nor.reachable().iter().all(|id| {
+            matches!(
+                nor.gates[*id],
+                Gate::Nor(_) | Gate::Input(_) | Gate::Constant(_)
+            )
+        }));
+    }
+
+    #[test]
+    fn equal_subterms_share_a_gate() {
+        let circuit = Circuit::from_expr(&expr(FORMULAS[0])).unwrap();
+        assert_eq!(circuit.gate_count(), 3);
+        assert_eq!(circuit.shared(), 1);
+        let circuit = Circuit::from_expr(&expr("(and a true (not (not b)))")).unwrap();
+        assert_eq!(circuit.gate_count(), 1);
+        assert_eq!(circuit.depth(), 1);
+    }
+
+    #[test]
+    fn rewriting_shrinks_the_graph() {
+        let aig = Aig::from_expr(&expr(FORMULAS[2])).unwrap();
+        assert_eq!(aig.size(), 3);
+        let (optimized, passes) = aig.optimized();
+        assert_eq!(optimized.size(), 0);
+        assert!(passes >= 1);
+        let aig = Aig::from_expr(&expr(FORMULAS[5])).unwrap();
+        assert_eq!((aig.size(), aig.depth()), (7, 3));
+    }
+
+    #[test]
+    fn non_propositional_formulas_are_refused() {
+        assert!(matches!(
+            CircuitReport::new(CircuitForm::Aig, &expr("(box p)")),
+            Err(CircuitError::NotPropositional(_))
+        ));
+        assert_eq!(CircuitForm::from_str("nor"), Some(CircuitForm::Nor));
+        assert_eq!(CircuitForm::from_str("xor"), None);
+    }
+}
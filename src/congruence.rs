@@ -0,0 +1,349 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt::Display;
+
+use crate::parser::*;
+
+#[derive(Debug, Clone)]
+pub enum CongruenceError {
+    NotALiteral(Box<LogExpr>),
+}
+
+impl Display for CongruenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CongruenceError::NotALiteral(expr) => {
+                write!(f, "{} is not an equality or disequality", expr)
+            }
+        }
+    }
+}
+
+/// An equality, or with `positive` false a disequality, between two terms
+#[derive(Debug, Clone)]
+pub struct EqLiteral {
+    pub lhs: Box<LogExpr>,
+    pub rhs: Box<LogExpr>,
+    pub positive: bool,
+}
+
+impl Display for EqLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = if self.positive { "=" } else { "!=" };
+        write!(f, "{} {} {}", self.lhs, op, self.rhs)
+    }
+}
+
+impl EqLiteral {
+    // Flattens a conjunction of (possibly negated) equalities
+    fn collect(
+        expr: &LogExpr,
+        positive: bool,
+        out: &mut Vec<EqLiteral>,
+    ) -> Result<(), CongruenceError> {
+        match expr {
+            LogExpr::True if positive => Ok(()),
+            LogExpr::Pred(name, args) if *name.text == "=" && args.len() == 2 => {
+                out.push(EqLiteral {
+                    lhs: args[0].clone(),
+                    rhs: args[1].clone(),
+                    positive,
+                });
+                Ok(())
+            }
+            LogExpr::UnaryOp(op, inner) if **op == Operator::Not => {
+                EqLiteral::collect(inner, !positive, out)
+            }
+            LogExpr::BinaryOp(op, exprs) if **op == Operator::And && positive => exprs
+                .iter()
+                .try_for_each(|e| EqLiteral::collect(e, positive, out)),
+            _ => Err(CongruenceError::NotALiteral(Box::new(expr.clone()))),
+        }
+    }
+}
+
+/// Why two terms were merged
+#[derive(Debug, Clone)]
+enum Reason {
+    Given(usize),
+    Congruence(usize, usize),
+}
+
+/// Congruence closure over the subterms of a set of literals, with a proof
+/// forest recording each merge so conflicts can be explained
+#[derive(Debug, Clone)]
+#[allow(clippy::vec_box)]
+pub struct CongruenceClosure {
+    pub literals: Vec<EqLiteral>,
+    terms: Vec<Box<LogExpr>>,
+    args: Vec<Vec<usize>>,
+    index: HashMap<Box<LogExpr>, usize>,
+    find: Vec<usize>,
+    members: Vec<Vec<usize>>,
+    uses: Vec<Vec<usize>>,
+    forest: Vec<Vec<(usize, Reason)>>,
+}
+
+/// The outcome of deciding a conjunction of literals
+#[derive(Debug, Clone)]
+#[allow(clippy::vec_box)]
+pub enum Decision {
+    Satisfiable(Vec<Vec<Box<LogExpr>>>),
+    Unsatisfiable(Vec<EqLiteral>),
+}
+
+impl Display for Decision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Decision::Satisfiable(classes) => {
+                write!(f, "\tSatisfiable. Equivalence classes:")?;
+                for class in classes {
+                    let class = class.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+                    write!(f, "\n\t\t{{{}}}", class.join(", "))?;
+                }
+                Ok(())
+            }
+            Decision::Unsatisfiable(conflict) => {
+                write!(f, "\tUnsatisfiable. Conflicting literals:")?;
+                for literal in conflict {
+                    write!(f, "\n\t\t{}", literal)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl CongruenceClosure {
+    pub fn new(formulas: &[Box<LogExpr>]) -> Result<CongruenceClosure, CongruenceError> {
+        let mut literals = Vec::new();
+        for formula in formulas {
+            EqLiteral::collect(formula, true, &mut literals)?;
+        }
+        let mut closure = CongruenceClosure {
+            literals: Vec::new(),
+            terms: Vec::new(),
+            args: Vec::new(),
+            index: HashMap::new(),
+            find: Vec::new(),
+            members: Vec::new(),
+            uses: Vec::new(),
+            forest: Vec::new(),
+        };
+        for literal in &literals {
+            closure.intern(&literal.lhs);
+            closure.intern(&literal.rhs);
+        }
+        closure.literals = literals;
+        Ok(closure)
+    }
+
+    // Adds a term and its subterms to the graph, returning its node
+    fn intern(&mut self, term: &LogExpr) -> usize {
+        if let Some(node) = self.index.get(term) {
+            return *node;
+        }
+        let args = match term {
+            LogExpr::Func(_, args) => args.iter().map(|arg| self.intern(arg)).collect(),
+            _ => Vec::new(),
+        };
+        let node = self.terms.len();
+        for arg in &args {
+            let rep = self.find[*arg];
+            self.uses[rep].push(node);
+        }
+        self.terms.push(Box::new(term.clone()));
+        self.args.push(args);
+        self.index.insert(Box::new(term.clone()), node);
+        self.find.push(node);
+        self.members.push(vec![node]);
+        self.uses.push(Vec::new());
+        self.forest.push(Vec::new());
+        node
+    }
+
+    fn congruent(&self, a: usize, b: usize) -> bool {
+        match (&*self.terms[a], &*self.terms[b]) {
+            (LogExpr::Func(f, _), LogExpr::Func(g, _)) => {
+                f.text == g.text
+                    && self.args[a].len() == self.args[b].len()
+                    && self.args[a]
+                        .iter()
+                        .zip(&self.args[b])
+                        .all(|(x, y)| self.find[*x] == self.find[*y])
+            }
+            _ => false,
+        }
+    }
+
+    fn merge(&mut self, a: usize, b: usize, reason: Reason) {
+        let mut pending = vec![(a, b, reason)];
+        while let Some((a, b, reason)) = pending.pop() {
+            let (ra, rb) = (self.find[a], self.find[b]);
+            if ra == rb {
+                continue;
+            }
+            self.forest[a].push((b, reason.clone()));
+            self.forest[b].push((a, reason));
+
+            // Relabel the smaller class, then look for newly congruent parents
+            let (small, large) = if self.members[ra].len() < self.members[rb].len() {
+                (ra, rb)
+            } else {
+                (rb, ra)
+            };
+            let (small_uses, large_uses) = (self.uses[small].clone(), self.uses[large].clone());
+            for member in std::mem::take(&mut self.members[small]) {
+                self.find[member] = large;
+                self.members[large].push(member);
+            }
+            self.uses[small].clear();
+            self.uses[large].extend(&small_uses);
+            for p in &small_uses {
+                for q in &large_uses {
+                    if self.find[*p] != self.find[*q] && self.congruent(*p, *q) {
+                        pending.push((*p, *q, Reason::Congruence(*p, *q)));
+                    }
+                }
+            }
+        }
+    }
+
+    // The given equalities on the unique forest path between two merged nodes
+    fn explain(&self, a: usize, b: usize, out: &mut BTreeSet<usize>) {
+        let mut previous: HashMap<usize, (usize, &Reason)> = HashMap::new();
+        let mut queue = VecDeque::from([a]);
+        while let Some(node) = queue.pop_front() {
+            if node == b {
+                break;
+            }
+            for (next, reason) in &self.forest[node] {
+                if *next != a && !previous.contains_key(next) {
+                    previous.insert(*next, (node, reason));
+                    queue.push_back(*next);
+                }
+            }
+        }
+        let mut node = b;
+        while node != a {
+            let (from, reason) = previous[&node];
+            match reason {
+                Reason::Given(i) => {
+                    out.insert(*i);
+                }
+                Reason::Congruence(p, q) => {
+                    for (x, y) in self.args[*p].iter().zip(&self.args[*q]) {
+                        self.explain(*x, *y, out);
+                    }
+                }
+            }
+            node = from;
+        }
+    }
+
+    /// Merges the equalities, then checks every disequality against the
+    /// resulting classes
+    pub fn decide(&mut self) -> Decision {
+        for i in 0..self.literals.len() {
+            if self.literals[i].positive {
+                let (a, b) = (
+                    self.index[&self.literals[i].lhs],
+                    self.index[&self.literals[i].rhs],
+                );
+                self.merge(a, b, Reason::Given(i));
+            }
+        }
+        for (i, literal) in self.literals.iter().enumerate() {
+            let (a, b) = (self.index[&literal.lhs], self.index[&literal.rhs]);
+            if !literal.positive && self.find[a] == self.find[b] {
+                let mut used = BTreeSet::new();
+                self.explain(a, b, &mut used);
+                used.insert(i);
+                let conflict = used.iter().map(|j| self.literals[*j].clone()).collect();
+                return Decision::Unsatisfiable(conflict);
+            }
+        }
+        let classes = self
+            .members
+            .iter()
+            .filter(|members| !members.is_empty())
+            .map(|members| members.iter().map(|m| self.terms[*m].clone()).collect())
+            .collect();
+        Decision::Satisfiable(classes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn decide(formulas: &[&str]) -> Decision {
+        let formulas = formulas.iter().map(|f| expr(f)).collect::<Vec<_>>();
+        CongruenceClosure::new(&formulas).unwrap().decide()
+    }
+
+    fn conflict(formulas: &[&str]) -> Vec<String> {
+        match decide(formulas) {
+            Decision::Unsatisfiable(conflict) => conflict.iter().map(|l| l.to_string()).collect(),
+            other => panic!("decided as {}", other),
+        }
+    }
+
+    #[test]
+    fn congruence_closes_over_function_applications() {
+        let conflict = conflict(&[
+            "(= (f (f (f a))) a)",
+            "(= (f (f (f (f (f a))))) a)",
+            "(not (= (f a) a))",
+        ]);
+        assert_eq!(conflict.len(), 3);
+    }
+
+    #[test]
+    fn conflicts_keep_only_the_literals_used() {
+        assert_eq!(
+            conflict(&["(= a b)", "(= c d)", "(not (= (f a) (f b)))"]),
+            ["a = b", "(f a) != (f b)"]
+        );
+        assert_eq!(
+            conflict(&["(and (= a b) (= b c))", "(not (= a c))"]),
+            ["a = b", "b = c", "a != c"]
+        );
+    }
+
+    #[test]
+    fn consistent_literals_report_their_classes() {
+        match decide(&["(= a b)", "(not (= (f a) c))"]) {
+            Decision::Satisfiable(classes) => {
+                let mut sizes = classes.iter().map(|c| c.len()).collect::<Vec<_>>();
+                sizes.sort();
+                assert_eq!(sizes, [1, 1, 2]);
+            }
+            other => panic!("decided as {}", other),
+        }
+        assert!(matches!(
+            decide(&["(= (g a b) c)", "(not (= (g b a) c))"]),
+            Decision::Satisfiable(_)
+        ));
+    }
+
+    #[test]
+    fn only_conjunctions_of_literals_are_accepted() {
+        for formula in [
+            "(or (= a b) (= b c))",
+            "(P a)",
+            "(not (and (= a b) (= b c)))",
+        ] {
+            assert!(matches!(
+                CongruenceClosure::new(&[expr(formula)]),
+                Err(CongruenceError::NotALiteral(_))
+            ));
+        }
+    }
+}
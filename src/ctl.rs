@@ -0,0 +1,654 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt::Display;
+
+use crate::lexer::*;
+use crate::ltl::*;
+use crate::parser::*;
+use crate::resolution::*;
+
+// Transitions are found by trying every pair of assignments
+const VARIABLE_LIMIT: usize = 10;
+
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
+pub enum SystemError {
+    Unreadable(Box<String>),
+    ExpectedSection(Box<Token>),
+    ExpectedVariable(Box<Token>),
+    ExpectedExpression(Box<Loc>),
+    TooManyVariables(usize),
+    UnknownVariable(Box<String>),
+    NotPropositional(Box<LogExpr>),
+    NoInitialState,
+    NotCtl(Box<LogExpr>),
+}
+
+impl Display for SystemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SystemError::Unreadable(path) => write!(f, "cannot read {}", path),
+            SystemError::ExpectedSection(token) => write!(
+                f,
+                "{}: expected `vars`, `init` or `trans`, found `{}`",
+                token.loc, token.text
+            ),
+            SystemError::ExpectedVariable(token) => {
+                write!(
+                    f,
+                    "{}: expected a state variable, found `{}`",
+                    token.loc, token.text
+                )
+            }
+            SystemError::ExpectedExpression(loc) => write!(f, "{}: expected an expression", loc),
+            SystemError::TooManyVariables(n) => write!(
+                f,
+                "{} state variables is more than the limit of {}",
+                n, VARIABLE_LIMIT
+            ),
+            SystemError::UnknownVariable(name) => write!(f, "{} is not a state variable", name),
+            SystemError::NotPropositional(expr) => {
+                write!(f, "{} is not a propositional formula", expr)
+            }
+            SystemError::NoInitialState => write!(f, "no state satisfies the initial condition"),
+            SystemError::NotCtl(expr) => write!(f, "{} is not a CTL formula", expr),
+        }
+    }
+}
+
+/// A finite transition system over boolean state variables. A state is
+/// initial when it satisfies every `init` condition, and each `trans`
+/// condition is one alternative step, relating the current values to the
+/// next ones, written primed (`x'`).
+#[derive(Debug, Clone)]
+#[allow(clippy::vec_box)]
+pub struct TransitionSystem {
+    pub variables: Vec<Box<String>>,
+    pub init: Vec<Box<LogExpr>>,
+    pub trans: Vec<Box<LogExpr>>,
+}
+
+impl TransitionSystem {
+    /// Reads `vars (x y ...)`, `init expr` and `trans expr` sections in any
+    /// order; sections may repeat
+    pub fn parse(lexer: &mut Lexer) -> Result<TransitionSystem, SystemError> {
+        let mut system = TransitionSystem {
+            variables: Vec::new(),
+            init: Vec::new(),
+            trans: Vec::new(),
+        };
+        loop {
+            let token = lexer.next_token();
+            match (&*token.kind, token.text.as_str()) {
+                (TokenKind::End, _) => break,
+                (TokenKind::String, "vars") => {
+                    let open = lexer.next_token();
+                    if *open.kind != TokenKind::LParen {
+                        return Err(SystemError::ExpectedVariable(open));
+                    }
+                    loop {
+                        let token = lexer.next_token();
+                        match *token.kind {
+                            TokenKind::RParen => break,
+                            TokenKind::String if !token.text.ends_with('\'') => {
+                                if !system.variables.contains(&token.text) {
+                                    system.variables.push(token.text);
+                                }
+                            }
+                            _ => return Err(SystemError::ExpectedVariable(token)),
+                        }
+                    }
+                }
+                (TokenKind::String, "init" | "trans") => {
+                    let loc = lexer.loc();
+                    let expr = LogExpr::parse(lexer).ok_or(SystemError::ExpectedExpression(loc))?;
+                    match token.text.as_str() {
+                        "init" => system.init.push(expr),
+                        _ => system.trans.push(expr),
+                    }
+                }
+                _ => return Err(SystemError::ExpectedSection(token)),
+            }
+        }
+        if system.variables.len() > VARIABLE_LIMIT {
+            return Err(SystemError::TooManyVariables(system.variables.len()));
+        }
+        Ok(system)
+    }
+
+    pub fn load(file_path: &str) -> Result<TransitionSystem, SystemError> {
+        let input = std::fs::read_to_string(file_path)
+            .map_err(|_| SystemError::Unreadable(Box::new(file_path.to_string())))?;
+        TransitionSystem::parse(&mut Lexer::new(&input, Some(file_path)))
+    }
+
+    // The state with the given index, one bit per variable
+    fn state(&self, index: usize) -> Vec<bool> {
+        (0..self.variables.len())
+            .map(|i| index & (1 << i) != 0)
+            .collect()
+    }
+
+    fn valuation(&self, now: &[bool], next: &[bool]) -> HashMap<String, bool> {
+        let mut valuation = HashMap::new();
+        for (i, var) in self.variables.iter().enumerate() {
+            valuation.insert(var.to_string(), now[i]);
+            valuation.insert(format!("{}'", var), next[i]);
+        }
+        valuation
+    }
+
+    /// The states reachable from the initial ones, with their successors
+    pub fn explore(&self) -> Result<StateGraph, SystemError> {
+        let count = 1 << self.variables.len();
+        let all = (0..count).map(|i| self.state(i)).collect::<Vec<_>>();
+        let mut graph = StateGraph {
+            variables: self.variables.clone(),
+            states: Vec::new(),
+            initial: Vec::new(),
+            edges: Vec::new(),
+        };
+        let mut index = HashMap::new();
+        let mut queue = VecDeque::new();
+        for state in &all {
+            let valuation = self.valuation(state, state);
+            let mut initial = true;
+            for condition in &self.init {
+                initial &= truth(condition, &valuation)?;
+            }
+            if initial {
+                let id = graph.intern(state, &mut index, &mut queue);
+                graph.initial.push(id);
+            }
+        }
+        if graph.initial.is_empty() {
+            return Err(SystemError::NoInitialState);
+        }
+        while let Some(id) = queue.pop_front() {
+            let now = graph.states[id].clone();
+            for next in &all {
+                let valuation = self.valuation(&now, next);
+                let mut step = false;
+                for condition in &self.trans {
+                    step |= truth(condition, &valuation)?;
+                }
+                if step {
+                    let to = graph.intern(next, &mut index, &mut queue);
+                    graph.edges[id].push(to);
+                }
+            }
+        }
+        // Every path is infinite, so a deadlocked state stutters
+        for id in 0..graph.states.len() {
+            if graph.edges[id].is_empty() {
+                graph.edges[id].push(id);
+            }
+        }
+        Ok(graph)
+    }
+}
+
+// The value of a propositional formula over the current and primed variables
+fn truth(expr: &LogExpr, valuation: &HashMap<String, bool>) -> Result<bool, SystemError> {
+    match expr {
+        LogExpr::True => Ok(true),
+        LogExpr::False => Ok(false),
+        LogExpr::Atom(token) => valuation
+            .get(token.text.as_str())
+            .cloned()
+            .ok_or(SystemError::UnknownVariable(token.text.clone())),
+        LogExpr::UnaryOp(op, inner) if **op == Operator::Not => Ok(!truth(inner, valuation)?),
+        LogExpr::BinaryOp(op, exprs) => {
+            let mut values = Vec::new();
+            for e in exprs {
+                values.push(truth(e, valuation)?);
+            }
+            match **op {
+                Operator::And => Ok(values.iter().all(|v| *v)),
+                Operator::Or => Ok(values.iter().any(|v| *v)),
+                Operator::Imp => match LogExpr::imp_parts(exprs) {
+                    Some((lhs, rhs)) => Ok(!truth(&lhs, valuation)? || truth(&rhs, valuation)?),
+                    None => Ok(values.first().cloned().unwrap_or(true)),
+                },
+                _ => Err(SystemError::NotPropositional(Box::new(expr.clone()))),
+            }
+        }
+        _ => Err(SystemError::NotPropositional(Box::new(expr.clone()))),
+    }
+}
+
+/// The explicit graph of reachable states, each a vector of variable values
+#[derive(Debug, Clone)]
+#[allow(clippy::vec_box)]
+pub struct StateGraph {
+    pub variables: Vec<Box<String>>,
+    pub states: Vec<Vec<bool>>,
+    pub initial: Vec<usize>,
+    pub edges: Vec<Vec<usize>>,
+}
+
+impl Display for StateGraph {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names = |ids: &[usize]| {
+            let names = ids.iter().map(|id| format!("s{}", id)).collect::<Vec<_>>();
+            names.join(", ")
+        };
+        writeln!(f, "\t\tinitial: {{{}}}", names(&self.initial))?;
+        for (id, edges) in self.edges.iter().enumerate() {
+            let mut atoms = self.atoms(id).into_iter().collect::<Vec<_>>();
+            atoms.sort();
+            writeln!(
+                f,
+                "\t\ts{}: {{{}}} -> {{{}}}",
+                id,
+                atoms.join(", "),
+                names(edges)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl StateGraph {
+    fn intern(
+        &mut self,
+        state: &[bool],
+        index: &mut HashMap<Vec<bool>, usize>,
+        queue: &mut VecDeque<usize>,
+    ) -> usize {
+        if let Some(id) = index.get(state) {
+            return *id;
+        }
+        let id = self.states.len();
+        self.states.push(state.to_vec());
+        self.edges.push(Vec::new());
+        index.insert(state.to_vec(), id);
+        queue.push_back(id);
+        id
+    }
+
+    pub fn atoms(&self, id: usize) -> HashSet<String> {
+        self.variables
+            .iter()
+            .zip(self.states[id].iter())
+            .filter(|(_, value)| **value)
+            .map(|(var, _)| var.to_string())
+            .collect()
+    }
+
+    // States with a successor in `sat`
+    fn pre(&self, sat: &[bool]) -> Vec<bool> {
+        self.edges
+            .iter()
+            .map(|edges| edges.iter().any(|to| sat[*to]))
+            .collect()
+    }
+
+    // Least fixpoint of Z = rhs or (lhs and EX Z)
+    fn exists_until(&self, lhs: &[bool], rhs: &[bool]) -> Vec<bool> {
+        let mut sat = rhs.to_vec();
+        loop {
+            let next = self.pre(&sat);
+            let updated = (0..sat.len())
+                .map(|i| rhs[i] || (lhs[i] && next[i]))
+                .collect::<Vec<_>>();
+            if updated == sat {
+                return sat;
+            }
+            sat = updated;
+        }
+    }
+
+    // Greatest fixpoint of Z = inner and EX Z
+    fn exists_always(&self, inner: &[bool]) -> Vec<bool> {
+        let mut sat = inner.to_vec();
+        loop {
+            let next = self.pre(&sat);
+            let updated = (0..sat.len())
+                .map(|i| inner[i] && next[i])
+                .collect::<Vec<_>>();
+            if updated == sat {
+                return sat;
+            }
+            sat = updated;
+        }
+    }
+
+    /// The states satisfying a CTL formula. Only `EX`, `EU` and `EG` are
+    /// computed directly; the other operators are rewritten into them.
+    pub fn check(&self, expr: &LogExpr) -> Result<Vec<bool>, SystemError> {
+        let n = self.states.len();
+        let not = |sat: Vec<bool>| sat.into_iter().map(|b| !b).collect::<Vec<_>>();
+        match expr {
+            LogExpr::True => Ok(vec![true; n]),
+            LogExpr::False => Ok(vec![false; n]),
+            LogExpr::Atom(token) => {
+                let i = self
+                    .variables
+                    .iter()
+                    .position(|var| *var == token.text)
+                    .ok_or(SystemError::UnknownVariable(token.text.clone()))?;
+                Ok(self.states.iter().map(|state| state[i]).collect())
+            }
+            LogExpr::UnaryOp(op, inner) => {
+                let sat = self.check(inner)?;
+                match **op {
+                    Operator::Not => Ok(not(sat)),
+                    Operator::ExistsNext => Ok(self.pre(&sat)),
+                    Operator::AllNext => Ok(not(self.pre(&not(sat)))),
+                    Operator::ExistsEventually => Ok(self.exists_until(&vec![true; n], &sat)),
+                    Operator::AllAlways => Ok(not(self.exists_until(&vec![true; n], &not(sat)))),
+                    Operator::ExistsAlways => Ok(self.exists_always(&sat)),
+                    Operator::AllEventually => Ok(not(self.exists_always(&not(sat)))),
+                    _ => Err(SystemError::NotCtl(Box::new(expr.clone()))),
+                }
+            }
+            LogExpr::BinaryOp(op, exprs) => {
+                let parts = exprs
+                    .iter()
+                    .map(|e| self.check(e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match **op {
+                    Operator::And => Ok((0..n).map(|i| parts.iter().all(|p| p[i])).collect()),
+                    Operator::Or => Ok((0..n).map(|i| parts.iter().any(|p| p[i])).collect()),
+                    Operator::Imp => match LogExpr::imp_parts(exprs) {
+                        Some((lhs, rhs)) => {
+                            let (lhs, rhs) = (self.check(&lhs)?, self.check(&rhs)?);
+                            Ok((0..n).map(|i| !lhs[i] || rhs[i]).collect())
+                        }
+                        None => Ok(parts.into_iter().next().unwrap_or(vec![true; n])),
+                    },
+                    Operator::ExistsUntil => Ok(self.exists_until(&parts[0], &parts[1])),
+                    // A[a U b] fails when b can be avoided forever, or until
+                    // both a and b fail
+                    Operator::AllUntil => {
+                        let (lhs, rhs) = (&parts[0], &parts[1]);
+                        let avoid = not(rhs.clone());
+                        let stuck = (0..n).map(|i| !lhs[i] && !rhs[i]).collect::<Vec<_>>();
+                        let until = self.exists_until(&avoid, &stuck);
+                        let always = self.exists_always(&avoid);
+                        Ok((0..n).map(|i| !until[i] && !always[i]).collect())
+                    }
+                    _ => Err(SystemError::NotCtl(Box::new(expr.clone()))),
+                }
+            }
+            _ => Err(SystemError::NotCtl(Box::new(expr.clone()))),
+        }
+    }
+
+    // A shortest path from `from` through `through` states to a `target`
+    // state, both ends included
+    fn path(&self, from: usize, through: &[bool], target: &[bool]) -> Option<Vec<usize>> {
+        let mut parent = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        parent.insert(from, from);
+        while let Some(v) = queue.pop_front() {
+            if target[v] {
+                let mut path = vec![v];
+                while path[path.len() - 1] != from {
+                    path.push(parent[&path[path.len() - 1]]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if !through[v] {
+                continue;
+            }
+            for &to in &self.edges[v] {
+                if parent.contains_key(&to) {
+                    continue;
+                }
+                parent.insert(to, v);
+                queue.push_back(to);
+            }
+        }
+        None
+    }
+
+    // Follows successors inside `sat` until a state repeats
+    fn lasso(&self, from: usize, sat: &[bool]) -> Option<Lasso> {
+        let mut path = vec![from];
+        loop {
+            let last = path[path.len() - 1];
+            let next = *self.edges[last].iter().find(|to| sat[**to])?;
+            if let Some(start) = path.iter().position(|s| *s == next) {
+                return Some((path, Some(start)));
+            }
+            path.push(next);
+        }
+    }
+
+    // Continues a path with the witness for `expr` at its last state
+    fn extend(&self, mut path: Vec<usize>, expr: &LogExpr) -> Result<Lasso, SystemError> {
+        let last = path[path.len() - 1];
+        match self.witness(expr, last)? {
+            Some((rest, loop_start)) => {
+                let offset = path.len() - 1;
+                path.extend(&rest[1..]);
+                Ok((path, loop_start.map(|start| start + offset)))
+            }
+            None => Ok((path, None)),
+        }
+    }
+
+    /// A path from `state` showing why a formula in negation normal form
+    /// holds there; `None` when its truth needs no path to show
+    pub fn witness(&self, expr: &LogExpr, state: usize) -> Result<Option<Lasso>, SystemError> {
+        let n = self.states.len();
+        match expr {
+            LogExpr::UnaryOp(op, inner) => match (&**op, &**inner) {
+                (Operator::ExistsNext, _) => {
+                    let sat = self.check(inner)?;
+                    match self.edges[state].iter().find(|to| sat[**to]) {
+                        Some(to) => Ok(Some(self.extend(vec![state, *to], inner)?)),
+                        None => Ok(None),
+                    }
+                }
+                (Operator::ExistsEventually, _) => {
+                    match self.path(state, &vec![true; n], &self.check(inner)?) {
+                        Some(path) => Ok(Some(self.extend(path, inner)?)),
+                        None => Ok(None),
+                    }
+                }
+                (Operator::ExistsAlways, _) => {
+                    Ok(self.lasso(state, &self.exists_always(&self.check(inner)?)))
+                }
+                // A[a U b] fails along a path avoiding b forever, or until
+                // both a and b fail
+                (Operator::Not, LogExpr::BinaryOp(op, exprs)) if **op == Operator::AllUntil => {
+                    let (lhs, rhs) = (self.check(&exprs[0])?, self.check(&exprs[1])?);
+                    let avoid = rhs.iter().map(|b| !b).collect::<Vec<_>>();
+                    let always = self.exists_always(&avoid);
+                    if always[state] {
+                        return Ok(self.lasso(state, &always));
+                    }
+                    let stuck = (0..n).map(|i| !lhs[i] && !rhs[i]).collect::<Vec<_>>();
+                    Ok(self.path(state, &avoid, &stuck).map(|path| (path, None)))
+                }
+                _ => Ok(None),
+            },
+            LogExpr::BinaryOp(op, exprs) => match **op {
+                Operator::ExistsUntil => {
+                    let (lhs, rhs) = (self.check(&exprs[0])?, self.check(&exprs[1])?);
+                    match self.path(state, &lhs, &rhs) {
+                        Some(path) => Ok(Some(self.extend(path, &exprs[1])?)),
+                        None => Ok(None),
+                    }
+                }
+                Operator::And | Operator::Or => {
+                    for e in exprs {
+                        if !self.check(e)?[state] {
+                            continue;
+                        }
+                        if let Some(lasso) = self.witness(e, state)? {
+                            return Ok(Some(lasso));
+                        }
+                    }
+                    Ok(None)
+                }
+                _ => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+
+    fn trace(&self, (path, loop_start): &Lasso) -> Trace {
+        Trace {
+            states: path.iter().map(|id| self.atoms(*id)).collect(),
+            loop_start: *loop_start,
+        }
+    }
+}
+
+// A path of states, looping back from its end when the start is given
+type Lasso = (Vec<usize>, Option<usize>);
+
+/// The verdict of a CTL formula on a transition system: it holds when every
+/// initial state satisfies it
+#[derive(Debug, Clone)]
+pub struct CtlCheck {
+    pub goal: Box<LogExpr>,
+    pub size: usize,
+    pub failing: Option<HashSet<String>>,
+    pub counterexample: Option<Trace>,
+}
+
+impl CtlCheck {
+    pub fn new(graph: &StateGraph, goal: &LogExpr) -> Result<CtlCheck, SystemError> {
+        let sat = graph.check(goal)?;
+        let mut check = CtlCheck {
+            goal: Box::new(goal.clone()),
+            size: graph.states.len(),
+            failing: None,
+            counterexample: None,
+        };
+        if let Some(state) = graph.initial.iter().find(|s| !sat[**s]) {
+            check.failing = Some(graph.atoms(*state));
+            let counterexample = graph.witness(&nnf(goal, false), *state)?;
+            check.counterexample = counterexample.map(|lasso| graph.trace(&lasso));
+        }
+        Ok(check)
+    }
+}
+
+impl Display for CtlCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "\tTransition system: {} reachable states", self.size)?;
+        match &self.failing {
+            None => write!(f, "\t{} holds in every initial state.", self.goal),
+            Some(state) => {
+                let mut atoms = state.iter().cloned().collect::<Vec<_>>();
+                atoms.sort();
+                write!(
+                    f,
+                    "\t{} fails in the initial state {{{}}}.",
+                    self.goal,
+                    atoms.join(", ")
+                )?;
+                match &self.counterexample {
+                    Some(trace) => {
+                        writeln!(f, " Counterexample:")?;
+                        write!(f, "{}", trace.to_string().trim_end())
+                    }
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn system(s: &str) -> Result<StateGraph, SystemError> {
+        TransitionSystem::parse(&mut Lexer::new(s, None))?.explore()
+    }
+
+    // From 00 to 10, then on to 11 or back to 00; 11 returns to 00. The
+    // state 01 is unreachable.
+    const SYSTEM: &str = "vars (a b)
+        init (and (not a) (not b))
+        trans (and (not a) (not b) a' (not b'))
+        trans (and a (not b) a' b')
+        trans (and a (not b) (not a') (not b'))
+        trans (and a b (not a') (not b'))";
+
+    fn holds(goal: &str) -> bool {
+        let check = CtlCheck::new(&system(SYSTEM).unwrap(), &expr(goal)).unwrap();
+        check.failing.is_none()
+    }
+
+    #[test]
+    fn only_reachable_states_are_explored() {
+        let graph = system(SYSTEM).unwrap();
+        assert_eq!(graph.states.len(), 3);
+        assert_eq!(graph.initial, [0]);
+        assert!(holds("(AG (not (and (not a) b)))"));
+    }
+
+    #[test]
+    fn path_quantifiers_are_told_apart() {
+        assert!(holds("(EF b)"));
+        assert!(!holds("(AF b)"));
+        assert!(holds("(EG (not b))"));
+        assert!(!holds("(AG (not b))"));
+        assert!(holds("(AX a)"));
+        assert!(holds("(AG (EF (and (not a) (not b))))"));
+        assert!(holds("(EU (not b) (and a b))"));
+        assert!(!holds("(AU (not b) b)"));
+        assert!(holds("(AG (imp b (AX (not a))))"));
+    }
+
+    #[test]
+    fn counterexamples_are_paths_of_the_system() {
+        let graph = system(SYSTEM).unwrap();
+        let check = CtlCheck::new(&graph, &expr("(AF b)")).unwrap();
+        let trace = check.counterexample.unwrap();
+        assert!(trace.loop_start.is_some());
+        let mut lexer = Lexer::new("(G (not b))", None);
+        lexer.temporal = true;
+        let never = LogExpr::parse(&mut lexer).unwrap();
+        assert!(trace.eval(&never).unwrap()[0]);
+    }
+
+    #[test]
+    fn deadlocks_stutter() {
+        let graph = system("vars (a) init a trans (and (not a) a')").unwrap();
+        assert_eq!(graph.edges, [vec![0]]);
+    }
+
+    #[test]
+    fn malformed_systems_and_formulas_are_refused() {
+        assert!(matches!(
+            system("vars (a) init (and a (not a))"),
+            Err(SystemError::NoInitialState)
+        ));
+        assert!(matches!(
+            system("vars (a) init c"),
+            Err(SystemError::UnknownVariable(_))
+        ));
+        assert!(matches!(
+            system("vars (a b c d e f g h i j k)"),
+            Err(SystemError::TooManyVariables(11))
+        ));
+        assert!(matches!(
+            system("vars (a') init a'"),
+            Err(SystemError::ExpectedVariable(_))
+        ));
+        assert!(matches!(
+            system("states (a)"),
+            Err(SystemError::ExpectedSection(_))
+        ));
+        let graph = system(SYSTEM).unwrap();
+        assert!(matches!(
+            graph.check(&expr("(box a)")),
+            Err(SystemError::NotCtl(_))
+        ));
+    }
+}
@@ -0,0 +1,387 @@
+use std::cmp::Reverse;
+use std::fmt::Display;
+
+use crate::minimize::*;
+use crate::parser::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalForm {
+    Dnf,
+    Cnf,
+}
+
+impl Display for NormalForm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NormalForm::Dnf => write!(f, "DNF"),
+            NormalForm::Cnf => write!(f, "CNF"),
+        }
+    }
+}
+
+impl NormalForm {
+    pub fn from_str(s: &str) -> Option<NormalForm> {
+        match s.to_lowercase().as_str() {
+            "dnf" => Some(NormalForm::Dnf),
+            "cnf" => Some(NormalForm::Cnf),
+            _ => None,
+        }
+    }
+}
+
+fn bits(mask: u64) -> impl Iterator<Item = u64> {
+    (0..64)
+        .map(|i| 1u64 << i)
+        .filter(move |bit| mask & bit != 0)
+}
+
+fn cost(cover: &[Cube]) -> (usize, u32) {
+    (cover.len(), cover.iter().map(|c| c.literals()).sum())
+}
+
+// The part of the cover inside the cube, with the cube's atoms dropped
+fn cofactor(cover: &[Cube], cube: Cube) -> Vec<Cube> {
+    cover
+        .iter()
+        .filter(|c| c.intersects(&cube))
+        .map(|c| Cube {
+            care: c.care & !cube.care,
+            value: c.value & !cube.care,
+        })
+        .collect()
+}
+
+// Shannon expansion on the most binate atom. A cover where every atom
+// occurs in one polarity only is a tautology just when it has the
+// universal cube.
+fn tautology(cover: &[Cube]) -> bool {
+    if cover.iter().any(|c| c.care == 0) {
+        return true;
+    }
+    let atoms = cover.iter().fold(0, |atoms, c| atoms | c.care);
+    // Too few minterms between them to fill the space
+    let space = atoms.count_ones() as i32;
+    let volume = cover
+        .iter()
+        .map(|c| 2f64.powi(space - c.literals() as i32))
+        .sum::<f64>();
+    if volume < 2f64.powi(space) {
+        return false;
+    }
+    let binate = bits(atoms)
+        .filter_map(|bit| {
+            let positive = cover.iter().filter(|c| c.care & c.value & bit != 0).count();
+            let negative = cover
+                .iter()
+                .filter(|c| c.care & !c.value & bit != 0)
+                .count();
+            (positive > 0 && negative > 0).then_some((positive + negative, bit))
+        })
+        .max();
+    match binate {
+        Some((_, bit)) => [bit, 0].iter().all(|value| {
+            tautology(&cofactor(
+                cover,
+                Cube {
+                    care: bit,
+                    value: *value,
+                },
+            ))
+        }),
+        None => false,
+    }
+}
+
+fn covered(cube: Cube, cover: &[Cube]) -> bool {
+    tautology(&cofactor(cover, cube))
+}
+
+// Raises literals of each cube, largest cubes first, for as long as the
+// cube stays clear of the off-set, then drops the cubes it swallows
+fn expand(cover: &[Cube], off: &[Cube]) -> Vec<Cube> {
+    // How many cubes have each atom positive and negative
+    let mut polarity = [(0usize, 0usize); 64];
+    for cube in cover {
+        for (i, counts) in polarity.iter_mut().enumerate() {
+            match (cube.care >> i & 1, cube.value >> i & 1) {
+                (1, 1) => counts.0 += 1,
+                (1, _) => counts.1 += 1,
+                _ => {}
+            }
+        }
+    }
+    let mut order = cover.to_vec();
+    order.sort_by_key(|c| c.literals());
+    let mut expanded: Vec<Cube> = Vec::new();
+    for cube in order {
+        if expanded.iter().any(|e| e.contains(&cube)) {
+            continue;
+        }
+        // Grow first toward the atoms the other cubes disagree on or leave free
+        let mut literals = bits(cube.care).collect::<Vec<_>>();
+        literals.sort_by_key(|bit| {
+            let (positive, negative) = polarity[bit.trailing_zeros() as usize];
+            match cube.value & bit {
+                0 => Reverse(cover.len() - negative),
+                _ => Reverse(cover.len() - positive),
+            }
+        });
+        // The literals of the cube each off-set cube contradicts: raising
+        // all of them would let the cube meet that off-set cube
+        let blocking = off
+            .iter()
+            .map(|r| (r.value ^ cube.value) & r.care & cube.care)
+            .collect::<Vec<_>>();
+        let mut raised = 0;
+        for bit in literals {
+            if !blocking.iter().any(|b| b & !(raised | bit) == 0) {
+                raised |= bit;
+            }
+        }
+        let grown = Cube {
+            care: cube.care & !raised,
+            value: cube.value & !raised,
+        };
+        expanded.retain(|e| !grown.contains(e));
+        expanded.push(grown);
+    }
+    expanded
+}
+
+fn others(cover: &[Cube], i: usize, dont_care: &[Cube]) -> Vec<Cube> {
+    cover
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| *j != i)
+        .map(|(_, c)| *c)
+        .chain(dont_care.iter().cloned())
+        .collect()
+}
+
+// Drops cubes the rest of the cover already covers, smallest cubes first
+fn irredundant(mut cover: Vec<Cube>, dont_care: &[Cube]) -> Vec<Cube> {
+    cover.sort_by_key(|c| Reverse(c.literals()));
+    let mut i = 0;
+    while i < cover.len() {
+        if covered(cover[i], &others(&cover, i, dont_care)) {
+            cover.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    cover
+}
+
+// Shrinks each cube, largest first, to the part the rest of the cover
+// misses, so the next expansion can grow it in another direction
+fn reduce(mut cover: Vec<Cube>, dont_care: &[Cube], atoms: usize) -> Vec<Cube> {
+    cover.sort_by_key(|c| c.literals());
+    for i in 0..cover.len() {
+        let rest = others(&cover, i, dont_care);
+        let mut cube = cover[i];
+        for bit in bits(!cube.care & atom_mask(atoms)) {
+            for value in [bit, 0] {
+                let half = Cube {
+                    care: cube.care | bit,
+                    value: cube.value | value,
+                };
+                if covered(half, &rest) {
+                    cube = Cube {
+                        care: cube.care | bit,
+                        value: cube.value | (value ^ bit),
+                    };
+                    break;
+                }
+            }
+        }
+        cover[i] = cube;
+    }
+    cover
+}
+
+/// A near-minimal two-level form found by the Espresso loop: expand,
+/// irredundant and reduce until the cover stops getting cheaper. A CNF is
+/// found as a DNF of the negation.
+#[derive(Debug, Clone)]
+pub struct Espresso {
+    pub form: NormalForm,
+    pub expr: Box<LogExpr>,
+    pub dont_care: Option<Box<LogExpr>>,
+    pub atoms: Vec<String>,
+    pub initial: (usize, u32),
+    pub cover: Vec<Cube>,
+    pub passes: usize,
+}
+
+impl Espresso {
+    pub fn new(
+        form: NormalForm,
+        expr: &LogExpr,
+        dont_care: Option<&LogExpr>,
+    ) -> Result<Espresso, MinimizeError> {
+        let sets = CubeSets::new(expr, dont_care, form == NormalForm::Cnf)?;
+        let n = sets.atoms.len();
+        let dc = &sets.dont_care;
+        let mut cover = irredundant(expand(&sets.on, &sets.off), dc);
+        let mut passes = 1;
+        loop {
+            let next = irredundant(expand(&reduce(cover.clone(), dc, n), &sets.off), dc);
+            if cost(&next) >= cost(&cover) {
+                break;
+            }
+            cover = next;
+            passes += 1;
+        }
+        cover.sort_by_key(Cube::rank);
+        Ok(Espresso {
+            form,
+            expr: Box::new(expr.clone()),
+            dont_care: dont_care.map(|d| Box::new(d.clone())),
+            initial: cost(&sets.on),
+            atoms: sets.atoms,
+            cover,
+            passes,
+        })
+    }
+
+    pub fn result(&self) -> Box<LogExpr> {
+        match self.form {
+            NormalForm::Dnf => sum_of_products(&self.cover, &self.atoms),
+            NormalForm::Cnf => product_of_sums(&self.cover, &self.atoms),
+        }
+    }
+}
+
+impl Display for Espresso {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\tEspresso {} of {}", self.form, self.expr)?;
+        if let Some(dont_care) = &self.dont_care {
+            write!(f, ", don't care where {}", dont_care)?;
+        }
+        writeln!(f, ":")?;
+        writeln!(
+            f,
+            "\t\tatoms: {}, passes: {}",
+            self.atoms.len(),
+            self.passes
+        )?;
+        let terms = match self.form {
+            NormalForm::Dnf => "terms",
+            NormalForm::Cnf => "clauses",
+        };
+        let (size, literals) = cost(&self.cover);
+        writeln!(
+            f,
+            "\t\t{}: {} -> {}, literals: {} -> {}",
+            terms, self.initial.0, size, self.initial.1, literals
+        )?;
+        write!(f, "\t\t{}", self.result())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bdd::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn equivalent(lhs: &LogExpr, rhs: &LogExpr) -> bool {
+        BddEquivalence::new(lhs, rhs).unwrap().difference.is_none()
+    }
+
+    fn and(lhs: &LogExpr, rhs: &LogExpr) -> Box<LogExpr> {
+        Box::new(LogExpr::BinaryOp(
+            Box::new(Operator::And),
+            vec![Box::new(lhs.clone()), Box::new(rhs.clone())],
+        ))
+    }
+
+    const FORMULAS: [&str; 5] = [
+        "(or (and (not a) (not b)) (and b (not c)) (and a c))",
+        "(or (and a b) (and (not a) c) (and b c))",
+        "(imp (and p q) (or r (not s)))",
+        "(and (or a b) (or (not a) c) (or b (not c) d))",
+        "(or (and a b c) (and a b (not c)) (and a (not b) c))",
+    ];
+
+    #[test]
+    fn both_forms_are_equivalent_to_the_formula() {
+        for formula in FORMULAS {
+            let formula = expr(formula);
+            for form in [NormalForm::Dnf, NormalForm::Cnf] {
+                let espresso = Espresso::new(form, &formula, None).unwrap();
+                assert!(
+                    equivalent(&espresso.result(), &formula),
+                    "{} of {}",
+                    form,
+                    formula
+                );
+                assert!(cost(&espresso.cover) <= espresso.initial);
+            }
+        }
+    }
+
+    #[test]
+    fn covers_match_the_minimum_off_cycles() {
+        for formula in &FORMULAS[1..] {
+            let formula = expr(formula);
+            let espresso = Espresso::new(NormalForm::Dnf, &formula, None).unwrap();
+            let minimum = Minimization::new(&formula, None).unwrap().cover_cubes();
+            assert_eq!(cost(&espresso.cover), cost(&minimum), "{}", formula);
+        }
+        let espresso = Espresso::new(NormalForm::Cnf, &expr(FORMULAS[4]), None).unwrap();
+        assert_eq!(cost(&espresso.cover), (2, 3));
+    }
+
+    #[test]
+    fn dont_cares_only_matter_outside_the_care_set() {
+        let (formula, dont_care) = (expr("(and a b)"), expr("(and a (not b))"));
+        let espresso = Espresso::new(NormalForm::Dnf, &formula, Some(&dont_care)).unwrap();
+        assert!(equivalent(&espresso.result(), &expr("a")));
+        let formula = expr(FORMULAS[3]);
+        let dont_care = expr("(and (not b) d)");
+        let care = expr("(not (and (not b) d))");
+        for form in [NormalForm::Dnf, NormalForm::Cnf] {
+            let espresso = Espresso::new(form, &formula, Some(&dont_care)).unwrap();
+            assert!(equivalent(
+                &and(&espresso.result(), &care),
+                &and(&formula, &care)
+            ));
+        }
+    }
+
+    #[test]
+    fn formulas_past_the_minterm_limit_are_minimized() {
+        let pairs = (0..10)
+            .map(|i| format!("(and p{} q{} (or p{} r))", i, i, i))
+            .collect::<Vec<_>>();
+        let formula = expr(&format!("(or {})", pairs.join(" ")));
+        let espresso = Espresso::new(NormalForm::Dnf, &formula, None).unwrap();
+        assert_eq!(cost(&espresso.cover), (10, 20));
+        assert!(equivalent(&espresso.result(), &formula));
+    }
+
+    #[test]
+    fn tautologies_are_recognized() {
+        let full = [Cube { care: 0, value: 0 }];
+        assert!(tautology(&full));
+        let split = [
+            Cube {
+                care: 0b1,
+                value: 0b1,
+            },
+            Cube {
+                care: 0b1,
+                value: 0b0,
+            },
+        ];
+        assert!(tautology(&split));
+        assert!(!tautology(&split[..1]));
+        assert_eq!(NormalForm::from_str("CNF"), Some(NormalForm::Cnf));
+        assert_eq!(NormalForm::from_str("anf"), None);
+    }
+}
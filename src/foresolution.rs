@@ -0,0 +1,442 @@
+use std::collections::HashSet;
+
+use crate::parser::*;
+use crate::prenex::*;
+use crate::resolution::*;
+
+/// Resolvents and factors generated before the prover gives up
+const CLAUSE_LIMIT: usize = 2000;
+
+// Follows variable bindings until reaching an unbound variable or a non-variable
+fn walk<'a>(term: &'a LogExpr, subst: &'a Bindings) -> &'a LogExpr {
+    match term {
+        LogExpr::Var(token) => match subst.get(&token.text) {
+            Some(bound) => walk(bound, subst),
+            None => term,
+        },
+        _ => term,
+    }
+}
+
+fn occurs(name: &str, term: &LogExpr, subst: &Bindings) -> bool {
+    match walk(term, subst) {
+        LogExpr::Var(token) => *token.text == name,
+        LogExpr::Func(_, args) | LogExpr::Pred(_, args) => {
+            args.iter().any(|arg| occurs(name, arg, subst))
+        }
+        _ => false,
+    }
+}
+
+/// Extends `subst` to a most general unifier of the two terms or atoms, with
+/// the occurs check
+pub fn unify(lhs: &LogExpr, rhs: &LogExpr, subst: &mut Bindings) -> bool {
+    let (lhs, rhs) = (walk(lhs, subst).clone(), walk(rhs, subst).clone());
+    match (&lhs, &rhs) {
+        (LogExpr::Var(a), LogExpr::Var(b)) if a.text == b.text => true,
+        (LogExpr::Var(var), term) | (term, LogExpr::Var(var)) => {
+            if occurs(&var.text, term, subst) {
+                false
+            } else {
+                subst.insert(var.text.clone(), Box::new(term.clone()));
+                true
+            }
+        }
+        (LogExpr::Func(f, args1), LogExpr::Func(g, args2))
+        | (LogExpr::Pred(f, args1), LogExpr::Pred(g, args2))
+            if f.text == g.text && args1.len() == args2.len() =>
+        {
+            args1
+                .iter()
+                .zip(args2.iter())
+                .all(|(a, b)| unify(a, b, subst))
+        }
+        _ => lhs == rhs,
+    }
+}
+
+/// Applies a triangular substitution all the way down
+pub fn apply(term: &LogExpr, subst: &Bindings) -> Box<LogExpr> {
+    match walk(term, subst) {
+        LogExpr::Func(name, args) => Box::new(LogExpr::Func(
+            name.clone(),
+            args.iter().map(|arg| apply(arg, subst)).collect(),
+        )),
+        LogExpr::Pred(name, args) => Box::new(LogExpr::Pred(
+            name.clone(),
+            args.iter().map(|arg| apply(arg, subst)).collect(),
+        )),
+        other => Box::new(other.clone()),
+    }
+}
+
+// One-way matching: only the variables of `pattern` may be bound
+fn match_term(pattern: &LogExpr, target: &LogExpr, subst: &mut Bindings) -> bool {
+    match (pattern, target) {
+        (LogExpr::Var(var), _) => match subst.get(&var.text) {
+            Some(bound) => **bound == *target,
+            None => {
+                subst.insert(var.text.clone(), Box::new(target.clone()));
+                true
+            }
+        },
+        (LogExpr::Func(f, args1), LogExpr::Func(g, args2))
+        | (LogExpr::Pred(f, args1), LogExpr::Pred(g, args2))
+            if f.text == g.text && args1.len() == args2.len() =>
+        {
+            args1
+                .iter()
+                .zip(args2.iter())
+                .all(|(a, b)| match_term(a, b, subst))
+        }
+        _ => pattern == target,
+    }
+}
+
+fn apply_clause(clause: &Clause, subst: &Bindings) -> Clause {
+    Clause::new(
+        clause
+            .literals
+            .iter()
+            .map(|l| Literal {
+                atom: apply(&l.atom, subst),
+                positive: l.positive,
+            })
+            .collect(),
+    )
+}
+
+// Renames variables in one step; unlike `apply` this is safe for permutations
+fn rename(term: &LogExpr, names: &Bindings) -> Box<LogExpr> {
+    match term {
+        LogExpr::Var(token) => names
+            .get(&token.text)
+            .cloned()
+            .unwrap_or_else(|| Box::new(term.clone())),
+        LogExpr::Func(name, args) => Box::new(LogExpr::Func(
+            name.clone(),
+            args.iter().map(|arg| rename(arg, names)).collect(),
+        )),
+        LogExpr::Pred(name, args) => Box::new(LogExpr::Pred(
+            name.clone(),
+            args.iter().map(|arg| rename(arg, names)).collect(),
+        )),
+        _ => Box::new(term.clone()),
+    }
+}
+
+#[allow(clippy::vec_box)]
+fn variables(term: &LogExpr, out: &mut Vec<Box<String>>) {
+    match term {
+        LogExpr::Var(token) if !out.contains(&token.text) => out.push(token.text.clone()),
+        LogExpr::Func(_, args) | LogExpr::Pred(_, args) => {
+            args.iter().for_each(|arg| variables(arg, out))
+        }
+        _ => {}
+    }
+}
+
+// Renames the variables of a clause to `<prefix>1`, `<prefix>2`, ... in order
+// of appearance, so variants of a clause look the same
+fn standardize(clause: &Clause, prefix: &str) -> Clause {
+    let mut vars = Vec::new();
+    for literal in &clause.literals {
+        variables(&literal.atom, &mut vars);
+    }
+    let names = vars
+        .iter()
+        .enumerate()
+        .map(|(i, var)| {
+            let name = format!("{}{}", prefix, i + 1);
+            (var.clone(), Box::new(LogExpr::Var(LogExpr::token(&name))))
+        })
+        .collect::<Bindings>();
+    Clause::new(
+        clause
+            .literals
+            .iter()
+            .map(|l| Literal {
+                atom: rename(&l.atom, &names),
+                positive: l.positive,
+            })
+            .collect(),
+    )
+}
+
+/// θ-subsumption: some instance of `general` is a subset of `specific`
+pub fn subsumes(general: &Clause, specific: &Clause) -> bool {
+    fn extend(rest: &[Literal], specific: &Clause, subst: &Bindings) -> bool {
+        match rest.split_first() {
+            None => true,
+            Some((literal, rest)) => specific.literals.iter().any(|target| {
+                let mut subst = subst.clone();
+                target.positive == literal.positive
+                    && match_term(&literal.atom, &target.atom, &mut subst)
+                    && extend(rest, specific, &subst)
+            }),
+        }
+    }
+    general.literals.len() <= specific.literals.len()
+        && extend(&general.literals, specific, &Bindings::new())
+}
+
+fn resolvents(given: &Clause, partner: &Clause) -> Vec<Clause> {
+    let partner = standardize(partner, "Y");
+    let mut results = Vec::new();
+    for (i, lit1) in given.literals.iter().enumerate() {
+        for (j, lit2) in partner.literals.iter().enumerate() {
+            let mut subst = Bindings::new();
+            if lit1.positive != lit2.positive && unify(&lit1.atom, &lit2.atom, &mut subst) {
+                let literals = given
+                    .literals
+                    .iter()
+                    .enumerate()
+                    .filter(|(k, _)| *k != i)
+                    .chain(partner.literals.iter().enumerate().filter(|(k, _)| *k != j))
+                    .map(|(_, l)| l.clone())
+                    .collect();
+                results.push(standardize(
+                    &apply_clause(&Clause::new(literals), &subst),
+                    "X",
+                ));
+            }
+        }
+    }
+    results
+}
+
+fn factors(clause: &Clause) -> Vec<Clause> {
+    let mut results = Vec::new();
+    for (i, lit1) in clause.literals.iter().enumerate() {
+        for lit2 in clause.literals.iter().skip(i + 1) {
+            let mut subst = Bindings::new();
+            if lit1.positive == lit2.positive && unify(&lit1.atom, &lit2.atom, &mut subst) {
+                results.push(standardize(&apply_clause(clause, &subst), "X"));
+            }
+        }
+    }
+    results
+}
+
+// Clause form of a Skolemized formula, its universal variables made `Var`s
+fn clauses_of(expr: &LogExpr, used: &mut HashSet<String>) -> Result<Vec<Clause>, ResolutionError> {
+    let mut matrix = Skolemized::with_used(expr, used)
+        .map_err(ResolutionError::Prenex)?
+        .formula;
+    while let LogExpr::Quant(_, var, body) = *matrix {
+        matrix = body.substitute_term(&var.text, &LogExpr::Var(var.clone()));
+    }
+    Ok(clausify(&matrix)?
+        .iter()
+        .map(|clause| standardize(clause, "X"))
+        .collect())
+}
+
+/// First-order resolution over Skolemized clause form, with factoring and a
+/// given-clause loop restricted to the set of support
+pub fn prove_first_order(
+    premises: &[Box<LogExpr>],
+    goal: Option<&LogExpr>,
+) -> Result<Resolution, ResolutionError> {
+    classical(premises.iter().map(|e| &**e).chain(goal))?;
+    let mut used = HashSet::new();
+    for expr in premises.iter().map(|e| &**e).chain(goal) {
+        expr.symbols(&mut used);
+    }
+    let mut input = Vec::new();
+    for premise in premises {
+        input.extend(
+            clauses_of(premise, &mut used)?
+                .into_iter()
+                .map(|c| (c, Source::Premise)),
+        );
+    }
+    if let Some(goal) = goal {
+        input.extend(
+            clauses_of(&goal.negate(), &mut used)?
+                .into_iter()
+                .map(|c| (c, Source::NegatedGoal)),
+        );
+    }
+
+    let mut steps: Vec<Step> = Vec::new();
+    let mut support: Vec<usize> = Vec::new();
+    let mut processed: Vec<usize> = Vec::new();
+    let done = |steps: Vec<Step>, refutation, gave_up| Resolution {
+        steps,
+        refutation,
+        gave_up,
+    };
+
+    for (clause, source) in input {
+        if steps.iter().any(|s| subsumes(&s.clause, &clause)) {
+            continue;
+        }
+        let index = steps.len();
+        let is_support = goal.is_none() || matches!(source, Source::NegatedGoal);
+        steps.push(Step {
+            number: index + 1,
+            clause,
+            source,
+        });
+        if steps[index].clause.is_empty() {
+            return Ok(done(steps, Some(index), false));
+        }
+        if is_support {
+            support.push(index);
+        } else {
+            processed.push(index);
+        }
+    }
+
+    // Lightest supported clause first, by number of symbols
+    let weight = |clause: &Clause| clause.to_string().len();
+    let mut removed: HashSet<usize> = HashSet::new();
+    let mut generated = 0;
+    while let Some(position) = support
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, &i)| weight(&steps[i].clause))
+        .map(|(position, _)| position)
+    {
+        let given = support.remove(position);
+        if removed.contains(&given) {
+            continue;
+        }
+        processed.push(given);
+
+        let mut derived = Vec::new();
+        for factor in factors(&steps[given].clause) {
+            derived.push((factor, Source::Factor(steps[given].number)));
+        }
+        for &partner in &processed {
+            if removed.contains(&partner) {
+                continue;
+            }
+            for resolvent in resolvents(&steps[given].clause, &steps[partner].clause) {
+                let source = Source::Resolvent(steps[given].number, steps[partner].number);
+                derived.push((resolvent, source));
+            }
+        }
+
+        for (clause, source) in derived {
+            generated += 1;
+            if generated > CLAUSE_LIMIT {
+                return Ok(done(steps, None, true));
+            }
+            if clause.is_tautology()
+                || steps
+                    .iter()
+                    .enumerate()
+                    .any(|(i, s)| !removed.contains(&i) && subsumes(&s.clause, &clause))
+            {
+                continue;
+            }
+            for (i, step) in steps.iter().enumerate() {
+                if subsumes(&clause, &step.clause) {
+                    removed.insert(i);
+                }
+            }
+            let index = steps.len();
+            steps.push(Step {
+                number: index + 1,
+                clause,
+                source,
+            });
+            if steps[index].clause.is_empty() {
+                return Ok(done(steps, Some(index), false));
+            }
+            support.push(index);
+        }
+    }
+    Ok(done(steps, None, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn proves(premises: &[&str], goal: &str) -> bool {
+        let premises = premises.iter().map(|s| expr(s)).collect::<Vec<_>>();
+        let resolution = prove_first_order(&premises, Some(&expr(goal))).unwrap();
+        assert!(!resolution.gave_up);
+        resolution.refutation.is_some()
+    }
+
+    #[test]
+    fn unifiers_are_most_general() {
+        let mut subst = Bindings::new();
+        assert!(unify(
+            &expr("(P X (f Y))"),
+            &expr("(P a (f X))"),
+            &mut subst
+        ));
+        assert_eq!(apply(&expr("(Q X Y)"), &subst).to_string(), "(Q a a)");
+        let mut subst = Bindings::new();
+        assert!(unify(&expr("(P X Y)"), &expr("(P Y (g Z))"), &mut subst));
+        assert_eq!(
+            apply(&expr("(P X Y)"), &subst).to_string(),
+            "(P (g Z) (g Z))"
+        );
+    }
+
+    #[test]
+    fn clashes_and_cycles_do_not_unify() {
+        let mut subst = Bindings::new();
+        assert!(!unify(&expr("(P X)"), &expr("(P (f X))"), &mut subst));
+        let mut subst = Bindings::new();
+        assert!(!unify(&expr("(P a)"), &expr("(P b)"), &mut subst));
+        let mut subst = Bindings::new();
+        assert!(!unify(&expr("(P (f X))"), &expr("(P (g X))"), &mut subst));
+    }
+
+    #[test]
+    fn general_clauses_subsume_their_instances() {
+        let clause = |s: &str| clausify(&expr(s)).unwrap().pop().unwrap();
+        assert!(subsumes(&clause("(P X)"), &clause("(or (P a) (Q b))")));
+        assert!(!subsumes(
+            &clause("(or (P X) (Q X))"),
+            &clause("(or (P a) (Q b))")
+        ));
+        assert!(!subsumes(&clause("(P a)"), &clause("(P X)")));
+    }
+
+    #[test]
+    fn first_order_entailments_are_refuted() {
+        assert!(proves(
+            &["(forall x (imp (Man x) (Mortal x)))", "(Man socrates)"],
+            "(Mortal socrates)"
+        ));
+        assert!(proves(&["(forall x (P x))"], "(exists x (P x))"));
+        assert!(proves(
+            &[],
+            "(exists x (imp (Drinks x) (forall y (Drinks y))))"
+        ));
+        assert!(proves(
+            &["(forall x (exists y (R x y)))"],
+            "(forall x (exists y (or (R x y) (Q y))))"
+        ));
+    }
+
+    #[test]
+    fn first_order_non_entailments_saturate() {
+        assert!(!proves(&["(exists x (P x))"], "(forall x (P x))"));
+        assert!(!proves(
+            &["(forall x (exists y (R x y)))"],
+            "(exists y (forall x (R x y)))"
+        ));
+    }
+
+    #[test]
+    fn modal_formulas_are_refused() {
+        assert!(matches!(
+            prove_first_order(&[], Some(&expr("(forall x (box (P x)))"))),
+            Err(ResolutionError::Prenex(_))
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::parser::*;
+
+/// A continuous t-norm family. Conjunction is the t-norm, disjunction its
+/// dual t-conorm, implication its residuum, and negation implication into 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TNorm {
+    Godel,
+    Product,
+    Lukasiewicz,
+}
+
+impl Display for TNorm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TNorm::Godel => write!(f, "Gödel"),
+            TNorm::Product => write!(f, "product"),
+            TNorm::Lukasiewicz => write!(f, "Łukasiewicz"),
+        }
+    }
+}
+
+impl TNorm {
+    pub fn from_str(s: &str) -> Option<TNorm> {
+        match s.to_lowercase().as_str() {
+            "godel" | "gödel" | "min" => Some(TNorm::Godel),
+            "product" | "goguen" => Some(TNorm::Product),
+            "lukasiewicz" | "łukasiewicz" => Some(TNorm::Lukasiewicz),
+            _ => None,
+        }
+    }
+
+    pub fn and(&self, a: f64, b: f64) -> f64 {
+        match self {
+            TNorm::Godel => a.min(b),
+            TNorm::Product => a * b,
+            TNorm::Lukasiewicz => (a + b - 1.0).max(0.0),
+        }
+    }
+
+    pub fn or(&self, a: f64, b: f64) -> f64 {
+        match self {
+            TNorm::Godel => a.max(b),
+            TNorm::Product => a + b - a * b,
+            TNorm::Lukasiewicz => (a + b).min(1.0),
+        }
+    }
+
+    pub fn imp(&self, a: f64, b: f64) -> f64 {
+        match self {
+            _ if a <= b => 1.0,
+            TNorm::Godel => b,
+            TNorm::Product => b / a,
+            TNorm::Lukasiewicz => 1.0 - a + b,
+        }
+    }
+
+    pub fn not(&self, a: f64) -> f64 {
+        self.imp(a, 0.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
+pub enum FuzzyError {
+    Unassigned(Box<String>),
+    NotFuzzy(Box<LogExpr>),
+}
+
+impl Display for FuzzyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FuzzyError::Unassigned(atom) => write!(f, "{} has no degree assigned", atom),
+            FuzzyError::NotFuzzy(expr) => write!(f, "{} is not a propositional formula", expr),
+        }
+    }
+}
+
+// Degrees print with at most four decimals
+pub fn show_degree(degree: f64) -> String {
+    let text = format!("{:.4}", degree);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// Degrees of truth in [0, 1] for atoms, read under a chosen t-norm
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
+pub struct Fuzzy {
+    pub tnorm: TNorm,
+    pub degrees: HashMap<Box<String>, f64>,
+}
+
+impl Display for Fuzzy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "\t\tt-norm: {}", self.tnorm)?;
+        let mut degrees = self.degrees.iter().collect::<Vec<_>>();
+        degrees.sort_by_key(|(atom, _)| atom.to_string());
+        for (atom, degree) in degrees {
+            writeln!(f, "\t\t{} = {}", atom, show_degree(*degree))?;
+        }
+        Ok(())
+    }
+}
+
+impl Fuzzy {
+    pub fn new() -> Fuzzy {
+        Fuzzy {
+            tnorm: TNorm::Godel,
+            degrees: HashMap::new(),
+        }
+    }
+
+    #[allow(clippy::box_collection)]
+    pub fn assign(&mut self, atom: Box<String>, degree: f64) {
+        self.degrees.insert(atom, degree);
+    }
+
+    pub fn eval(&self, expr: &LogExpr) -> Result<f64, FuzzyError> {
+        let tnorm = self.tnorm;
+        match expr {
+            LogExpr::True => Ok(1.0),
+            LogExpr::False => Ok(0.0),
+            LogExpr::Atom(token) | LogExpr::Var(token) => self
+                .degrees
+                .get(&token.text)
+                .cloned()
+                .ok_or(FuzzyError::Unassigned(token.text.clone())),
+            LogExpr::UnaryOp(op, inner) if **op == Operator::Not => {
+                Ok(tnorm.not(self.eval(inner)?))
+            }
+            LogExpr::BinaryOp(op, exprs) => match **op {
+                Operator::And => exprs
+                    .iter()
+                    .try_fold(1.0, |degree, e| Ok(tnorm.and(degree, self.eval(e)?))),
+                Operator::Or => exprs
+                    .iter()
+                    .try_fold(0.0, |degree, e| Ok(tnorm.or(degree, self.eval(e)?))),
+                Operator::Imp => match LogExpr::imp_parts(exprs) {
+                    Some((lhs, rhs)) => Ok(tnorm.imp(self.eval(&lhs)?, self.eval(&rhs)?)),
+                    None => match exprs.first() {
+                        Some(e) => self.eval(e),
+                        None => Ok(1.0),
+                    },
+                },
+                _ => Err(FuzzyError::NotFuzzy(Box::new(expr.clone()))),
+            },
+            _ => Err(FuzzyError::NotFuzzy(Box::new(expr.clone()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn fuzzy(tnorm: TNorm) -> Fuzzy {
+        let mut fuzzy = Fuzzy::new();
+        fuzzy.tnorm = tnorm;
+        fuzzy.assign(Box::new("p".to_string()), 0.8);
+        fuzzy.assign(Box::new("q".to_string()), 0.4);
+        fuzzy
+    }
+
+    fn degree(tnorm: TNorm, s: &str) -> String {
+        show_degree(fuzzy(tnorm).eval(&expr(s)).unwrap())
+    }
+
+    #[test]
+    fn each_t_norm_has_its_own_connectives() {
+        let cases = [
+            ("(and p q)", ["0.4", "0.32", "0.2"]),
+            ("(or p q)", ["0.8", "0.88", "1"]),
+            ("(imp p q)", ["0.4", "0.5", "0.6"]),
+            ("(imp q p)", ["1", "1", "1"]),
+            ("(not q)", ["0", "0", "0.6"]),
+        ];
+        for (formula, degrees) in cases {
+            let found = [TNorm::Godel, TNorm::Product, TNorm::Lukasiewicz]
+                .map(|tnorm| degree(tnorm, formula));
+            assert_eq!(found, degrees, "{}", formula);
+        }
+    }
+
+    #[test]
+    fn residuation_gives_modus_ponens_its_bound() {
+        // p * (p -> q) never exceeds q
+        for tnorm in [TNorm::Godel, TNorm::Product, TNorm::Lukasiewicz] {
+            let fuzzy = fuzzy(tnorm);
+            let lhs = fuzzy.eval(&expr("(and p (imp p q))")).unwrap();
+            assert!(lhs <= fuzzy.eval(&expr("q")).unwrap() + 1e-9, "{}", tnorm);
+        }
+    }
+
+    #[test]
+    fn degrees_print_without_trailing_zeros() {
+        assert_eq!(show_degree(0.5), "0.5");
+        assert_eq!(show_degree(1.0), "1");
+        assert_eq!(show_degree(-0.0), "0");
+        assert_eq!(show_degree(1.0 / 3.0), "0.3333");
+    }
+
+    #[test]
+    fn unassigned_atoms_and_other_operators_are_refused() {
+        let fuzzy = fuzzy(TNorm::Godel);
+        assert!(matches!(
+            fuzzy.eval(&expr("(and p r)")),
+            Err(FuzzyError::Unassigned(_))
+        ));
+        assert!(matches!(
+            fuzzy.eval(&expr("(box p)")),
+            Err(FuzzyError::NotFuzzy(_))
+        ));
+        assert_eq!(TNorm::from_str("Goguen"), Some(TNorm::Product));
+        assert_eq!(TNorm::from_str("drastic"), None);
+    }
+
+    #[test]
+    fn assign_takes_an_atom_and_one_degree() {
+        let assign = |s: &str| Command::parse(&mut Lexer::new(s, None));
+        assert!(matches!(
+            *assign(":assign p 0.25").unwrap(),
+            Command::AssignDegree { degree, .. } if degree == 0.25
+        ));
+        assert!(matches!(*assign(":assign").unwrap(), Command::ShowDegrees));
+        assert!(matches!(
+            assign(":assign t 0.5"),
+            Err(ParserError::ExpectedSymbolName)
+        ));
+        assert!(matches!(
+            assign(":assign p 1.5"),
+            Err(ParserError::ExpectedDegree)
+        ));
+        assert!(matches!(
+            assign(":assign p 0.5 q"),
+            Err(ParserError::UnexpectedToken)
+        ));
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::lexer::*;
+use crate::parser::*;
+use crate::prenex::*;
+
+/// Terms of a level, or ground instances, generated before giving up
+const INSTANCE_LIMIT: usize = 10000;
+
+#[derive(Debug, Clone)]
+pub enum HerbrandError {
+    Prenex(PrenexError),
+    TooManyInstances,
+}
+
+impl Display for HerbrandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HerbrandError::Prenex(e) => write!(f, "{}", e),
+            HerbrandError::TooManyInstances => write!(
+                f,
+                "more than {} terms or ground instances would be generated",
+                INSTANCE_LIMIT
+            ),
+        }
+    }
+}
+
+// Collects the constants and function symbols used in term position
+#[allow(clippy::box_collection, clippy::vec_box)]
+fn term_symbols(
+    expr: &LogExpr,
+    bound: &HashSet<String>,
+    constants: &mut Vec<Box<String>>,
+    functions: &mut Vec<(Box<String>, usize)>,
+) {
+    match expr {
+        LogExpr::Atom(token)
+            if !bound.contains(token.text.as_str()) && !constants.contains(&token.text) =>
+        {
+            constants.push(token.text.clone());
+        }
+        LogExpr::Func(name, args) => {
+            let symbol = (name.text.clone(), args.len());
+            if !functions.contains(&symbol) {
+                functions.push(symbol);
+            }
+            args.iter()
+                .for_each(|arg| term_symbols(arg, bound, constants, functions));
+        }
+        _ => {}
+    }
+}
+
+#[allow(clippy::box_collection, clippy::vec_box)]
+fn formula_symbols(
+    expr: &LogExpr,
+    bound: &HashSet<String>,
+    constants: &mut Vec<Box<String>>,
+    functions: &mut Vec<(Box<String>, usize)>,
+) {
+    match expr {
+        LogExpr::Pred(_, args) => args
+            .iter()
+            .for_each(|arg| term_symbols(arg, bound, constants, functions)),
+        LogExpr::UnaryOp(_, inner) => formula_symbols(inner, bound, constants, functions),
+        LogExpr::BinaryOp(_, exprs) => exprs
+            .iter()
+            .for_each(|e| formula_symbols(e, bound, constants, functions)),
+        _ => {}
+    }
+}
+
+// All tuples of `arity` terms drawn from `terms`
+#[allow(clippy::vec_box)]
+fn term_tuples(
+    terms: &[Box<LogExpr>],
+    arity: usize,
+) -> Result<Vec<Vec<Box<LogExpr>>>, HerbrandError> {
+    let count = u32::try_from(arity)
+        .ok()
+        .and_then(|arity| terms.len().checked_pow(arity))
+        .filter(|count| *count <= INSTANCE_LIMIT);
+    if count.is_none() {
+        return Err(HerbrandError::TooManyInstances);
+    }
+    let mut result = vec![Vec::new()];
+    for _ in 0..arity {
+        result = result
+            .into_iter()
+            .flat_map(|tuple| {
+                terms.iter().map(move |term| {
+                    let mut tuple = tuple.clone();
+                    tuple.push(term.clone());
+                    tuple
+                })
+            })
+            .collect();
+    }
+    Ok(result)
+}
+
+/// Compact name of a ground term or atom, e.g. `P(a,g(b))`
+fn term_name(term: &LogExpr) -> String {
+    match term {
+        LogExpr::Func(name, args) | LogExpr::Pred(name, args) => {
+            let args = args.iter().map(|arg| term_name(arg)).collect::<Vec<_>>();
+            format!("{}({})", name.text, args.join(","))
+        }
+        _ => term.to_string(),
+    }
+}
+
+// Turns each ground atom into a propositional atom named after it
+fn propositionalize(expr: &LogExpr) -> Box<LogExpr> {
+    match expr {
+        LogExpr::Pred(..) => LogExpr::atom(&term_name(expr)),
+        LogExpr::UnaryOp(op, inner) => {
+            Box::new(LogExpr::UnaryOp(op.clone(), propositionalize(inner)))
+        }
+        LogExpr::BinaryOp(op, exprs) => Box::new(LogExpr::BinaryOp(
+            op.clone(),
+            exprs.iter().map(|e| propositionalize(e)).collect(),
+        )),
+        _ => Box::new(expr.clone()),
+    }
+}
+
+/// The Herbrand universe of a Skolemized formula, level by level: level 0
+/// holds the constants and level k + 1 the terms first built from level k
+#[derive(Debug, Clone)]
+#[allow(clippy::vec_box)]
+pub struct Herbrand {
+    pub skolemized: Skolemized,
+    pub variables: Vec<Box<Token>>,
+    pub matrix: Box<LogExpr>,
+    pub levels: Vec<Vec<Box<LogExpr>>>,
+}
+
+impl Herbrand {
+    pub fn new(expr: &LogExpr, depth: usize) -> Result<Herbrand, HerbrandError> {
+        let skolemized = Skolemized::new(expr).map_err(HerbrandError::Prenex)?;
+        let mut variables = Vec::new();
+        let mut matrix = skolemized.formula.clone();
+        while let LogExpr::Quant(_, var, body) = *matrix {
+            variables.push(var);
+            matrix = body;
+        }
+
+        let bound = variables.iter().map(|v| v.text.to_string()).collect();
+        let (mut constants, mut functions) = (Vec::new(), Vec::new());
+        formula_symbols(&matrix, &bound, &mut constants, &mut functions);
+        // The universe is never empty: without constants, invent one
+        if constants.is_empty() {
+            constants.push(Box::new(LogExpr::fresh_name("a", &[&matrix])));
+        }
+
+        let mut universe: Vec<Box<LogExpr>> = constants.iter().map(|c| LogExpr::atom(c)).collect();
+        let mut levels = vec![universe.clone()];
+        for _ in 0..depth {
+            let mut level = Vec::new();
+            for (name, arity) in &functions {
+                for args in term_tuples(&universe, *arity)? {
+                    let term = Box::new(LogExpr::Func(LogExpr::token(name), args));
+                    if !universe.contains(&term) && !level.contains(&term) {
+                        level.push(term);
+                    }
+                }
+            }
+            universe.extend(level.iter().cloned());
+            levels.push(level);
+        }
+        Ok(Herbrand {
+            skolemized,
+            variables,
+            matrix,
+            levels,
+        })
+    }
+
+    #[allow(clippy::vec_box)]
+    pub fn universe(&self) -> Vec<Box<LogExpr>> {
+        self.levels.iter().flatten().cloned().collect()
+    }
+
+    /// Every instance of the matrix with its universal variables replaced by
+    /// terms of the universe, as propositional formulas
+    #[allow(clippy::vec_box)]
+    pub fn instances(&self) -> Result<Vec<Box<LogExpr>>, HerbrandError> {
+        Ok(term_tuples(&self.universe(), self.variables.len())?
+            .into_iter()
+            .map(|terms| {
+                let instance = self
+                    .variables
+                    .iter()
+                    .zip(terms.iter())
+                    .fold(self.matrix.clone(), |expr, (var, term)| {
+                        expr.substitute_term(&var.text, term)
+                    });
+                propositionalize(&instance)
+            })
+            .collect())
+    }
+}
+
+impl Display for Herbrand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "\tSkolem form: {}", self.skolemized.formula)?;
+        for (k, level) in self.levels.iter().enumerate() {
+            let terms = level.iter().map(|t| term_name(t)).collect::<Vec<_>>();
+            if terms.is_empty() {
+                write!(f, "\t\tH{}: no new terms", k)?;
+            } else {
+                write!(f, "\t\tH{}: {}", k, terms.join(", "))?;
+            }
+            if k + 1 < self.levels.len() {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn names(terms: &[Box<LogExpr>]) -> Vec<String> {
+        terms.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn levels_grow_from_the_constants() {
+        let herbrand = Herbrand::new(&expr("(forall x (imp (P x) (P (s x))))"), 2).unwrap();
+        assert_eq!(
+            herbrand.levels.iter().map(|l| l.len()).collect::<Vec<_>>(),
+            [1, 1, 1]
+        );
+        let herbrand = Herbrand::new(&expr("(forall x (R a (f x b)))"), 1).unwrap();
+        assert_eq!(names(&herbrand.levels[0]), ["a", "b"]);
+        assert_eq!(
+            names(&herbrand.levels[1]),
+            ["(f a a)", "(f a b)", "(f b a)", "(f b b)"]
+        );
+    }
+
+    #[test]
+    fn skolem_constants_join_the_universe() {
+        let herbrand = Herbrand::new(&expr("(exists y (forall x (R x y)))"), 0).unwrap();
+        assert_eq!(names(&herbrand.universe()), ["sk1"]);
+        assert_eq!(names(&herbrand.instances().unwrap()), ["R(sk1,sk1)"]);
+    }
+
+    #[test]
+    fn instances_cover_every_tuple_of_terms() {
+        let herbrand = Herbrand::new(&expr("(forall x (forall y (or (P x) (Q y))))"), 0).unwrap();
+        assert_eq!(herbrand.instances().unwrap().len(), 1);
+        let herbrand =
+            Herbrand::new(&expr("(forall x (forall y (imp (R x y) (R y x))))"), 1).unwrap();
+        assert_eq!(names(&herbrand.universe()).len(), 1);
+        let herbrand =
+            Herbrand::new(&expr("(forall x (forall y (R (f x) (g y) c d)))"), 1).unwrap();
+        assert_eq!(herbrand.universe().len(), 6);
+        assert_eq!(herbrand.instances().unwrap().len(), 36);
+    }
+
+    #[test]
+    fn runaway_universes_are_capped() {
+        let formula = expr("(forall x (forall y (forall z (P (f x y z) a b c d))))");
+        assert!(matches!(
+            Herbrand::new(&formula, 3),
+            Err(HerbrandError::TooManyInstances)
+        ));
+        let herbrand = Herbrand::new(&formula, 1).unwrap();
+        assert!(matches!(
+            herbrand.instances(),
+            Err(HerbrandError::TooManyInstances)
+        ));
+    }
+
+    #[test]
+    fn modal_formulas_are_refused() {
+        assert!(matches!(
+            Herbrand::new(&expr("(forall x (box (P x)))"), 1),
+            Err(HerbrandError::Prenex(_))
+        ));
+    }
+}
@@ -0,0 +1,310 @@
+use std::fmt::Display;
+
+use crate::parser::*;
+
+#[derive(Debug)]
+#[allow(clippy::box_collection)]
+pub enum ProofError {
+    UnknownRule(Box<String>),
+    NoSuchLine(usize),
+    WrongPremiseCount { expected: usize, found: usize },
+    NotAnInstance,
+}
+
+impl Display for ProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofError::UnknownRule(name) => {
+                write!(f, "no axiom or inference rule named {}", name)
+            }
+            ProofError::NoSuchLine(n) => write!(f, "line {} does not exist yet", n),
+            ProofError::WrongPremiseCount { expected, found } => {
+                write!(f, "rule takes {} premise lines, {} given", expected, found)
+            }
+            ProofError::NotAnInstance => write!(f, "formula is not an instance of the rule"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
+pub struct ProofLine {
+    pub formula: Box<LogExpr>,
+    pub rule: Box<String>,
+    pub refs: Vec<usize>,
+}
+
+/// A Hilbert-style proof, checked one line at a time against a rule set
+#[derive(Debug, Clone)]
+pub struct Proof {
+    pub goal: Option<Box<LogExpr>>,
+    pub lines: Vec<ProofLine>,
+}
+
+// Turns the atoms of a proved formula into metavariables, so substitution can
+// be checked by matching
+fn schematize(expr: &LogExpr) -> Box<LogExpr> {
+    match expr {
+        LogExpr::Atom(token) => Box::new(LogExpr::Var(token.clone())),
+        LogExpr::UnaryOp(op, inner) => Box::new(LogExpr::UnaryOp(op.clone(), schematize(inner))),
+        LogExpr::BinaryOp(op, exprs) => Box::new(LogExpr::BinaryOp(
+            op.clone(),
+            exprs.iter().map(|e| schematize(e)).collect(),
+        )),
+        _ => Box::new(expr.clone()),
+    }
+}
+
+impl Proof {
+    pub fn new(goal: Option<Box<LogExpr>>) -> Proof {
+        Proof {
+            goal,
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        match (&self.goal, self.lines.last()) {
+            (Some(goal), Some(line)) => line.formula == *goal,
+            _ => false,
+        }
+    }
+
+    fn line(&self, n: usize) -> Result<&LogExpr, ProofError> {
+        n.checked_sub(1)
+            .and_then(|i| self.lines.get(i))
+            .map(|line| line.formula.as_ref())
+            .ok_or(ProofError::NoSuchLine(n))
+    }
+
+    fn expect_refs(refs: &[usize], expected: usize) -> Result<(), ProofError> {
+        if refs.len() == expected {
+            Ok(())
+        } else {
+            Err(ProofError::WrongPremiseCount {
+                expected,
+                found: refs.len(),
+            })
+        }
+    }
+
+    // Checks the formula follows from the referenced lines by the named rule.
+    // Declared axioms and inference rules take precedence over the built-in
+    // `mp` (modus ponens) and `subst` (uniform substitution of atoms).
+    fn check(
+        &self,
+        rule_set: &RuleSet,
+        formula: &LogExpr,
+        rule: &str,
+        refs: &[usize],
+    ) -> Result<(), ProofError> {
+        let mut bindings = Bindings::new();
+        let matched = if let Some(schema) = rule_set.axioms.get(&Box::new(rule.to_string())) {
+            Proof::expect_refs(refs, 0)?;
+            schema.match_with(formula, &mut bindings)
+        } else if let Some(inference) = rule_set.inferences.get(&Box::new(rule.to_string())) {
+            Proof::expect_refs(refs, inference.premises.len())?;
+            let lines = refs
+                .iter()
+                .map(|n| self.line(*n))
+                .collect::<Result<Vec<_>, _>>()?;
+            inference
+                .premises
+                .iter()
+                .zip(lines)
+                .all(|(premise, line)| premise.match_with(line, &mut bindings))
+                && inference.conclusion.match_with(formula, &mut bindings)
+        } else if rule == "mp" {
+            Proof::expect_refs(refs, 2)?;
+            let (minor, major) = (self.line(refs[0])?, self.line(refs[1])?);
+            match major {
+                LogExpr::BinaryOp(op, exprs) if **op == Operator::Imp => {
+                    match LogExpr::imp_parts(exprs) {
+                        Some((lhs, rhs)) => *lhs == *minor && *rhs == *formula,
+                        None => false,
+                    }
+                }
+                _ => false,
+            }
+        } else if rule == "subst" {
+            Proof::expect_refs(refs, 1)?;
+            schematize(self.line(refs[0])?).match_with(formula, &mut bindings)
+        } else {
+            return Err(ProofError::UnknownRule(Box::new(rule.to_string())));
+        };
+        if matched {
+            Ok(())
+        } else {
+            Err(ProofError::NotAnInstance)
+        }
+    }
+
+    /// Appends a line if it is justified, returning its line number
+    #[allow(clippy::box_collection)]
+    pub fn add_step(
+        &mut self,
+        rule_set: &RuleSet,
+        formula: Box<LogExpr>,
+        rule: Box<String>,
+        refs: Vec<usize>,
+    ) -> Result<usize, ProofError> {
+        self.check(rule_set, &formula, &rule, &refs)?;
+        self.lines.push(ProofLine {
+            formula,
+            rule,
+            refs,
+        });
+        Ok(self.lines.len())
+    }
+}
+
+impl Display for ProofLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}  [{}", self.formula, self.rule)?;
+        let refs = self.refs.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        if !refs.is_empty() {
+            write!(f, " {}", refs.join(", "))?;
+        }
+        write!(f, "]")
+    }
+}
+
+impl Display for Proof {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(goal) = &self.goal {
+            writeln!(f, "\tGoal: {}", goal)?;
+        }
+        for (i, line) in self.lines.iter().enumerate() {
+            writeln!(f, "\t\t{}. {}", i + 1, line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn rule_set() -> RuleSet {
+        let mut rule_set = RuleSet::new();
+        rule_set.add_axiom(Box::new("k".to_string()), expr("(imp A (imp B A))"));
+        rule_set.add_axiom(
+            Box::new("s".to_string()),
+            expr("(imp (imp A (imp B C)) (imp (imp A B) (imp A C)))"),
+        );
+        rule_set
+    }
+
+    fn step(
+        proof: &mut Proof,
+        rule_set: &RuleSet,
+        s: &str,
+        rule: &str,
+        refs: &[usize],
+    ) -> Result<usize, ProofError> {
+        proof.add_step(rule_set, expr(s), Box::new(rule.to_string()), refs.to_vec())
+    }
+
+    #[test]
+    fn identity_follows_from_k_and_s() {
+        let rule_set = rule_set();
+        let mut proof = Proof::new(Some(expr("(imp p p)")));
+        let steps = [
+            (
+                "(imp (imp p (imp (imp p p) p)) (imp (imp p (imp p p)) (imp p p)))",
+                "s",
+                vec![],
+            ),
+            ("(imp p (imp (imp p p) p))", "k", vec![]),
+            ("(imp (imp p (imp p p)) (imp p p))", "mp", vec![2, 1]),
+            ("(imp p (imp p p))", "k", vec![]),
+            ("(imp p p)", "mp", vec![4, 3]),
+        ];
+        for (s, rule, refs) in steps {
+            step(&mut proof, &rule_set, s, rule, &refs).unwrap();
+        }
+        assert!(proof.is_complete());
+    }
+
+    #[test]
+    fn unjustified_steps_are_rejected() {
+        let rule_set = rule_set();
+        let mut proof = Proof::new(None);
+        assert!(matches!(
+            step(&mut proof, &rule_set, "(imp p (imp q q))", "k", &[]),
+            Err(ProofError::NotAnInstance)
+        ));
+        assert!(matches!(
+            step(&mut proof, &rule_set, "q", "mp", &[1, 2]),
+            Err(ProofError::NoSuchLine(1))
+        ));
+        assert!(matches!(
+            step(&mut proof, &rule_set, "q", "lem", &[]),
+            Err(ProofError::UnknownRule(_))
+        ));
+        step(&mut proof, &rule_set, "(imp p (imp q p))", "k", &[]).unwrap();
+        assert!(matches!(
+            step(&mut proof, &rule_set, "q", "mp", &[1]),
+            Err(ProofError::WrongPremiseCount {
+                expected: 2,
+                found: 1
+            })
+        ));
+        // Modus ponens needs the minor premise to be the antecedent
+        assert!(step(&mut proof, &rule_set, "(imp q p)", "mp", &[1, 1]).is_err());
+    }
+
+    #[test]
+    fn substitution_and_inference_rules() {
+        let mut rule_set = rule_set();
+        rule_set.add_inference(
+            Box::new("mt".to_string()),
+            vec![expr("(imp A B)"), expr("(not B)")],
+            expr("(not A)"),
+        );
+        rule_set.add_axiom(Box::new("pq".to_string()), expr("(imp p q)"));
+        rule_set.add_axiom(Box::new("nq".to_string()), expr("(not q)"));
+        let mut proof = Proof::new(None);
+        step(&mut proof, &rule_set, "(imp p (imp q p))", "k", &[]).unwrap();
+        step(
+            &mut proof,
+            &rule_set,
+            "(imp (and r s) (imp q (and r s)))",
+            "subst",
+            &[1],
+        )
+        .unwrap();
+        assert!(matches!(
+            step(&mut proof, &rule_set, "(not p)", "mt", &[1]),
+            Err(ProofError::WrongPremiseCount {
+                expected: 2,
+                found: 1
+            })
+        ));
+        step(&mut proof, &rule_set, "(imp p q)", "pq", &[]).unwrap();
+        step(&mut proof, &rule_set, "(not q)", "nq", &[]).unwrap();
+        assert_eq!(
+            step(&mut proof, &rule_set, "(not p)", "mt", &[3, 4]).unwrap(),
+            5
+        );
+        // Every reference is resolved before any premise is matched
+        assert!(matches!(
+            step(&mut proof, &rule_set, "(not p)", "mt", &[4, 9]),
+            Err(ProofError::NoSuchLine(9))
+        ));
+    }
+
+    #[test]
+    fn rules_axioms_and_inferences_share_names() {
+        let mut rule_set = rule_set();
+        rule_set.add_rule(Box::new("k".to_string()), expr("p"), expr("q"));
+        assert!(!rule_set.axioms.contains_key(&Box::new("k".to_string())));
+        let mut proof = Proof::new(None);
+        assert!(step(&mut proof, &rule_set, "(imp p (imp q p))", "k", &[]).is_err());
+    }
+}
@@ -0,0 +1,496 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::modal::*;
+use crate::modaltableau::*;
+use crate::parser::*;
+use crate::tableau::*;
+
+/// Which consequence relation the session decides
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Logic {
+    Classical,
+    Intuitionistic,
+}
+
+impl Display for Logic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Logic::Classical => write!(f, "classical"),
+            Logic::Intuitionistic => write!(f, "intuitionistic"),
+        }
+    }
+}
+
+impl Logic {
+    pub fn from_str(s: &str) -> Option<Logic> {
+        match s {
+            "classical" => Some(Logic::Classical),
+            "intuitionistic" => Some(Logic::Intuitionistic),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum IntuitionisticError {
+    NotPropositional(Box<LogExpr>),
+}
+
+impl Display for IntuitionisticError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntuitionisticError::NotPropositional(expr) => {
+                write!(f, "{} is not a propositional formula", expr)
+            }
+        }
+    }
+}
+
+// Formulas with binary connectives only; `(not a)` is `(imp a false)`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Prop {
+    Atom(String),
+    Top,
+    Bot,
+    And(Box<Prop>, Box<Prop>),
+    Or(Box<Prop>, Box<Prop>),
+    Imp(Box<Prop>, Box<Prop>),
+}
+
+impl Display for Prop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Prop::Atom(name) => write!(f, "{}", name),
+            Prop::Top => write!(f, "true"),
+            Prop::Bot => write!(f, "false"),
+            Prop::And(a, b) => write!(f, "(and {} {})", a, b),
+            Prop::Or(a, b) => write!(f, "(or {} {})", a, b),
+            Prop::Imp(a, b) if **b == Prop::Bot => write!(f, "(not {})", a),
+            Prop::Imp(a, b) => write!(f, "(imp {} {})", a, b),
+        }
+    }
+}
+
+impl Prop {
+    // Metavariables are read as atoms, so a schema is provable when its
+    // instance over fresh atoms is
+    fn from_expr(expr: &LogExpr) -> Result<Prop, IntuitionisticError> {
+        let fold = |exprs: &[Box<LogExpr>], unit: Prop, join: fn(Box<Prop>, Box<Prop>) -> Prop| {
+            let mut parts = exprs
+                .iter()
+                .map(|e| Prop::from_expr(e))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut prop = parts.pop().unwrap_or(unit);
+            while let Some(part) = parts.pop() {
+                prop = join(Box::new(part), Box::new(prop));
+            }
+            Ok(prop)
+        };
+        match expr {
+            LogExpr::Atom(token) | LogExpr::Var(token) => Ok(Prop::Atom(token.text.to_string())),
+            LogExpr::True => Ok(Prop::Top),
+            LogExpr::False => Ok(Prop::Bot),
+            LogExpr::UnaryOp(op, inner) if **op == Operator::Not => Ok(Prop::Imp(
+                Box::new(Prop::from_expr(inner)?),
+                Box::new(Prop::Bot),
+            )),
+            LogExpr::BinaryOp(op, exprs) => match **op {
+                Operator::And => fold(exprs, Prop::Top, Prop::And),
+                Operator::Or => fold(exprs, Prop::Bot, Prop::Or),
+                Operator::Imp => match LogExpr::imp_parts(exprs) {
+                    Some((lhs, rhs)) => Ok(Prop::Imp(
+                        Box::new(Prop::from_expr(&lhs)?),
+                        Box::new(Prop::from_expr(&rhs)?),
+                    )),
+                    None => fold(exprs, Prop::Top, Prop::And),
+                },
+                _ => Err(IntuitionisticError::NotPropositional(Box::new(
+                    expr.clone(),
+                ))),
+            },
+            _ => Err(IntuitionisticError::NotPropositional(Box::new(
+                expr.clone(),
+            ))),
+        }
+    }
+}
+
+/// A G4ip derivation of `antecedent ⊢ succedent`, premises first
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
+pub struct Derivation {
+    pub sequent: Box<String>,
+    pub rule: &'static str,
+    pub premises: Vec<Derivation>,
+}
+
+impl Derivation {
+    fn fmt_node(
+        node: &Derivation,
+        f: &mut std::fmt::Formatter<'_>,
+        head: &str,
+        prefix: &str,
+    ) -> std::fmt::Result {
+        writeln!(f, "{}{}  [{}]", head, node.sequent, node.rule)?;
+        let last = node.premises.len().saturating_sub(1);
+        for (i, premise) in node.premises.iter().enumerate() {
+            let (branch, rest) = if i == last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            Derivation::fmt_node(
+                premise,
+                f,
+                &format!("{}{}", prefix, branch),
+                &format!("{}{}", prefix, rest),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// A world of a tree-shaped countermodel with the atoms it forces; every
+// child forces at least the atoms of its parent
+#[derive(Debug, Clone)]
+struct World {
+    atoms: HashSet<String>,
+    children: Vec<World>,
+}
+
+impl World {
+    fn kripke(&self) -> Kripke {
+        let mut kripke = Kripke::new();
+        let mut valuation: HashMap<Box<String>, HashSet<Box<String>>> = HashMap::new();
+        let mut stack = vec![(self, None)];
+        while let Some((world, parent)) = stack.pop() {
+            let name = Box::new(format!("w{}", kripke.worlds.len()));
+            kripke.worlds.push(name.clone());
+            if let Some(parent) = parent {
+                kripke.access.insert((parent, name.clone()));
+            }
+            for atom in &world.atoms {
+                valuation
+                    .entry(Box::new(atom.clone()))
+                    .or_default()
+                    .insert(name.clone());
+            }
+            for child in world.children.iter().rev() {
+                stack.push((child, Some(name.clone())));
+            }
+        }
+        kripke.valuation = valuation;
+        kripke
+    }
+}
+
+#[allow(clippy::box_collection)]
+fn sequent(antecedent: &[Prop], succedent: &Prop) -> Box<String> {
+    let antecedent = antecedent.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+    match antecedent.is_empty() {
+        true => Box::new(format!("⊢ {}", succedent)),
+        false => Box::new(format!("{} ⊢ {}", antecedent.join(", "), succedent)),
+    }
+}
+
+fn with(antecedent: &[Prop], skip: usize, added: Vec<Prop>) -> Vec<Prop> {
+    let mut result = antecedent
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != skip)
+        .map(|(_, p)| p.clone())
+        .collect::<Vec<_>>();
+    for prop in added {
+        if !result.contains(&prop) {
+            result.push(prop);
+        }
+    }
+    result
+}
+
+// Proof search in Dyckhoff's contraction-free calculus. Invertible rules go
+// first, so when a search fails the failed premises of the remaining rules
+// assemble into a countermodel, after Pinto and Dyckhoff.
+fn search(antecedent: Vec<Prop>, succedent: Prop) -> Result<Derivation, World> {
+    let derivation = |rule, premises| {
+        Ok(Derivation {
+            sequent: sequent(&antecedent, &succedent),
+            rule,
+            premises,
+        })
+    };
+    if antecedent.contains(&Prop::Bot) {
+        return derivation("⊥L", Vec::new());
+    }
+    if antecedent.contains(&succedent) {
+        return derivation("axiom", Vec::new());
+    }
+    if succedent == Prop::Top {
+        return derivation("⊤R", Vec::new());
+    }
+    for (i, prop) in antecedent.iter().enumerate() {
+        let single = |rule, added| {
+            let premise = search(with(&antecedent, i, added), succedent.clone())?;
+            derivation(rule, vec![premise])
+        };
+        match prop {
+            Prop::Top => return single("⊤L", vec![]),
+            Prop::And(a, b) => return single("∧L", vec![(**a).clone(), (**b).clone()]),
+            Prop::Or(a, b) => {
+                let left = search(with(&antecedent, i, vec![(**a).clone()]), succedent.clone())?;
+                let right = search(with(&antecedent, i, vec![(**b).clone()]), succedent.clone())?;
+                return derivation("∨L", vec![left, right]);
+            }
+            Prop::Imp(a, b) => match &**a {
+                Prop::Atom(_) if antecedent.contains(a) => {
+                    return single("→L0", vec![(**b).clone()])
+                }
+                Prop::Top => return single("⊤→L", vec![(**b).clone()]),
+                Prop::Bot => return single("⊥→L", vec![]),
+                Prop::And(c, d) => {
+                    let curried = Prop::Imp(d.clone(), b.clone());
+                    return single("∧→L", vec![Prop::Imp(c.clone(), Box::new(curried))]);
+                }
+                Prop::Or(c, d) => {
+                    let split = vec![
+                        Prop::Imp(c.clone(), b.clone()),
+                        Prop::Imp(d.clone(), b.clone()),
+                    ];
+                    return single("∨→L", split);
+                }
+                _ => {}
+            },
+            Prop::Atom(_) | Prop::Bot => {}
+        }
+    }
+    match &succedent {
+        Prop::And(a, b) => {
+            let left = search(antecedent.clone(), (**a).clone())?;
+            let right = search(antecedent.clone(), (**b).clone())?;
+            return derivation("∧R", vec![left, right]);
+        }
+        Prop::Imp(a, b) => {
+            let premise = search(
+                with(&antecedent, usize::MAX, vec![(**a).clone()]),
+                (**b).clone(),
+            )?;
+            return derivation("→R", vec![premise]);
+        }
+        _ => {}
+    }
+    // Only rules that may fail while their conclusion holds are left
+    let mut children = Vec::new();
+    if let Prop::Or(a, b) = &succedent {
+        match search(antecedent.clone(), (**a).clone()) {
+            Ok(premise) => return derivation("∨R1", vec![premise]),
+            Err(world) => children.push(world),
+        }
+        match search(antecedent.clone(), (**b).clone()) {
+            Ok(premise) => return derivation("∨R2", vec![premise]),
+            Err(world) => children.push(world),
+        }
+    }
+    for (i, prop) in antecedent.iter().enumerate() {
+        if let Prop::Imp(ab, d) = prop {
+            if let Prop::Imp(a, b) = &**ab {
+                let weaker = Prop::Imp(b.clone(), d.clone());
+                match search(
+                    with(&antecedent, i, vec![weaker]),
+                    Prop::Imp(a.clone(), b.clone()),
+                ) {
+                    // The right premise is invertible, so its countermodel
+                    // refutes the conclusion too
+                    Ok(left) => {
+                        let right =
+                            search(with(&antecedent, i, vec![(**d).clone()]), succedent.clone())?;
+                        return derivation("→→L", vec![left, right]);
+                    }
+                    Err(world) => children.push(world),
+                }
+            }
+        }
+    }
+    let atoms = antecedent
+        .iter()
+        .filter_map(|p| match p {
+            Prop::Atom(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    Err(World { atoms, children })
+}
+
+/// The outcome of intuitionistic proof search: a derivation, or a Kripke
+/// model whose root forces the premises but not the goal
+#[derive(Debug, Clone)]
+pub struct IntuitionisticProof {
+    pub goal: Box<LogExpr>,
+    pub entailment: bool,
+    pub result: Result<Derivation, Kripke>,
+}
+
+impl IntuitionisticProof {
+    pub fn new(
+        premises: &[Box<LogExpr>],
+        goal: Option<&LogExpr>,
+    ) -> Result<IntuitionisticProof, IntuitionisticError> {
+        let antecedent = premises
+            .iter()
+            .map(|e| Prop::from_expr(e))
+            .collect::<Result<Vec<_>, _>>()?;
+        let goal = goal.cloned().unwrap_or(LogExpr::False);
+        let result = search(
+            with(&antecedent, usize::MAX, vec![]),
+            Prop::from_expr(&goal)?,
+        );
+        Ok(IntuitionisticProof {
+            goal: Box::new(goal),
+            entailment: !premises.is_empty(),
+            result: result.map_err(|world| world.kripke()),
+        })
+    }
+
+    pub fn is_provable(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl Display for IntuitionisticProof {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.result, self.entailment) {
+            (Ok(derivation), entailment) => {
+                Derivation::fmt_node(derivation, f, "\t", "\t")?;
+                match entailment {
+                    true => write!(f, "\tThe premises intuitionistically entail {}.", self.goal),
+                    false => write!(f, "\t{} is intuitionistically provable.", self.goal),
+                }
+            }
+            (Err(kripke), entailment) => {
+                match entailment {
+                    true => writeln!(
+                        f,
+                        "\tThe premises do not intuitionistically entail {}. Countermodel:",
+                        self.goal
+                    )?,
+                    false => writeln!(
+                        f,
+                        "\t{} is not intuitionistically provable. Countermodel:",
+                        self.goal
+                    )?,
+                }
+                write!(
+                    f,
+                    "{}\t\tordered by the reflexive, transitive closure of R; fails at w0",
+                    kripke
+                )
+            }
+        }
+    }
+}
+
+/// Whether a rule, axiom or inference rule holds classically but has no
+/// intuitionistic proof
+pub fn classical_only(rule_set: &RuleSet, name: &str) -> bool {
+    let formula = match rule_set.inferences.get(&Box::new(name.to_string())) {
+        Some(inference) => Some(Box::new(LogExpr::BinaryOp(
+            Box::new(Operator::Imp),
+            vec![
+                Box::new(LogExpr::BinaryOp(
+                    Box::new(Operator::And),
+                    inference.premises.iter().map(|p| atomize(p)).collect(),
+                )),
+                atomize(&inference.conclusion),
+            ],
+        ))),
+        None => rule_soundness(rule_set, name),
+    };
+    match formula.map(|formula| IntuitionisticProof::new(&[], Some(&formula))) {
+        Some(Ok(proof)) if !proof.is_provable() => {
+            matches!(Tableau::new(&[proof.goal.negate()]), Ok(t) if t.model.is_none())
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn provable(premises: &[&str], goal: &str) -> bool {
+        let premises = premises.iter().map(|p| expr(p)).collect::<Vec<_>>();
+        IntuitionisticProof::new(&premises, Some(&expr(goal)))
+            .unwrap()
+            .is_provable()
+    }
+
+    #[test]
+    fn constructive_theorems_are_derived() {
+        assert!(provable(&[], "(imp p p)"));
+        assert!(provable(&[], "(imp p (not (not p)))"));
+        assert!(provable(&[], "(not (not (or p (not p))))"));
+        assert!(provable(&[], "(imp (not (not (not p))) (not p))"));
+        assert!(provable(&[], "(imp (or (not p) (not q)) (not (and p q)))"));
+        assert!(provable(&["(imp p q)", "(imp q r)"], "(imp p r)"));
+        assert!(provable(&["(or p q)", "(not p)"], "q"));
+    }
+
+    #[test]
+    fn classical_principles_have_countermodels() {
+        for goal in [
+            "(or p (not p))",
+            "(imp (not (not p)) p)",
+            "(imp (imp (imp p q) p) p)",
+            "(imp (not (and p q)) (or (not p) (not q)))",
+            "(or (imp p q) (imp q p))",
+        ] {
+            let proof = IntuitionisticProof::new(&[], Some(&expr(goal))).unwrap();
+            match proof.result {
+                Ok(_) => panic!("{} was derived", goal),
+                Err(kripke) => assert!(kripke.worlds.len() > 1, "{}", goal),
+            }
+        }
+        assert!(!provable(&["(not (not p))"], "p"));
+    }
+
+    #[test]
+    fn classical_only_rules_are_flagged() {
+        let mut rule_set = RuleSet::new();
+        let name = |s: &str| Box::new(s.to_string());
+        rule_set.add_axiom(name("lem"), expr("(or A (not A))"));
+        rule_set.add_axiom(name("k"), expr("(imp A (imp B A))"));
+        rule_set.add_axiom(name("bad"), expr("(imp A B)"));
+        rule_set.add_inference(name("dne"), vec![expr("(not (not A))")], expr("A"));
+        rule_set.add_inference(name("mp"), vec![expr("A"), expr("(imp A B)")], expr("B"));
+        rule_set.add_rule(name("dn"), expr("(not (not A))"), expr("A"));
+        assert!(classical_only(&rule_set, "lem"));
+        assert!(classical_only(&rule_set, "dne"));
+        assert!(classical_only(&rule_set, "dn"));
+        assert!(!classical_only(&rule_set, "k"));
+        assert!(!classical_only(&rule_set, "mp"));
+        // Unsound even classically
+        assert!(!classical_only(&rule_set, "bad"));
+        assert!(!classical_only(&rule_set, "missing"));
+    }
+
+    #[test]
+    fn non_propositional_formulas_are_refused() {
+        for goal in ["(box p)", "(forall x (P x))"] {
+            assert!(matches!(
+                IntuitionisticProof::new(&[], Some(&expr(goal))),
+                Err(IntuitionisticError::NotPropositional(_))
+            ));
+        }
+        assert_eq!(
+            Logic::from_str("intuitionistic"),
+            Some(Logic::Intuitionistic)
+        );
+        assert_eq!(Logic::from_str("linear"), None);
+    }
+}
@@ -0,0 +1,227 @@
+use std::fmt::Display;
+
+use crate::minimize::*;
+use crate::parser::*;
+
+// Larger maps stop being readable as a single grid
+pub const KMAP_ATOM_LIMIT: usize = 6;
+
+// The k-bit Gray code sequence, so neighbouring rows and columns differ in
+// exactly one atom
+fn gray_code(k: usize) -> Vec<u64> {
+    (0..1u64 << k).map(|i| i ^ i >> 1).collect()
+}
+
+/// A Karnaugh map: the first half of the atoms label the rows and the rest
+/// the columns, with cells marked 1, 0 or - for don't care, and the groups
+/// of a minimal cover lettered
+#[derive(Debug, Clone)]
+pub struct KarnaughMap {
+    pub expr: Box<LogExpr>,
+    pub dont_care: Option<Box<LogExpr>>,
+    pub atoms: Vec<String>,
+    pub minterms: Vec<u64>,
+    pub dont_cares: Vec<u64>,
+    pub groups: Vec<Cube>,
+}
+
+impl KarnaughMap {
+    pub fn new(
+        expr: &LogExpr,
+        dont_care: Option<&LogExpr>,
+        groups: bool,
+    ) -> Result<KarnaughMap, MinimizeError> {
+        let sets = CubeSets::new(expr, dont_care, false)?;
+        let n = sets.atoms.len();
+        if n > KMAP_ATOM_LIMIT {
+            return Err(MinimizeError::TooManyAtoms(n, KMAP_ATOM_LIMIT));
+        }
+        let expand = |cubes: &[Cube]| cubes.iter().flat_map(|c| c.minterms(n)).collect();
+        let groups = match groups {
+            true => Minimization::new(expr, dont_care)?.cover_cubes(),
+            false => Vec::new(),
+        };
+        Ok(KarnaughMap {
+            expr: Box::new(expr.clone()),
+            dont_care: dont_care.map(|d| Box::new(d.clone())),
+            minterms: expand(&sets.on),
+            dont_cares: expand(&sets.dont_care),
+            atoms: sets.atoms,
+            groups,
+        })
+    }
+
+    // Gray code bits are read first atom first, while minterm bit i is atom i
+    fn minterm(&self, row: u64, column: u64) -> u64 {
+        let rows = self.atoms.len() / 2;
+        let columns = self.atoms.len() - rows;
+        let mut minterm = 0;
+        for i in 0..rows {
+            minterm |= (row >> (rows - 1 - i) & 1) << i;
+        }
+        for i in 0..columns {
+            minterm |= (column >> (columns - 1 - i) & 1) << (rows + i);
+        }
+        minterm
+    }
+
+    fn cell(&self, minterm: u64) -> String {
+        let mut cell = if self.minterms.contains(&minterm) {
+            "1".to_string()
+        } else if self.dont_cares.contains(&minterm) {
+            "-".to_string()
+        } else {
+            "0".to_string()
+        };
+        for (i, group) in self.groups.iter().enumerate() {
+            if group.covers(minterm) {
+                cell.push(group_letter(i));
+            }
+        }
+        cell
+    }
+}
+
+fn group_letter(i: usize) -> char {
+    (b'A' + (i % 26) as u8) as char
+}
+
+fn gray_label(code: u64, bits: usize) -> String {
+    (0..bits)
+        .map(|i| match code >> (bits - 1 - i) & 1 {
+            0 => '0',
+            _ => '1',
+        })
+        .collect()
+}
+
+impl Display for KarnaughMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\tKarnaugh map of {}", self.expr)?;
+        if let Some(dont_care) = &self.dont_care {
+            write!(f, ", don't care where {}", dont_care)?;
+        }
+        writeln!(f, ":")?;
+        let rows = self.atoms.len() / 2;
+        let columns = self.atoms.len() - rows;
+        let (row_codes, column_codes) = (gray_code(rows), gray_code(columns));
+        let corner = format!(
+            "{}\\{}",
+            self.atoms[..rows].join(""),
+            self.atoms[rows..].join("")
+        );
+        let cells = row_codes
+            .iter()
+            .map(|row| {
+                column_codes
+                    .iter()
+                    .map(|column| self.cell(self.minterm(*row, *column)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let label_width = corner.len().max(rows);
+        let width = cells
+            .iter()
+            .flatten()
+            .map(|c| c.len())
+            .max()
+            .unwrap_or(1)
+            .max(columns);
+        let header = column_codes
+            .iter()
+            .map(|c| format!(" {:width$} ", gray_label(*c, columns)))
+            .collect::<Vec<_>>();
+        let line = format!("{:label_width$} |{}", corner, header.join("|"));
+        writeln!(f, "\t\t{}", line.trim_end())?;
+        let rule = vec!["-".repeat(width + 2); column_codes.len()];
+        write!(f, "\t\t{}-+{}", "-".repeat(label_width), rule.join("+"))?;
+        for (row, cells) in row_codes.iter().zip(&cells) {
+            let cells = cells
+                .iter()
+                .map(|c| format!(" {:width$} ", c))
+                .collect::<Vec<_>>();
+            let line = format!(
+                "{:label_width$} |{}",
+                gray_label(*row, rows),
+                cells.join("|")
+            );
+            write!(f, "\n\t\t{}", line.trim_end())?;
+        }
+        for (i, group) in self.groups.iter().enumerate() {
+            write!(
+                f,
+                "\n\t\t{} = {}",
+                group_letter(i),
+                group.to_expr(&self.atoms)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    #[test]
+    fn neighbouring_codes_differ_in_one_bit() {
+        assert_eq!(gray_code(2), [0b00, 0b01, 0b11, 0b10]);
+        let codes = gray_code(3);
+        for (i, code) in codes.iter().enumerate() {
+            let next = codes[(i + 1) % codes.len()];
+            assert_eq!((code ^ next).count_ones(), 1);
+        }
+        assert_eq!(gray_label(0b10, 2), "10");
+    }
+
+    #[test]
+    fn cells_follow_the_gray_order() {
+        let map = KarnaughMap::new(&expr("(or (and a b) (and (not a) c))"), None, false).unwrap();
+        assert_eq!(
+            map.to_string(),
+            "\tKarnaugh map of (or (and a b) (and (not a) c)):\n\
+             \t\ta\\bc | 00 | 01 | 11 | 10\n\
+             \t\t-----+----+----+----+----\n\
+             \t\t0    | 0  | 1  | 1  | 0\n\
+             \t\t1    | 0  | 0  | 1  | 1"
+        );
+    }
+
+    #[test]
+    fn groups_letter_a_minimal_cover() {
+        let formula = expr("(or (and a b) (and (not a) c))");
+        let map = KarnaughMap::new(&formula, None, true).unwrap();
+        assert_eq!(map.groups.len(), 2);
+        let text = map.to_string();
+        assert!(text.contains("A = "));
+        assert!(text.contains("B = "));
+        assert!(!text.contains("C = "));
+        for minterm in &map.minterms {
+            assert!(map.groups.iter().any(|g| g.covers(*minterm)));
+        }
+    }
+
+    #[test]
+    fn dont_cares_are_dashed() {
+        let map =
+            KarnaughMap::new(&expr("(and a b)"), Some(&expr("(and a (not b))")), true).unwrap();
+        assert_eq!(map.dont_cares.len(), 1);
+        assert_eq!(map.groups.len(), 1);
+        assert_eq!(map.cell(map.dont_cares[0]), "-A");
+        assert_eq!(map.cell(0), "0");
+    }
+
+    #[test]
+    fn large_maps_are_refused() {
+        let formula = expr("(or p1 p2 p3 p4 p5 p6 p7)");
+        assert!(matches!(
+            KarnaughMap::new(&formula, None, false),
+            Err(MinimizeError::TooManyAtoms(7, KMAP_ATOM_LIMIT))
+        ));
+    }
+}
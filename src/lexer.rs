@@ -1,3 +1,6 @@
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TokenKind {
     // Success tokens
@@ -10,6 +13,51 @@ pub enum TokenKind {
     Quit,
     Load,
     Save,
+    Tableau,
+    Resolve,
+    Axiom,
+    Inference,
+    Proof,
+    Step,
+    Bind,
+    Domain,
+    Interpret,
+    Evaluate,
+    FindModel,
+    Prenex,
+    Skolem,
+    Herbrand,
+    Ground,
+    Congruence,
+    Free,
+    Worlds,
+    Access,
+    Valuation,
+    At,
+    Global,
+    Modal,
+    ModalRule,
+    Trace,
+    Buchi,
+    LtlSat,
+    LtlValid,
+    System,
+    Ctl,
+    Logic,
+    Semantics,
+    TruthTable,
+    Valid,
+    TNorm,
+    Assign,
+    Degree,
+    Bdd,
+    BddEquiv,
+    BddDot,
+    Minimize,
+    Espresso,
+    KarnaughMap,
+    Circuit,
+    Turnstile,
     End,
 
     // Error tokens
@@ -18,6 +66,7 @@ pub enum TokenKind {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[allow(clippy::box_collection)]
 pub enum Loc {
     FileLoc {
         path: Box<String>,
@@ -29,7 +78,17 @@ pub enum Loc {
     },
 }
 
-#[derive(Debug, Clone, Hash)]
+impl Display for Loc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Loc::FileLoc { path, row, col } => write!(f, "{}:{}:{}", path, row, col),
+            Loc::ReplLoc { col } => write!(f, "col {}", col),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
 pub struct Token {
     pub kind: Box<TokenKind>,
     pub text: Box<String>,
@@ -44,7 +103,16 @@ impl PartialEq for Token {
 
 impl Eq for Token {} // Automatically derived based on PartialEq
 
+impl Hash for Token {
+    // Must agree with `PartialEq`, so the location is left out
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+        self.text.hash(state);
+    }
+}
+
 #[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
 pub struct Lexer {
     input: Box<Vec<char>>,
     peeked: Option<Box<Token>>,
@@ -54,6 +122,8 @@ pub struct Lexer {
     line_beginning: Box<usize>,
     line_current: Box<usize>,
     paren_layer: Box<usize>,
+    // Whether formulas read X, F, G, U and R as temporal operators
+    pub temporal: bool,
 }
 
 impl Lexer {
@@ -67,6 +137,7 @@ impl Lexer {
             line_beginning: Box::new(0),
             line_current: Box::new(0),
             paren_layer: Box::new(0),
+            temporal: false,
         }
     }
 
@@ -99,7 +170,7 @@ impl Lexer {
     }
 
     pub fn lex_whitespaces(&mut self) {
-        while let Some(c) = self.input.get(0) {
+        while let Some(c) = self.input.first() {
             if c.is_whitespace() {
                 self.drop_char();
             } else {
@@ -119,7 +190,7 @@ impl Lexer {
                     Box::new(Token {
                         kind: Box::new(TokenKind::LParen),
                         text: Box::new(c.to_string()),
-                        loc: loc,
+                        loc,
                     })
                 }
                 ')' => {
@@ -127,13 +198,13 @@ impl Lexer {
                         Box::new(Token {
                             kind: Box::new(TokenKind::ParenOverflow),
                             text: Box::new(")".to_string()),
-                            loc: loc,
+                            loc,
                         })
                     } else {
                         let token = Box::new(Token {
                             kind: Box::new(TokenKind::RParen),
                             text: Box::new(c.to_string()),
-                            loc: loc,
+                            loc,
                         });
                         *self.paren_layer -= 1;
                         token
@@ -141,7 +212,7 @@ impl Lexer {
                 }
                 ':' => {
                     let mut text = c.to_string();
-                    while let Some(&next_char) = self.input.get(0) {
+                    while let Some(&next_char) = self.input.first() {
                         if next_char.is_whitespace() || next_char == '(' || next_char == ')' {
                             break;
                         }
@@ -152,54 +223,279 @@ impl Lexer {
                         ":rule" => Box::new(Token {
                             kind: Box::new(TokenKind::Rule),
                             text: Box::new(text),
-                            loc: loc,
+                            loc,
                         }),
                         ":delete" => Box::new(Token {
                             kind: Box::new(TokenKind::Delete),
                             text: Box::new(text),
-                            loc: loc,
+                            loc,
                         }),
                         ":apply" => Box::new(Token {
                             kind: Box::new(TokenKind::Apply),
                             text: Box::new(text),
-                            loc: loc,
+                            loc,
                         }),
                         ":quit" => Box::new(Token {
                             kind: Box::new(TokenKind::Quit),
                             text: Box::new(text),
-                            loc: loc,
+                            loc,
                         }),
                         ":load" => Box::new(Token {
                             kind: Box::new(TokenKind::Load),
                             text: Box::new(text),
-                            loc: loc,
+                            loc,
                         }),
                         ":save" => Box::new(Token {
                             kind: Box::new(TokenKind::Save),
                             text: Box::new(text),
-                            loc: loc,
+                            loc,
+                        }),
+                        ":tableau" => Box::new(Token {
+                            kind: Box::new(TokenKind::Tableau),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":resolve" => Box::new(Token {
+                            kind: Box::new(TokenKind::Resolve),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":axiom" => Box::new(Token {
+                            kind: Box::new(TokenKind::Axiom),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":inference" => Box::new(Token {
+                            kind: Box::new(TokenKind::Inference),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":proof" => Box::new(Token {
+                            kind: Box::new(TokenKind::Proof),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":step" => Box::new(Token {
+                            kind: Box::new(TokenKind::Step),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":bind" => Box::new(Token {
+                            kind: Box::new(TokenKind::Bind),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":domain" => Box::new(Token {
+                            kind: Box::new(TokenKind::Domain),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":interpret" => Box::new(Token {
+                            kind: Box::new(TokenKind::Interpret),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":eval" => Box::new(Token {
+                            kind: Box::new(TokenKind::Evaluate),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":findmodel" => Box::new(Token {
+                            kind: Box::new(TokenKind::FindModel),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":prenex" => Box::new(Token {
+                            kind: Box::new(TokenKind::Prenex),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":skolem" => Box::new(Token {
+                            kind: Box::new(TokenKind::Skolem),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":herbrand" => Box::new(Token {
+                            kind: Box::new(TokenKind::Herbrand),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":ground" => Box::new(Token {
+                            kind: Box::new(TokenKind::Ground),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":congruence" => Box::new(Token {
+                            kind: Box::new(TokenKind::Congruence),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":free" => Box::new(Token {
+                            kind: Box::new(TokenKind::Free),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":worlds" => Box::new(Token {
+                            kind: Box::new(TokenKind::Worlds),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":access" => Box::new(Token {
+                            kind: Box::new(TokenKind::Access),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":valuation" => Box::new(Token {
+                            kind: Box::new(TokenKind::Valuation),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":at" => Box::new(Token {
+                            kind: Box::new(TokenKind::At),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":global" => Box::new(Token {
+                            kind: Box::new(TokenKind::Global),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":modal" => Box::new(Token {
+                            kind: Box::new(TokenKind::Modal),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":modalrule" => Box::new(Token {
+                            kind: Box::new(TokenKind::ModalRule),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":trace" => Box::new(Token {
+                            kind: Box::new(TokenKind::Trace),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":buchi" => Box::new(Token {
+                            kind: Box::new(TokenKind::Buchi),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":ltlsat" => Box::new(Token {
+                            kind: Box::new(TokenKind::LtlSat),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":ltlvalid" => Box::new(Token {
+                            kind: Box::new(TokenKind::LtlValid),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":system" => Box::new(Token {
+                            kind: Box::new(TokenKind::System),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":ctl" => Box::new(Token {
+                            kind: Box::new(TokenKind::Ctl),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":logic" => Box::new(Token {
+                            kind: Box::new(TokenKind::Logic),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":semantics" => Box::new(Token {
+                            kind: Box::new(TokenKind::Semantics),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":truthtable" => Box::new(Token {
+                            kind: Box::new(TokenKind::TruthTable),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":valid" => Box::new(Token {
+                            kind: Box::new(TokenKind::Valid),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":tnorm" => Box::new(Token {
+                            kind: Box::new(TokenKind::TNorm),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":assign" => Box::new(Token {
+                            kind: Box::new(TokenKind::Assign),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":degree" => Box::new(Token {
+                            kind: Box::new(TokenKind::Degree),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":bdd" => Box::new(Token {
+                            kind: Box::new(TokenKind::Bdd),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":bddequiv" => Box::new(Token {
+                            kind: Box::new(TokenKind::BddEquiv),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":bdddot" => Box::new(Token {
+                            kind: Box::new(TokenKind::BddDot),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":minimize" => Box::new(Token {
+                            kind: Box::new(TokenKind::Minimize),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":espresso" => Box::new(Token {
+                            kind: Box::new(TokenKind::Espresso),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":kmap" => Box::new(Token {
+                            kind: Box::new(TokenKind::KarnaughMap),
+                            text: Box::new(text),
+                            loc,
+                        }),
+                        ":circuit" => Box::new(Token {
+                            kind: Box::new(TokenKind::Circuit),
+                            text: Box::new(text),
+                            loc,
                         }),
                         _ => Box::new(Token {
                             kind: Box::new(TokenKind::String),
                             text: Box::new(text),
-                            loc: loc,
+                            loc,
                         }),
                     }
                 }
                 _ => {
                     // Handle symbols: accumulate characters until we hit a non-symbol character
                     let mut text = c.to_string();
-                    while let Some(&next_char) = self.input.get(0) {
+                    while let Some(&next_char) = self.input.first() {
                         if next_char.is_whitespace() || next_char == '(' || next_char == ')' {
                             break;
                         }
                         text.push(self.drop_char().unwrap());
                     }
 
+                    let kind = if text == "|-" {
+                        TokenKind::Turnstile
+                    } else {
+                        TokenKind::String
+                    };
                     Box::new(Token {
-                        kind: Box::new(TokenKind::String),
+                        kind: Box::new(kind),
                         text: Box::new(text),
-                        loc: loc,
+                        loc,
                     })
                 }
             },
@@ -209,20 +505,20 @@ impl Lexer {
                     Box::new(Token {
                         kind: Box::new(TokenKind::End),
                         text: Box::new("".to_string()),
-                        loc: loc,
+                        loc,
                     })
                 } else {
                     Box::new(Token {
                         kind: Box::new(TokenKind::UnclosedParen),
                         text: Box::new("".to_string()),
-                        loc: loc,
+                        loc,
                     })
                 }
             }
         }
     }
 
-    pub fn peek_token(&mut self) -> &Box<Token> {
+    pub fn peek_token(&mut self) -> &Token {
         let token = self.next_token();
         self.peeked.insert(token)
     }
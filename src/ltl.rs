@@ -0,0 +1,254 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::lexer::*;
+use crate::parser::*;
+
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
+pub enum TraceError {
+    Unreadable(Box<String>),
+    ExpectedState(Box<Token>),
+    EmptyTrace,
+    EmptyLoop,
+    NotTemporal(Box<LogExpr>),
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceError::Unreadable(path) => write!(f, "cannot read {}", path),
+            TraceError::ExpectedState(token) => write!(
+                f,
+                "{}: expected a state `(atom ...)` or `loop`, found `{}`",
+                token.loc, token.text
+            ),
+            TraceError::EmptyTrace => write!(f, "the trace has no states"),
+            TraceError::EmptyLoop => write!(f, "`loop` must be followed by a state"),
+            TraceError::NotTemporal(expr) => {
+                write!(f, "{} is not a propositional temporal formula", expr)
+            }
+        }
+    }
+}
+
+/// A sequence of states, each the set of atoms true in it. A lasso-shaped
+/// trace repeats the states from `loop_start` to the end forever; a finite
+/// trace just stops.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub states: Vec<HashSet<String>>,
+    pub loop_start: Option<usize>,
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, state) in self.states.iter().enumerate() {
+            if Some(i) == self.loop_start {
+                writeln!(f, "\t\tloop")?;
+            }
+            let mut atoms = state.iter().cloned().collect::<Vec<_>>();
+            atoms.sort();
+            writeln!(f, "\t\t{}: {{{}}}", i, atoms.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Trace {
+    /// Reads states written as `(p q)`, one per step, with a `loop` marker
+    /// before the first state of the repeating part
+    pub fn parse(lexer: &mut Lexer) -> Result<Trace, TraceError> {
+        let mut states = Vec::new();
+        let mut loop_start = None;
+        loop {
+            let token = lexer.next_token();
+            match *token.kind {
+                TokenKind::End => break,
+                TokenKind::String if *token.text == "loop" && loop_start.is_none() => {
+                    loop_start = Some(states.len());
+                }
+                TokenKind::LParen => {
+                    let mut state = HashSet::new();
+                    loop {
+                        let token = lexer.next_token();
+                        match *token.kind {
+                            TokenKind::RParen => break,
+                            TokenKind::String => {
+                                state.insert(token.text.to_string());
+                            }
+                            _ => return Err(TraceError::ExpectedState(token)),
+                        }
+                    }
+                    states.push(state);
+                }
+                _ => return Err(TraceError::ExpectedState(token)),
+            }
+        }
+        if states.is_empty() {
+            return Err(TraceError::EmptyTrace);
+        }
+        if loop_start == Some(states.len()) {
+            return Err(TraceError::EmptyLoop);
+        }
+        Ok(Trace { states, loop_start })
+    }
+
+    pub fn load(file_path: &str) -> Result<Trace, TraceError> {
+        let input = std::fs::read_to_string(file_path)
+            .map_err(|_| TraceError::Unreadable(Box::new(file_path.to_string())))?;
+        Trace::parse(&mut Lexer::new(&input, Some(file_path)))
+    }
+
+    fn successor(&self, i: usize) -> Option<usize> {
+        if i + 1 < self.states.len() {
+            Some(i + 1)
+        } else {
+            self.loop_start
+        }
+    }
+
+    // Positions whose successor satisfies `sat`; the last state of a finite
+    // trace has none, so it counts as `weak`
+    fn pre(&self, sat: &[bool], weak: bool) -> Vec<bool> {
+        (0..self.states.len())
+            .map(|i| self.successor(i).map_or(weak, |j| sat[j]))
+            .collect()
+    }
+
+    /// The truth value of a formula at every position of the trace. Until is
+    /// a least and release a greatest fixpoint over the positions; on a
+    /// finite trace `X` is strong, so it fails at the last state.
+    pub fn eval(&self, expr: &LogExpr) -> Result<Vec<bool>, TraceError> {
+        let n = self.states.len();
+        let unary = |op: &Operator, inner: &LogExpr| {
+            LogExpr::UnaryOp(Box::new(op.clone()), Box::new(inner.clone()))
+        };
+        match expr {
+            LogExpr::True => Ok(vec![true; n]),
+            LogExpr::False => Ok(vec![false; n]),
+            LogExpr::Atom(token) => Ok(self
+                .states
+                .iter()
+                .map(|state| state.contains(token.text.as_str()))
+                .collect()),
+            LogExpr::UnaryOp(op, inner) => {
+                let sat = self.eval(inner)?;
+                match **op {
+                    Operator::Not => Ok(sat.iter().map(|b| !b).collect()),
+                    Operator::Next => Ok(self.pre(&sat, false)),
+                    Operator::Eventually => {
+                        let until = vec![Box::new(LogExpr::True), inner.clone()];
+                        self.eval(&LogExpr::BinaryOp(Box::new(Operator::Until), until))
+                    }
+                    Operator::Always => {
+                        let negated = unary(&Operator::Eventually, &unary(&Operator::Not, inner));
+                        Ok(self.eval(&negated)?.iter().map(|b| !b).collect())
+                    }
+                    _ => Err(TraceError::NotTemporal(Box::new(expr.clone()))),
+                }
+            }
+            LogExpr::BinaryOp(op, exprs) => {
+                let parts = exprs
+                    .iter()
+                    .map(|e| self.eval(e))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match **op {
+                    Operator::And => Ok((0..n).map(|i| parts.iter().all(|p| p[i])).collect()),
+                    Operator::Or => Ok((0..n).map(|i| parts.iter().any(|p| p[i])).collect()),
+                    Operator::Imp => match LogExpr::imp_parts(exprs) {
+                        Some((lhs, rhs)) => {
+                            let (lhs, rhs) = (self.eval(&lhs)?, self.eval(&rhs)?);
+                            Ok((0..n).map(|i| !lhs[i] || rhs[i]).collect())
+                        }
+                        None => Ok(parts.into_iter().next().unwrap_or(vec![true; n])),
+                    },
+                    Operator::Until | Operator::Release => {
+                        let release = **op == Operator::Release;
+                        let (lhs, rhs) = (&parts[0], &parts[1]);
+                        let mut sat = vec![release; n];
+                        loop {
+                            let next = self.pre(&sat, release);
+                            let updated = (0..n)
+                                .map(|i| match release {
+                                    false => rhs[i] || (lhs[i] && next[i]),
+                                    true => rhs[i] && (lhs[i] || next[i]),
+                                })
+                                .collect::<Vec<_>>();
+                            if updated == sat {
+                                return Ok(sat);
+                            }
+                            sat = updated;
+                        }
+                    }
+                    _ => Err(TraceError::NotTemporal(Box::new(expr.clone()))),
+                }
+            }
+            _ => Err(TraceError::NotTemporal(Box::new(expr.clone()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(s: &str) -> Trace {
+        Trace::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn eval(trace: &Trace, s: &str) -> Vec<bool> {
+        let mut lexer = Lexer::new(s, None);
+        lexer.temporal = true;
+        trace.eval(&LogExpr::parse(&mut lexer).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn lassos_repeat_their_loop() {
+        let trace = trace("(p) loop (q) ()");
+        assert_eq!(trace.loop_start, Some(1));
+        assert_eq!(eval(&trace, "(X q)"), [true, false, true]);
+        assert_eq!(eval(&trace, "(G (F q))"), [true, true, true]);
+        assert_eq!(eval(&trace, "(F (G q))"), [false, false, false]);
+        assert_eq!(eval(&trace, "(G (not p))"), [false, true, true]);
+        assert_eq!(
+            eval(&trace, "(always (imp q (next (not q))))"),
+            [true, true, true]
+        );
+    }
+
+    #[test]
+    fn finite_traces_stop() {
+        let trace = trace("(p) (p) (p q)");
+        assert_eq!(trace.loop_start, None);
+        assert_eq!(eval(&trace, "(X p)"), [true, true, false]);
+        assert_eq!(eval(&trace, "(G p)"), [true, true, true]);
+        assert_eq!(eval(&trace, "(U p q)"), [true, true, true]);
+        assert_eq!(eval(&trace, "(R q p)"), [true, true, true]);
+        assert_eq!(eval(&trace, "(U p (not p))"), [false, false, false]);
+    }
+
+    #[test]
+    fn release_is_dual_to_until() {
+        let trace = trace("(a) (a b) loop (b) ()");
+        for (lhs, rhs) in [("a", "b"), ("b", "a"), ("(not a)", "b")] {
+            let until = eval(&trace, &format!("(not (U (not {}) (not {})))", lhs, rhs));
+            assert_eq!(eval(&trace, &format!("(R {} {})", lhs, rhs)), until);
+        }
+    }
+
+    #[test]
+    fn malformed_traces_and_formulas_are_refused() {
+        let parse = |s: &str| Trace::parse(&mut Lexer::new(s, None));
+        assert!(matches!(parse(""), Err(TraceError::EmptyTrace)));
+        assert!(matches!(parse("(p) loop"), Err(TraceError::EmptyLoop)));
+        assert!(matches!(parse("(p) q"), Err(TraceError::ExpectedState(_))));
+        assert!(matches!(parse("((p))"), Err(TraceError::ExpectedState(_))));
+        let trace = trace("(p)");
+        let modal = LogExpr::parse(&mut Lexer::new("(box p)", None)).unwrap();
+        assert!(matches!(
+            trace.eval(&modal),
+            Err(TraceError::NotTemporal(_))
+        ));
+    }
+}
@@ -1,12 +1,64 @@
+mod bdd;
+mod buchi;
+mod circuit;
+mod congruence;
+mod ctl;
+mod espresso;
+mod foresolution;
+mod fuzzy;
+mod herbrand;
+mod hilbert;
+mod intuitionistic;
+mod kmap;
 mod lexer;
+mod ltl;
+mod manyvalued;
+mod minimize;
+mod modal;
+mod modaltableau;
+mod model;
+mod modelfinder;
 mod parser;
+mod prenex;
+mod resolution;
+mod signature;
+mod tableau;
+use bdd::*;
+use buchi::*;
+use circuit::*;
+use congruence::*;
+use ctl::*;
+use espresso::*;
+use foresolution::*;
+use fuzzy::*;
+use herbrand::*;
+use hilbert::*;
+use intuitionistic::*;
+use kmap::*;
 use lexer::*;
+use ltl::*;
+use manyvalued::*;
+use minimize::*;
+use modal::*;
+use modaltableau::*;
+use model::*;
+use modelfinder::*;
 use parser::*;
-use std::collections::HashMap;
+use prenex::*;
+use resolution::*;
+use signature::*;
 use std::io::{self, Write};
+use tableau::*;
 
 fn main() {
     let mut rule_set = RuleSet::new();
+    let mut proof = Proof::new(None);
+    let mut signature = Signature::new();
+    let mut structure = Structure::new();
+    let mut kripke = Kripke::new();
+    let mut logic = Logic::Classical;
+    let mut semantics = Semantics::Classical;
+    let mut fuzzy = Fuzzy::new();
     loop {
         print!("RustyWFF> ");
         io::stdout().flush().unwrap();
@@ -15,15 +67,34 @@ fn main() {
         io::stdin().read_line(&mut input).unwrap();
 
         let mut lexer = Lexer::new(&input, None);
-        match Command::parse(&mut lexer) {
+        let parsed = Command::parse(&mut lexer);
+        if let Ok(command) = &parsed {
+            if let Err(e) = command
+                .exprs()
+                .into_iter()
+                .try_for_each(|e| signature.check(e))
+            {
+                println!("\tSignature error at {}.", e);
+                continue;
+            }
+            if let Some(name) = classical_verdict(command, logic, semantics) {
+                println!(
+                    "\tLogic error: {} decides classical validity; use :resolve instead.",
+                    name
+                );
+                continue;
+            }
+        }
+        match parsed {
             Ok(command) => match *command {
                 Command::QuitRepl => {
                     println!("Exiting RustyWFF...");
                     return;
                 }
                 Command::DefineRule { name, lhs, rhs } => {
-                    rule_set.add_rule(name, lhs, rhs);
+                    rule_set.add_rule(name.clone(), lhs, rhs);
                     println!("\tRule added:\n{}", rule_set);
+                    warn_classical(logic, &rule_set, &name);
                 }
                 Command::DeleteRule { name } => {
                     rule_set.delete_rule(name);
@@ -45,6 +116,366 @@ fn main() {
                         }
                     }
                 }
+                Command::Tableau { exprs } => match Tableau::new(&exprs) {
+                    Ok(tableau) => println!("{}", tableau),
+                    Err(e) => println!("\tTableau error: {}.", e),
+                },
+                Command::Resolve { premises, goal } if logic == Logic::Intuitionistic => {
+                    match IntuitionisticProof::new(&premises, goal.as_deref()) {
+                        Ok(proof) => println!("{}", proof),
+                        Err(e) => println!("\tLogic error: {}.", e),
+                    }
+                }
+                Command::Resolve { premises, goal } => {
+                    let first_order = premises
+                        .iter()
+                        .chain(goal.iter())
+                        .any(|e| e.is_first_order());
+                    if first_order {
+                        match prove_first_order(&premises, goal.as_deref()) {
+                            Ok(resolution) => println!("{}", resolution),
+                            Err(e) => println!("\tResolution error: {}.", e),
+                        }
+                    } else {
+                        match Resolution::prove(&premises, goal.as_deref()) {
+                            Ok(resolution) => println!("{}", resolution),
+                            Err(e) => println!("\tResolution error: {}.", e),
+                        }
+                    }
+                }
+                Command::DefineAxiom { name, schema } => {
+                    rule_set.add_axiom(name.clone(), schema);
+                    println!("\tAxiom added:\n{}", rule_set);
+                    warn_classical(logic, &rule_set, &name);
+                }
+                Command::DefineInference {
+                    name,
+                    premises,
+                    conclusion,
+                } => {
+                    rule_set.add_inference(name.clone(), premises, conclusion);
+                    println!("\tInference rule added:\n{}", rule_set);
+                    warn_classical(logic, &rule_set, &name);
+                }
+                Command::StartProof { goal: None } => {
+                    print!("{}", proof);
+                }
+                Command::StartProof { goal } => {
+                    proof = Proof::new(goal);
+                    print!("{}", proof);
+                }
+                Command::ProofStep { expr, rule, refs } => {
+                    match proof.add_step(&rule_set, expr, rule, refs) {
+                        Ok(n) => {
+                            println!("\t\t{}. {}", n, proof.lines[n - 1]);
+                            if proof.is_complete() {
+                                println!("\tGoal proved.");
+                            }
+                        }
+                        Err(e) => println!("\tStep rejected: {}.", e),
+                    }
+                }
+                Command::BindSymbol { kind, name, arity } => {
+                    signature.bind(kind, name, arity);
+                    println!("\tSymbol bound:\n{}", signature);
+                }
+                Command::ShowSignature => {
+                    print!("{}", signature);
+                }
+                Command::SetDomain { elements } => {
+                    structure.set_domain(elements);
+                    println!("\tDomain set:\n{}", structure);
+                }
+                Command::Interpret {
+                    kind,
+                    name,
+                    entries,
+                } => match structure.interpret(kind, name, entries) {
+                    Ok(()) => println!("\tInterpretation added:\n{}", structure),
+                    Err(e) => println!("\tModel error: {}.", e),
+                },
+                Command::EvaluateModel { expr } => {
+                    let env = Env::new();
+                    let mut reasons = Vec::new();
+                    match structure.eval(&expr, &env).and_then(|value| {
+                        structure
+                            .explain(&expr, &env, 0, &mut reasons)
+                            .map(|_| value)
+                    }) {
+                        Ok(value) => {
+                            println!("\t{} is {}", expr, value);
+                            for reason in reasons {
+                                println!("{}", reason);
+                            }
+                        }
+                        Err(e) => println!("\tModel error: {}.", e),
+                    }
+                }
+                Command::FindModel {
+                    max_size,
+                    premises,
+                    goal,
+                } => {
+                    // A model of the premises and the negated goal is a countermodel
+                    let label = if goal.is_some() {
+                        "Countermodel"
+                    } else {
+                        "Model"
+                    };
+                    let mut formulas = premises;
+                    formulas.extend(goal.map(|g| g.negate()));
+                    match ModelFinder::new(&formulas).find(&formulas, max_size) {
+                        Ok(Some(found)) => println!("\t{} found:\n{}", label, found),
+                        Ok(None) => println!("\tNo model with at most {} elements.", max_size),
+                        Err(e) => println!("\tModel error: {}.", e),
+                    }
+                }
+                Command::Prenex { expr } => match prenex(&expr) {
+                    Ok(prenex) => println!("\t{}", prenex),
+                    Err(e) => println!("\tPrenex error: {}.", e),
+                },
+                Command::Skolemize { expr } => match Skolemized::new(&expr) {
+                    Ok(skolemized) => println!("{}", skolemized),
+                    Err(e) => println!("\tPrenex error: {}.", e),
+                },
+                Command::Congruence { premises, goal } => {
+                    // The goal follows when the premises and its negation conflict
+                    let mut formulas = premises;
+                    formulas.extend(goal.iter().map(|g| g.negate()));
+                    match CongruenceClosure::new(&formulas) {
+                        Ok(mut closure) => {
+                            let decision = closure.decide();
+                            println!("{}", decision);
+                            if let (Some(goal), Decision::Unsatisfiable(_)) = (goal, decision) {
+                                println!("\tThe premises entail {}.", goal);
+                            }
+                        }
+                        Err(e) => println!("\tCongruence error: {}.", e),
+                    }
+                }
+                Command::FreeVariables { expr } => {
+                    let list = |names: Vec<String>| match names.is_empty() {
+                        true => "none".to_string(),
+                        false => names.join(", "),
+                    };
+                    println!("\tFree variables: {}", list(expr.free_variables()));
+                    println!("\tBound variables: {}", list(expr.bound_variables()));
+                    println!("\tMetavariables: {}", list(expr.metavariables()));
+                    if expr.is_closed() {
+                        println!("\tThe formula is closed.");
+                    } else {
+                        println!("\tUniversal closure: {}", expr.universal_closure());
+                    }
+                }
+                Command::SetWorlds { worlds } => {
+                    kripke.set_worlds(worlds);
+                    println!("\tWorlds set:\n{}", kripke);
+                }
+                Command::ShowKripke => {
+                    print!("{}", kripke);
+                }
+                Command::AddAccess { pairs } => match kripke.add_access(pairs) {
+                    Ok(()) => println!("\tAccessibility added:\n{}", kripke),
+                    Err(e) => println!("\tKripke error: {}.", e),
+                },
+                Command::SetValuation { atom, worlds } => {
+                    match kripke.set_valuation(atom, worlds) {
+                        Ok(()) => println!("\tValuation set:\n{}", kripke),
+                        Err(e) => println!("\tKripke error: {}.", e),
+                    }
+                }
+                Command::EvaluateAt { world, expr } => match kripke.eval(&expr, &world) {
+                    Ok(value) => println!("\t{} is {} at {}", expr, value, world),
+                    Err(e) => println!("\tKripke error: {}.", e),
+                },
+                Command::EvaluateGlobally { expr } => match kripke.failures(&expr) {
+                    Ok(failures) if failures.is_empty() => {
+                        println!("\t{} holds at every world", expr)
+                    }
+                    Ok(failures) => {
+                        let failures = failures.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+                        println!("\t{} fails at {}", expr, failures.join(", "))
+                    }
+                    Err(e) => println!("\tKripke error: {}.", e),
+                },
+                Command::ModalValidity { system, expr } => {
+                    if let Some(frame) = Frame::system(&system) {
+                        match ModalTableau::new(&system, frame, &expr) {
+                            Ok(tableau) => println!("{}", tableau),
+                            Err(e) => println!("\tKripke error: {}.", e),
+                        }
+                    }
+                }
+                Command::ModalRule { system, name } => {
+                    match (Frame::system(&system), rule_soundness(&rule_set, &name)) {
+                        (Some(frame), Some(formula)) => {
+                            match ModalTableau::new(&system, frame, &formula) {
+                                Ok(tableau) if tableau.is_valid() => {
+                                    println!("\tRule {} is sound in {}.", name, system)
+                                }
+                                Ok(tableau) => {
+                                    println!("{}", tableau);
+                                    println!("\tRule {} is unsound in {}.", name, system);
+                                }
+                                Err(e) => println!("\tKripke error: {}.", e),
+                            }
+                        }
+                        (_, None) => println!("\tNo rule or axiom named {}.", name),
+                        _ => {}
+                    }
+                }
+                Command::CheckTrace { file_path, expr } => {
+                    match Trace::load(&file_path).and_then(|trace| {
+                        let sat = trace.eval(&expr)?;
+                        Ok((trace, sat))
+                    }) {
+                        Ok((trace, sat)) => {
+                            print!("\tTrace:\n{}", trace);
+                            let verdict = if sat[0] { "holds" } else { "fails" };
+                            println!("\t{} {} on the trace", expr, verdict);
+                            let positions = (0..sat.len())
+                                .filter(|i| sat[*i])
+                                .map(|i| i.to_string())
+                                .collect::<Vec<_>>();
+                            println!("\t\ttrue at positions: {{{}}}", positions.join(", "));
+                        }
+                        Err(e) => println!("\tTrace error: {}.", e),
+                    }
+                }
+                Command::ShowBuchi { expr } => match Buchi::new(&expr) {
+                    Ok(buchi) => print!("{}", buchi),
+                    Err(e) => println!("\tTrace error: {}.", e),
+                },
+                Command::LtlSatisfiable { expr } => match LtlCheck::new(&expr, false) {
+                    Ok(check) => println!("{}", check),
+                    Err(e) => println!("\tTrace error: {}.", e),
+                },
+                Command::LtlValidity { expr } => match LtlCheck::new(&expr, true) {
+                    Ok(check) => println!("{}", check),
+                    Err(e) => println!("\tTrace error: {}.", e),
+                },
+                Command::ShowSystem { file_path } => {
+                    match TransitionSystem::load(&file_path).and_then(|system| system.explore()) {
+                        Ok(graph) => print!("\tTransition system:\n{}", graph),
+                        Err(e) => println!("\tSystem error: {}.", e),
+                    }
+                }
+                Command::CheckCtl { file_path, expr } => {
+                    match TransitionSystem::load(&file_path)
+                        .and_then(|system| system.explore())
+                        .and_then(|graph| CtlCheck::new(&graph, &expr))
+                    {
+                        Ok(check) => println!("{}", check),
+                        Err(e) => println!("\tSystem error: {}.", e),
+                    }
+                }
+                Command::SetLogic { logic: None } => {
+                    println!("\tLogic: {}", logic);
+                }
+                Command::SetLogic {
+                    logic: Some(chosen),
+                } => {
+                    logic = chosen;
+                    println!("\tLogic set: {}", logic);
+                }
+                Command::SetSemantics { semantics: None } => {
+                    println!("\tSemantics: {}", semantics);
+                }
+                Command::SetSemantics {
+                    semantics: Some(chosen),
+                } => {
+                    semantics = chosen;
+                    println!("\tSemantics set: {}", semantics);
+                }
+                Command::TruthTable { expr } => match TruthTable::new(semantics, &expr) {
+                    Ok(table) => println!("{}", table),
+                    Err(e) => println!("\tSemantics error: {}.", e),
+                },
+                Command::Consequence { premises, goal } => {
+                    match Consequence::new(semantics, &premises, &goal) {
+                        Ok(consequence) => println!("{}", consequence),
+                        Err(e) => println!("\tSemantics error: {}.", e),
+                    }
+                }
+                Command::SetTNorm { tnorm: None } => {
+                    println!("\tt-norm: {}", fuzzy.tnorm);
+                }
+                Command::SetTNorm { tnorm: Some(tnorm) } => {
+                    fuzzy.tnorm = tnorm;
+                    println!("\tt-norm set:\n{}", fuzzy);
+                }
+                Command::ShowDegrees => {
+                    print!("{}", fuzzy);
+                }
+                Command::AssignDegree { atom, degree } => {
+                    fuzzy.assign(atom, degree);
+                    println!("\tDegree assigned:\n{}", fuzzy);
+                }
+                Command::EvaluateDegree { expr } => match fuzzy.eval(&expr) {
+                    Ok(degree) => println!("\t{} has degree {}", expr, show_degree(degree)),
+                    Err(e) => println!("\tFuzzy error: {}.", e),
+                },
+                Command::ShowBdd { order, expr } => match BddReport::new(&expr, order) {
+                    Ok(report) => println!("{}", report),
+                    Err(e) => println!("\tBDD error: {}.", e),
+                },
+                Command::BddEquivalent { lhs, rhs } => match BddEquivalence::new(&lhs, &rhs) {
+                    Ok(equivalence) => println!("{}", equivalence),
+                    Err(e) => println!("\tBDD error: {}.", e),
+                },
+                Command::WriteBddDot {
+                    file_path,
+                    order,
+                    expr,
+                } => match write_dot(&file_path, &expr, order) {
+                    Ok(size) => println!("\tWrote {} nodes to {}", size, file_path),
+                    Err(e) => println!("\tBDD error: {}.", e),
+                },
+                Command::Minimize { expr, dont_care } => {
+                    match Minimization::new(&expr, dont_care.as_deref()) {
+                        Ok(minimization) => println!("{}", minimization),
+                        Err(e) => println!("\tMinimize error: {}.", e),
+                    }
+                }
+                Command::Espresso {
+                    form,
+                    expr,
+                    dont_care,
+                } => match Espresso::new(form, &expr, dont_care.as_deref()) {
+                    Ok(espresso) => println!("{}", espresso),
+                    Err(e) => println!("\tMinimize error: {}.", e),
+                },
+                Command::KarnaughMap {
+                    groups,
+                    expr,
+                    dont_care,
+                } => match KarnaughMap::new(&expr, dont_care.as_deref(), groups) {
+                    Ok(map) => println!("{}", map),
+                    Err(e) => println!("\tMinimize error: {}.", e),
+                },
+                Command::ShowCircuit { form, expr } => match CircuitReport::new(form, &expr) {
+                    Ok(report) => println!("{}", report),
+                    Err(e) => println!("\tCircuit error: {}.", e),
+                },
+                Command::Herbrand { depth, expr } => match Herbrand::new(&expr, depth) {
+                    Ok(herbrand) => println!("{}", herbrand),
+                    Err(e) => println!("\tHerbrand error: {}.", e),
+                },
+                Command::GroundInstances { depth, expr } => match Herbrand::new(&expr, depth) {
+                    Ok(herbrand) => {
+                        println!("{}", herbrand);
+                        match herbrand.instances() {
+                            Ok(instances) => {
+                                println!("\tGround instances ({}):", instances.len());
+                                for (i, instance) in instances.iter().enumerate() {
+                                    println!("\t\t{}. {}", i + 1, instance);
+                                }
+                            }
+                            Err(e) => println!("\tHerbrand error: {}.", e),
+                        }
+                    }
+                    Err(e) => println!("\tHerbrand error: {}.", e),
+                },
                 _ => {
                     println!("{:?}", command);
                 }
@@ -55,3 +486,67 @@ fn main() {
         }
     }
 }
+
+// Commands whose verdict is classical validity, which the intuitionistic
+// mode must not report; only :resolve has an intuitionistic prover
+fn classical_verdict(command: &Command, logic: Logic, semantics: Semantics) -> Option<&str> {
+    if logic != Logic::Intuitionistic {
+        return None;
+    }
+    match command {
+        Command::Tableau { .. } => Some(":tableau"),
+        Command::Consequence { .. } if semantics == Semantics::Classical => Some(":valid"),
+        Command::ShowBdd { .. } => Some(":bdd"),
+        Command::BddEquivalent { .. } => Some(":bddequiv"),
+        _ => None,
+    }
+}
+
+// Rules that only hold classically are still added, but flagged
+fn warn_classical(logic: Logic, rule_set: &RuleSet, name: &str) {
+    if logic == Logic::Intuitionistic && classical_only(rule_set, name) {
+        println!("\tWarning: {} is only classically valid.", name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verdict(s: &str, logic: Logic, semantics: Semantics) -> Option<String> {
+        let command = Command::parse(&mut Lexer::new(s, None)).unwrap();
+        classical_verdict(&command, logic, semantics).map(|name| name.to_string())
+    }
+
+    #[test]
+    fn classical_deciders_are_refused_intuitionistically() {
+        let intuitionistic = Logic::Intuitionistic;
+        let classical = Semantics::Classical;
+        assert_eq!(
+            verdict(":tableau (or p (not p))", intuitionistic, classical).as_deref(),
+            Some(":tableau")
+        );
+        assert_eq!(
+            verdict(":valid (or p (not p))", intuitionistic, classical).as_deref(),
+            Some(":valid")
+        );
+        assert_eq!(
+            verdict(":bdd (or p (not p))", intuitionistic, classical).as_deref(),
+            Some(":bdd")
+        );
+        assert_eq!(
+            verdict(":bddequiv p (not (not p))", intuitionistic, classical).as_deref(),
+            Some(":bddequiv")
+        );
+    }
+
+    #[test]
+    fn other_logics_and_commands_pass() {
+        let command = ":tableau (or p (not p))";
+        assert!(verdict(command, Logic::Classical, Semantics::Classical).is_none());
+        let command = ":valid (or p (not p))";
+        assert!(verdict(command, Logic::Intuitionistic, Semantics::K3).is_none());
+        let command = ":resolve (or p (not p))";
+        assert!(verdict(command, Logic::Intuitionistic, Semantics::Classical).is_none());
+    }
+}
@@ -0,0 +1,515 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use crate::parser::*;
+
+// Every assignment of values to atoms is tried
+const ASSIGNMENT_LIMIT: usize = 65536;
+
+/// A truth-value semantics for propositional formulas. The linear ones
+/// order their values from false to true; FDE has the four values of
+/// Belnap's lattice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Semantics {
+    Classical,
+    K3,
+    Lp,
+    Fde,
+    // Łukasiewicz with n values, n ≥ 2
+    Lukasiewicz(usize),
+}
+
+impl Display for Semantics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Semantics::Classical => write!(f, "classical"),
+            Semantics::K3 => write!(f, "K3"),
+            Semantics::Lp => write!(f, "LP"),
+            Semantics::Fde => write!(f, "FDE"),
+            Semantics::Lukasiewicz(n) => write!(f, "Ł{}", n),
+        }
+    }
+}
+
+/// A truth value, as its index among the values of the semantics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Value(pub usize);
+
+impl Semantics {
+    pub fn from_str(s: &str) -> Option<Semantics> {
+        match s {
+            "classical" => Some(Semantics::Classical),
+            "K3" => Some(Semantics::K3),
+            "LP" => Some(Semantics::Lp),
+            "FDE" => Some(Semantics::Fde),
+            _ => {
+                let n = s
+                    .strip_prefix('L')
+                    .or_else(|| s.strip_prefix('Ł'))?
+                    .parse::<usize>()
+                    .ok()?;
+                (n >= 2).then_some(Semantics::Lukasiewicz(n))
+            }
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        match self {
+            Semantics::Classical => 2,
+            Semantics::K3 | Semantics::Lp => 3,
+            Semantics::Fde => 4,
+            Semantics::Lukasiewicz(n) => *n,
+        }
+    }
+
+    // The greatest value; FDE counts its values f, n, b, t
+    fn top(&self) -> usize {
+        self.count() - 1
+    }
+
+    // Whether an FDE value is told true, and whether it is told false
+    fn told(value: Value) -> (bool, bool) {
+        match value.0 {
+            0 => (false, true),
+            1 => (false, false),
+            2 => (true, true),
+            _ => (true, false),
+        }
+    }
+
+    fn untold(told_true: bool, told_false: bool) -> Value {
+        match (told_true, told_false) {
+            (false, true) => Value(0),
+            (false, false) => Value(1),
+            (true, true) => Value(2),
+            (true, false) => Value(3),
+        }
+    }
+
+    pub fn name(&self, value: Value) -> String {
+        match (self, value.0) {
+            (Semantics::Lukasiewicz(_), 0) => "0".to_string(),
+            (Semantics::Lukasiewicz(_), v) if v == self.top() => "1".to_string(),
+            (Semantics::Lukasiewicz(_), v) => {
+                let (mut a, mut b) = (v, self.top());
+                while b != 0 {
+                    (a, b) = (b, a % b);
+                }
+                format!("{}/{}", v / a, self.top() / a)
+            }
+            (_, 0) => "false".to_string(),
+            (_, v) if v == self.top() => "true".to_string(),
+            (Semantics::K3, _) => "unknown".to_string(),
+            (Semantics::Lp, _) | (Semantics::Fde, 2) => "both".to_string(),
+            _ => "neither".to_string(),
+        }
+    }
+
+    pub fn values(&self) -> Vec<Value> {
+        (0..self.count()).map(Value).collect()
+    }
+
+    pub fn designated(&self, value: Value) -> bool {
+        match self {
+            Semantics::Lp => value.0 >= 1,
+            Semantics::Fde => Semantics::told(value).0,
+            _ => value.0 == self.top(),
+        }
+    }
+
+    fn constant(&self, expr: &LogExpr) -> Option<Value> {
+        let top = self.top();
+        match (self, expr) {
+            (_, LogExpr::True) => Some(Value(top)),
+            (_, LogExpr::False) => Some(Value(0)),
+            (Semantics::K3 | Semantics::Fde, LogExpr::Unknown) => Some(Value(1)),
+            (Semantics::Lukasiewicz(_), LogExpr::Unknown) if top.is_multiple_of(2) => {
+                Some(Value(top / 2))
+            }
+            (Semantics::Lp, LogExpr::Both) => Some(Value(1)),
+            (Semantics::Fde, LogExpr::Both) => Some(Value(2)),
+            _ => None,
+        }
+    }
+
+    pub fn not(&self, a: Value) -> Value {
+        match self {
+            Semantics::Fde => {
+                let (t, f) = Semantics::told(a);
+                Semantics::untold(f, t)
+            }
+            _ => Value(self.top() - a.0),
+        }
+    }
+
+    pub fn and(&self, a: Value, b: Value) -> Value {
+        match self {
+            Semantics::Fde => {
+                let ((at, af), (bt, bf)) = (Semantics::told(a), Semantics::told(b));
+                Semantics::untold(at && bt, af || bf)
+            }
+            _ => Value(a.0.min(b.0)),
+        }
+    }
+
+    pub fn or(&self, a: Value, b: Value) -> Value {
+        self.not(self.and(self.not(a), self.not(b)))
+    }
+
+    /// Material implication, except in Łukasiewicz logics where it is
+    /// `min(1, 1 - a + b)`
+    pub fn imp(&self, a: Value, b: Value) -> Value {
+        match self {
+            Semantics::Lukasiewicz(_) => Value((self.top() - a.0 + b.0).min(self.top())),
+            _ => self.or(self.not(a), b),
+        }
+    }
+
+    pub fn eval(
+        &self,
+        expr: &LogExpr,
+        assignment: &HashMap<String, Value>,
+    ) -> Result<Value, SemanticsError> {
+        match expr {
+            LogExpr::Atom(token) | LogExpr::Var(token) => Ok(assignment
+                .get(token.text.as_str())
+                .cloned()
+                .unwrap_or(Value(0))),
+            LogExpr::True | LogExpr::False | LogExpr::Unknown | LogExpr::Both => self
+                .constant(expr)
+                .ok_or(SemanticsError::NoSuchValue(Box::new(expr.clone()), *self)),
+            LogExpr::UnaryOp(op, inner) if **op == Operator::Not => {
+                Ok(self.not(self.eval(inner, assignment)?))
+            }
+            LogExpr::BinaryOp(op, exprs) => match **op {
+                Operator::And | Operator::Or => {
+                    let mut value = Value(if **op == Operator::And { self.top() } else { 0 });
+                    for e in exprs {
+                        let v = self.eval(e, assignment)?;
+                        value = match **op {
+                            Operator::And => self.and(value, v),
+                            _ => self.or(value, v),
+                        };
+                    }
+                    Ok(value)
+                }
+                Operator::Imp => match LogExpr::imp_parts(exprs) {
+                    Some((lhs, rhs)) => {
+                        Ok(self.imp(self.eval(&lhs, assignment)?, self.eval(&rhs, assignment)?))
+                    }
+                    None => match exprs.first() {
+                        Some(e) => self.eval(e, assignment),
+                        None => Ok(Value(self.top())),
+                    },
+                },
+                _ => Err(SemanticsError::NotPropositional(Box::new(expr.clone()))),
+            },
+            _ => Err(SemanticsError::NotPropositional(Box::new(expr.clone()))),
+        }
+    }
+
+    // Every assignment of values to the atoms, the first atom varying slowest
+    fn assignments(&self, atoms: &[String]) -> Result<Vec<Vec<Value>>, SemanticsError> {
+        let total = (0..atoms.len()).try_fold(1usize, |total, _| {
+            total
+                .checked_mul(self.count())
+                .filter(|total| *total <= ASSIGNMENT_LIMIT)
+        });
+        let total = total.ok_or(SemanticsError::TooManyAssignments(atoms.len(), *self))?;
+        Ok((0..total)
+            .map(|mut index| {
+                let mut row = vec![Value(0); atoms.len()];
+                for slot in row.iter_mut().rev() {
+                    *slot = Value(index % self.count());
+                    index /= self.count();
+                }
+                row
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SemanticsError {
+    NoSuchValue(Box<LogExpr>, Semantics),
+    NotPropositional(Box<LogExpr>),
+    TooManyAssignments(usize, Semantics),
+}
+
+impl Display for SemanticsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SemanticsError::NoSuchValue(expr, semantics) => {
+                write!(f, "{} is not a value of {}", expr, semantics)
+            }
+            SemanticsError::NotPropositional(expr) => {
+                write!(f, "{} is not a propositional formula", expr)
+            }
+            SemanticsError::TooManyAssignments(atoms, semantics) => write!(
+                f,
+                "{} atoms have more than {} assignments in {}",
+                atoms, ASSIGNMENT_LIMIT, semantics
+            ),
+        }
+    }
+}
+
+// The atoms and metavariables of the formulas, in order of appearance
+fn atoms_of(exprs: &[&LogExpr]) -> Vec<String> {
+    fn collect(expr: &LogExpr, atoms: &mut Vec<String>) {
+        match expr {
+            LogExpr::Atom(token) | LogExpr::Var(token) if !atoms.contains(&token.text) => {
+                atoms.push(token.text.to_string());
+            }
+            LogExpr::UnaryOp(_, inner) => collect(inner, atoms),
+            LogExpr::BinaryOp(_, exprs) => exprs.iter().for_each(|e| collect(e, atoms)),
+            _ => {}
+        }
+    }
+    let mut atoms = Vec::new();
+    exprs.iter().for_each(|e| collect(e, &mut atoms));
+    atoms
+}
+
+fn bind(atoms: &[String], row: &[Value]) -> HashMap<String, Value> {
+    atoms.iter().cloned().zip(row.iter().cloned()).collect()
+}
+
+/// The value of a formula under every assignment to its atoms
+#[derive(Debug, Clone)]
+pub struct TruthTable {
+    pub semantics: Semantics,
+    pub expr: Box<LogExpr>,
+    pub atoms: Vec<String>,
+    pub rows: Vec<(Vec<Value>, Value)>,
+}
+
+impl TruthTable {
+    pub fn new(semantics: Semantics, expr: &LogExpr) -> Result<TruthTable, SemanticsError> {
+        let atoms = atoms_of(&[expr]);
+        let mut rows = Vec::new();
+        for row in semantics.assignments(&atoms)? {
+            let value = semantics.eval(expr, &bind(&atoms, &row))?;
+            rows.push((row, value));
+        }
+        Ok(TruthTable {
+            semantics,
+            expr: Box::new(expr.clone()),
+            atoms,
+            rows,
+        })
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.rows
+            .iter()
+            .all(|(_, value)| self.semantics.designated(*value))
+    }
+}
+
+impl Display for TruthTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let semantics = self.semantics;
+        let designated = semantics
+            .values()
+            .into_iter()
+            .filter(|v| semantics.designated(*v))
+            .map(|v| semantics.name(v))
+            .collect::<Vec<_>>();
+        writeln!(
+            f,
+            "\tSemantics: {} (designated: {})",
+            semantics,
+            designated.join(", ")
+        )?;
+        let mut header = self.atoms.clone();
+        header.push(self.expr.to_string());
+        writeln!(f, "\t\t{}", header.join("\t"))?;
+        for (row, value) in &self.rows {
+            let mut cells = row.iter().map(|v| semantics.name(*v)).collect::<Vec<_>>();
+            let mark = if semantics.designated(*value) {
+                " *"
+            } else {
+                ""
+            };
+            cells.push(format!("{}{}", semantics.name(*value), mark));
+            writeln!(f, "\t\t{}", cells.join("\t"))?;
+        }
+        match self.is_valid() {
+            true => write!(f, "\t{} is valid in {}.", self.expr, semantics),
+            false => write!(f, "\t{} is not valid in {}.", self.expr, semantics),
+        }
+    }
+}
+
+/// Designated-value consequence: every assignment designating all the
+/// premises designates the goal
+#[derive(Debug, Clone)]
+pub struct Consequence {
+    pub semantics: Semantics,
+    pub goal: Box<LogExpr>,
+    pub entailment: bool,
+    pub counterexample: Option<Vec<(String, Value)>>,
+}
+
+impl Consequence {
+    pub fn new(
+        semantics: Semantics,
+        premises: &[Box<LogExpr>],
+        goal: &LogExpr,
+    ) -> Result<Consequence, SemanticsError> {
+        let mut exprs = premises.iter().map(|p| &**p).collect::<Vec<_>>();
+        exprs.push(goal);
+        let atoms = atoms_of(&exprs);
+        let mut consequence = Consequence {
+            semantics,
+            goal: Box::new(goal.clone()),
+            entailment: !premises.is_empty(),
+            counterexample: None,
+        };
+        for row in semantics.assignments(&atoms)? {
+            let assignment = bind(&atoms, &row);
+            let mut holds = true;
+            for premise in premises {
+                holds &= semantics.designated(semantics.eval(premise, &assignment)?);
+            }
+            if holds && !semantics.designated(semantics.eval(goal, &assignment)?) {
+                consequence.counterexample = Some(atoms.iter().cloned().zip(row).collect());
+                break;
+            }
+        }
+        Ok(consequence)
+    }
+}
+
+impl Display for Consequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (goal, semantics) = (&self.goal, self.semantics);
+        match (&self.counterexample, self.entailment) {
+            (None, true) => write!(f, "\tThe premises entail {} in {}.", goal, semantics),
+            (None, false) => write!(f, "\t{} is valid in {}.", goal, semantics),
+            (Some(assignment), entailment) => {
+                match entailment {
+                    true => write!(f, "\tThe premises do not entail {} in {}.", goal, semantics)?,
+                    false => write!(f, "\t{} is not valid in {}.", goal, semantics)?,
+                }
+                let assignment = assignment
+                    .iter()
+                    .map(|(atom, value)| format!("{} = {}", atom, semantics.name(*value)))
+                    .collect::<Vec<_>>();
+                match assignment.is_empty() {
+                    true => write!(f, " Counterexample: any assignment"),
+                    false => write!(f, " Counterexample: {}", assignment.join(", ")),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn semantics(s: &str) -> Semantics {
+        Semantics::from_str(s).unwrap()
+    }
+
+    fn entails(s: &str, premises: &[&str], goal: &str) -> bool {
+        let premises = premises.iter().map(|p| expr(p)).collect::<Vec<_>>();
+        Consequence::new(semantics(s), &premises, &expr(goal))
+            .unwrap()
+            .counterexample
+            .is_none()
+    }
+
+    fn valid(s: &str, goal: &str) -> bool {
+        TruthTable::new(semantics(s), &expr(goal))
+            .unwrap()
+            .is_valid()
+    }
+
+    #[test]
+    fn lp_fails_modus_ponens() {
+        assert!(!entails("LP", &["p", "(imp p q)"], "q"));
+        let premises = [expr("p"), expr("(imp p q)")];
+        let consequence = Consequence::new(Semantics::Lp, &premises, &expr("q")).unwrap();
+        let names = consequence
+            .counterexample
+            .unwrap()
+            .into_iter()
+            .map(|(atom, value)| format!("{} = {}", atom, Semantics::Lp.name(value)))
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["p = both", "q = false"]);
+        assert!(entails("K3", &["p", "(imp p q)"], "q"));
+        assert!(entails("classical", &["p", "(imp p q)"], "q"));
+    }
+
+    #[test]
+    fn gluts_and_gaps_split_excluded_middle_and_explosion() {
+        assert!(valid("LP", "(or p (not p))"));
+        assert!(!valid("K3", "(or p (not p))"));
+        assert!(!entails("LP", &["p", "(not p)"], "q"));
+        assert!(entails("K3", &["p", "(not p)"], "q"));
+        assert!(!valid("FDE", "(or p (not p))"));
+        assert!(!entails("FDE", &["p", "(not p)"], "q"));
+        assert!(!valid("FDE", "(imp p p)"));
+        assert!(entails("FDE", &["(and p q)"], "(or q r)"));
+        assert!(entails("FDE", &["(not (or p q))"], "(and (not p) (not q))"));
+    }
+
+    #[test]
+    fn lukasiewicz_implication_is_not_material() {
+        assert!(valid("L3", "(imp p p)"));
+        assert!(!valid("L3", "(or p (not p))"));
+        assert!(!valid("K3", "(imp p p)"));
+        assert!(valid("L5", "(imp (and p q) p)"));
+        assert!(!valid("L3", "(imp (imp p (imp p q)) (imp p q))"));
+        let l5 = semantics("Ł5");
+        let names = l5
+            .values()
+            .into_iter()
+            .map(|v| l5.name(v))
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["0", "1/4", "1/2", "3/4", "1"]);
+    }
+
+    #[test]
+    fn semantics_are_named() {
+        assert_eq!(Semantics::from_str("L2"), Some(Semantics::Lukasiewicz(2)));
+        assert_eq!(Semantics::from_str("L1"), None);
+        assert_eq!(Semantics::from_str("S5"), None);
+        assert_eq!(Semantics::Fde.name(Value(1)), "neither");
+        assert_eq!(Semantics::K3.name(Value(1)), "unknown");
+    }
+
+    #[test]
+    fn missing_values_and_large_tables_are_refused() {
+        let table = |s: &str, e: &str| TruthTable::new(semantics(s), &expr(e));
+        assert!(matches!(
+            table("LP", "(or p unknown)"),
+            Err(SemanticsError::NoSuchValue(..))
+        ));
+        assert!(matches!(
+            table("K3", "both"),
+            Err(SemanticsError::NoSuchValue(..))
+        ));
+        assert!(matches!(
+            table("L4", "unknown"),
+            Err(SemanticsError::NoSuchValue(..))
+        ));
+        assert!(table("L5", "unknown").is_ok());
+        assert!(matches!(
+            table("K3", "(box p)"),
+            Err(SemanticsError::NotPropositional(_))
+        ));
+        assert!(matches!(
+            table("FDE", "(and p1 p2 p3 p4 p5 p6 p7 p8 p9)"),
+            Err(SemanticsError::TooManyAssignments(9, Semantics::Fde))
+        ));
+    }
+}
@@ -0,0 +1,496 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::bdd::*;
+use crate::parser::*;
+
+// Quine–McCluskey works on minterms, so the atoms are bounded; at twelve an
+// optimized build still minimizes in well under a second
+pub const MINTERM_ATOM_LIMIT: usize = 12;
+// Products kept while Petrick's method multiplies out the chart
+const PETRICK_LIMIT: usize = 4096;
+
+#[derive(Debug, Clone)]
+pub enum MinimizeError {
+    Bdd(BddError),
+    TooManyAtoms(usize, usize),
+}
+
+impl Display for MinimizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MinimizeError::Bdd(e) => write!(f, "{}", e),
+            MinimizeError::TooManyAtoms(atoms, limit) => {
+                write!(f, "{} atoms are more than the limit of {}", atoms, limit)
+            }
+        }
+    }
+}
+
+/// A conjunction of literals: bit i of `care` says whether atom i occurs,
+/// and bit i of `value` whether it occurs positively
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cube {
+    pub care: u64,
+    pub value: u64,
+}
+
+// The bits of the first n atoms
+pub fn atom_mask(n: usize) -> u64 {
+    match n {
+        64.. => u64::MAX,
+        _ => (1 << n) - 1,
+    }
+}
+
+impl Cube {
+    pub fn literals(&self) -> u32 {
+        self.care.count_ones()
+    }
+
+    pub fn contains(&self, other: &Cube) -> bool {
+        self.care & !other.care == 0 && other.value & self.care == self.value
+    }
+
+    pub fn intersects(&self, other: &Cube) -> bool {
+        (self.value ^ other.value) & self.care & other.care == 0
+    }
+
+    // Fewer literals first, then by the atoms used, earliest atoms first
+    pub fn rank(&self) -> (u32, std::cmp::Reverse<u64>, u64) {
+        (
+            self.literals(),
+            std::cmp::Reverse(self.care.reverse_bits()),
+            (!self.value & self.care).reverse_bits(),
+        )
+    }
+
+    pub fn covers(&self, minterm: u64) -> bool {
+        minterm & self.care == self.value
+    }
+
+    /// The minterms of the cube over the first n atoms
+    pub fn minterms(&self, n: usize) -> Vec<u64> {
+        let free = !self.care & atom_mask(n);
+        let mut minterms = Vec::new();
+        let mut sub = free;
+        loop {
+            minterms.push(self.value | sub);
+            if sub == 0 {
+                break;
+            }
+            sub = (sub - 1) & free;
+        }
+        minterms
+    }
+
+    pub fn from_path(path: &[(usize, bool)]) -> Cube {
+        path.iter()
+            .fold(Cube { care: 0, value: 0 }, |cube, (level, value)| Cube {
+                care: cube.care | 1 << level,
+                value: cube.value | (*value as u64) << level,
+            })
+    }
+
+    pub fn to_expr(self, atoms: &[String]) -> Box<LogExpr> {
+        let mut literals = Vec::new();
+        for (i, atom) in atoms.iter().enumerate() {
+            if self.care & 1 << i != 0 {
+                let literal = LogExpr::atom(atom);
+                literals.push(match self.value & 1 << i {
+                    0 => literal.negate(),
+                    _ => literal,
+                });
+            }
+        }
+        match literals.len() {
+            0 => Box::new(LogExpr::True),
+            1 => literals.remove(0),
+            _ => Box::new(LogExpr::BinaryOp(Box::new(Operator::And), literals)),
+        }
+    }
+}
+
+/// The clause excluding the cube, its literals flipped and disjoined
+pub fn excluding_clause(cube: Cube, atoms: &[String]) -> Box<LogExpr> {
+    let mut literals = Vec::new();
+    for (i, atom) in atoms.iter().enumerate() {
+        if cube.care & 1 << i != 0 {
+            let literal = LogExpr::atom(atom);
+            literals.push(match cube.value & 1 << i {
+                0 => literal,
+                _ => literal.negate(),
+            });
+        }
+    }
+    match literals.len() {
+        0 => Box::new(LogExpr::False),
+        1 => literals.remove(0),
+        _ => Box::new(LogExpr::BinaryOp(Box::new(Operator::Or), literals)),
+    }
+}
+
+/// The conjunction of the clauses excluding each cube of a cover of the
+/// negation, `true` when there are none
+pub fn product_of_sums(cubes: &[Cube], atoms: &[String]) -> Box<LogExpr> {
+    let mut clauses = cubes
+        .iter()
+        .map(|c| excluding_clause(*c, atoms))
+        .collect::<Vec<_>>();
+    match clauses.len() {
+        0 => Box::new(LogExpr::True),
+        1 => clauses.remove(0),
+        _ => Box::new(LogExpr::BinaryOp(Box::new(Operator::And), clauses)),
+    }
+}
+
+/// The disjunction of the cubes, `false` when there are none
+pub fn sum_of_products(cubes: &[Cube], atoms: &[String]) -> Box<LogExpr> {
+    let mut terms = cubes.iter().map(|c| c.to_expr(atoms)).collect::<Vec<_>>();
+    match terms.len() {
+        0 => Box::new(LogExpr::False),
+        1 => terms.remove(0),
+        _ => Box::new(LogExpr::BinaryOp(Box::new(Operator::Or), terms)),
+    }
+}
+
+/// A formula split into cube covers over its atoms, in order of appearance
+#[derive(Debug, Clone)]
+pub struct CubeSets {
+    pub atoms: Vec<String>,
+    pub on: Vec<Cube>,
+    pub dont_care: Vec<Cube>,
+    pub off: Vec<Cube>,
+}
+
+impl CubeSets {
+    /// The covers of a formula, or of its negation, where the don't-care
+    /// condition is taken out of both the on-set and the off-set
+    pub fn new(
+        expr: &LogExpr,
+        dont_care: Option<&LogExpr>,
+        negated: bool,
+    ) -> Result<CubeSets, MinimizeError> {
+        let exprs = std::iter::once(expr).chain(dont_care).collect::<Vec<_>>();
+        let atoms = BddOrder::Appearance
+            .order(&exprs)
+            .map_err(MinimizeError::Bdd)?;
+        if atoms.len() > 64 {
+            return Err(MinimizeError::TooManyAtoms(atoms.len(), 64));
+        }
+        let mut bdd = Bdd::new(atoms.clone());
+        let mut f = bdd.build(expr).map_err(MinimizeError::Bdd)?;
+        if negated {
+            f = bdd.not(f);
+        }
+        let d = match dont_care {
+            Some(d) => bdd.build(d).map_err(MinimizeError::Bdd)?,
+            None => FALSE,
+        };
+        let not_d = bdd.not(d);
+        let on = bdd.ite(f, not_d, FALSE);
+        let off = bdd.ite(f, FALSE, not_d);
+        let cubes = |root: usize| {
+            bdd.paths(root)
+                .iter()
+                .map(|path| Cube::from_path(path))
+                .collect::<Vec<_>>()
+        };
+        Ok(CubeSets {
+            on: cubes(on),
+            dont_care: cubes(d),
+            off: cubes(off),
+            atoms,
+        })
+    }
+}
+
+// Merges cubes differing in one literal until nothing merges; what never
+// merged is prime
+fn prime_implicants(minterms: &[u64], n: usize) -> Vec<Cube> {
+    let mut current = minterms
+        .iter()
+        .map(|m| Cube {
+            care: atom_mask(n),
+            value: *m,
+        })
+        .collect::<HashSet<_>>();
+    let mut primes: Vec<Cube> = Vec::new();
+    while !current.is_empty() {
+        let mut next = HashSet::new();
+        let mut merged = HashSet::new();
+        for cube in &current {
+            for bit in (0..n).map(|i| 1u64 << i) {
+                if cube.care & bit == 0 || cube.value & bit != 0 {
+                    continue;
+                }
+                let partner = Cube {
+                    care: cube.care,
+                    value: cube.value | bit,
+                };
+                if current.contains(&partner) {
+                    next.insert(Cube {
+                        care: cube.care & !bit,
+                        value: cube.value,
+                    });
+                    merged.insert(*cube);
+                    merged.insert(partner);
+                }
+            }
+        }
+        primes.extend(current.iter().filter(|c| !merged.contains(c)));
+        current = next;
+    }
+    primes.sort_by_key(Cube::rank);
+    primes
+}
+
+// Multiplies out the product of sums "some prime covering each minterm",
+// keeping products absorbed. Returns the cheapest product and whether the
+// search was exhaustive.
+fn petrick(clauses: &[Vec<usize>], primes: &[Cube]) -> (Vec<usize>, bool) {
+    let cost = |product: &Vec<usize>| {
+        let literals = product.iter().map(|i| primes[*i].literals()).sum::<u32>();
+        (product.len(), literals)
+    };
+    let mut products: Vec<Vec<usize>> = vec![Vec::new()];
+    let mut exact = true;
+    for clause in clauses {
+        let mut next: Vec<Vec<usize>> = Vec::new();
+        for product in &products {
+            if clause.iter().any(|i| product.contains(i)) {
+                next.push(product.clone());
+                continue;
+            }
+            for i in clause {
+                let mut extended = product.clone();
+                extended.push(*i);
+                extended.sort();
+                next.push(extended);
+            }
+        }
+        next.sort_by_key(|p| (p.len(), p.clone()));
+        next.dedup();
+        let mut absorbed: Vec<Vec<usize>> = Vec::new();
+        for product in next {
+            if !absorbed
+                .iter()
+                .any(|kept| kept.iter().all(|i| product.contains(i)))
+            {
+                absorbed.push(product);
+            }
+        }
+        if absorbed.len() > PETRICK_LIMIT {
+            absorbed.sort_by_key(cost);
+            absorbed.truncate(PETRICK_LIMIT);
+            exact = false;
+        }
+        products = absorbed;
+    }
+    let best = products.into_iter().min_by_key(cost).unwrap_or_default();
+    (best, exact)
+}
+
+/// A minimal sum of products found by Quine–McCluskey and Petrick's method
+#[derive(Debug, Clone)]
+pub struct Minimization {
+    pub expr: Box<LogExpr>,
+    pub dont_care: Option<Box<LogExpr>>,
+    pub atoms: Vec<String>,
+    pub primes: Vec<Cube>,
+    pub essential: Vec<usize>,
+    pub cover: Vec<usize>,
+    pub exact: bool,
+}
+
+impl Minimization {
+    pub fn new(expr: &LogExpr, dont_care: Option<&LogExpr>) -> Result<Minimization, MinimizeError> {
+        let CubeSets {
+            atoms,
+            on,
+            dont_care: dc,
+            ..
+        } = CubeSets::new(expr, dont_care, false)?;
+        let n = atoms.len();
+        if n > MINTERM_ATOM_LIMIT {
+            return Err(MinimizeError::TooManyAtoms(n, MINTERM_ATOM_LIMIT));
+        }
+        let expand = |cubes: &[Cube]| {
+            let mut minterms = cubes.iter().flat_map(|c| c.minterms(n)).collect::<Vec<_>>();
+            minterms.sort();
+            minterms
+        };
+        let (minterms, dont_cares) = (expand(&on), expand(&dc));
+        let all = minterms
+            .iter()
+            .chain(&dont_cares)
+            .cloned()
+            .collect::<Vec<_>>();
+        // Primes covering only don't-cares are never worth choosing
+        let primes = prime_implicants(&all, n)
+            .into_iter()
+            .filter(|p| minterms.iter().any(|m| p.covers(*m)))
+            .collect::<Vec<_>>();
+        let covering = |m: u64| {
+            (0..primes.len())
+                .filter(|i| primes[*i].covers(m))
+                .collect::<Vec<_>>()
+        };
+        let mut essential = Vec::new();
+        for m in &minterms {
+            if let [only] = covering(*m)[..] {
+                if !essential.contains(&only) {
+                    essential.push(only);
+                }
+            }
+        }
+        essential.sort();
+        let mut clauses = minterms
+            .iter()
+            .filter(|m| !essential.iter().any(|i| primes[*i].covers(**m)))
+            .map(|m| covering(*m))
+            .collect::<Vec<_>>();
+        clauses.sort_by_key(|c| (c.len(), c.clone()));
+        clauses.dedup();
+        let (rest, exact) = petrick(&clauses, &primes);
+        let mut cover = essential.iter().chain(&rest).cloned().collect::<Vec<_>>();
+        cover.sort();
+        Ok(Minimization {
+            expr: Box::new(expr.clone()),
+            dont_care: dont_care.map(|d| Box::new(d.clone())),
+            atoms,
+            primes,
+            essential,
+            cover,
+            exact,
+        })
+    }
+
+    pub fn cover_cubes(&self) -> Vec<Cube> {
+        self.cover.iter().map(|i| self.primes[*i]).collect()
+    }
+
+    pub fn result(&self) -> Box<LogExpr> {
+        sum_of_products(&self.cover_cubes(), &self.atoms)
+    }
+}
+
+impl Display for Minimization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\tPrime implicants of {}", self.expr)?;
+        if let Some(dont_care) = &self.dont_care {
+            write!(f, ", don't care where {}", dont_care)?;
+        }
+        writeln!(f, " (* essential):")?;
+        for (i, prime) in self.primes.iter().enumerate() {
+            let mark = if self.essential.contains(&i) {
+                " *"
+            } else {
+                ""
+            };
+            writeln!(f, "\t\t{}{}", prime.to_expr(&self.atoms), mark)?;
+        }
+        let literals = self.cover_cubes().iter().map(|c| c.literals()).sum::<u32>();
+        writeln!(
+            f,
+            "\tMinimal sum of products (terms: {}, literals: {}):",
+            self.cover.len(),
+            literals
+        )?;
+        write!(f, "\t\t{}", self.result())?;
+        if !self.exact {
+            write!(
+                f,
+                "\n\t\tPetrick's method was cut short, so the cover may not be minimum."
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn equivalent(lhs: &LogExpr, rhs: &LogExpr) -> bool {
+        BddEquivalence::new(lhs, rhs).unwrap().difference.is_none()
+    }
+
+    fn literals(minimization: &Minimization) -> u32 {
+        minimization
+            .cover_cubes()
+            .iter()
+            .map(|c| c.literals())
+            .sum()
+    }
+
+    #[test]
+    fn cyclic_covers_have_no_essential_primes() {
+        // Six primes, each minterm covered by exactly two of them
+        let formula = expr("(or (and (not a) (not b)) (and b (not c)) (and a c))");
+        let minimization = Minimization::new(&formula, None).unwrap();
+        assert_eq!(minimization.primes.len(), 6);
+        assert!(minimization.essential.is_empty());
+        assert_eq!(minimization.cover.len(), 3);
+        assert_eq!(literals(&minimization), 6);
+        assert!(minimization.exact);
+        assert!(equivalent(&minimization.result(), &formula));
+    }
+
+    #[test]
+    fn redundant_terms_are_dropped() {
+        let formula = expr("(or (and a b) (and (not a) c) (and b c))");
+        let minimization = Minimization::new(&formula, None).unwrap();
+        assert_eq!(minimization.essential.len(), 2);
+        assert_eq!(minimization.cover.len(), 2);
+        assert!(equivalent(&minimization.result(), &formula));
+        let minimization = Minimization::new(&expr("(or p (not p))"), None).unwrap();
+        assert!(equivalent(&minimization.result(), &expr("true")));
+        let minimization = Minimization::new(&expr("(and p (not p))"), None).unwrap();
+        assert!(minimization.cover.is_empty());
+    }
+
+    #[test]
+    fn dont_cares_widen_the_primes() {
+        let minimization =
+            Minimization::new(&expr("(and a b)"), Some(&expr("(and a (not b))"))).unwrap();
+        assert!(equivalent(&minimization.result(), &expr("a")));
+        // Primes inside the don't-care set alone are never offered
+        let minimization =
+            Minimization::new(&expr("(and a b c)"), Some(&expr("(and (not a) (not b))"))).unwrap();
+        assert!(minimization.primes.iter().all(|p| p.covers(0b111)));
+    }
+
+    #[test]
+    fn cubes_expand_to_their_minterms() {
+        let cube = Cube {
+            care: 0b101,
+            value: 0b001,
+        };
+        let mut minterms = cube.minterms(3);
+        minterms.sort();
+        assert_eq!(minterms, [0b001, 0b011]);
+        assert!(cube.covers(0b011));
+        assert!(!cube.covers(0b101));
+        assert_eq!(cube.literals(), 2);
+    }
+
+    #[test]
+    fn large_and_non_propositional_formulas_are_refused() {
+        let atoms = (0..13).map(|i| format!("p{}", i)).collect::<Vec<_>>();
+        let formula = expr(&format!("(or {})", atoms.join(" ")));
+        assert!(matches!(
+            Minimization::new(&formula, None),
+            Err(MinimizeError::TooManyAtoms(13, MINTERM_ATOM_LIMIT))
+        ));
+        assert!(matches!(
+            Minimization::new(&expr("(box p)"), None),
+            Err(MinimizeError::Bdd(_))
+        ));
+    }
+}
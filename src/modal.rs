@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::parser::*;
+
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection, clippy::vec_box)]
+pub enum KripkeError {
+    NoWorlds,
+    UnknownWorld(Box<String>),
+    NotAPair(Vec<Box<String>>),
+    NotModal(Box<LogExpr>),
+}
+
+impl Display for KripkeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KripkeError::NoWorlds => write!(f, "no worlds have been declared"),
+            KripkeError::UnknownWorld(world) => write!(f, "{} is not a world", world),
+            KripkeError::NotAPair(worlds) => {
+                let worlds = worlds.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+                write!(f, "({}) is not a pair of worlds", worlds.join(" "))
+            }
+            KripkeError::NotModal(expr) => {
+                write!(f, "{} is not a propositional modal formula", expr)
+            }
+        }
+    }
+}
+
+/// A Kripke model: worlds, an accessibility relation, and the worlds at which
+/// each atom is true. Atoms without a valuation are false everywhere.
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection, clippy::vec_box)]
+pub struct Kripke {
+    pub worlds: Vec<Box<String>>,
+    pub access: HashSet<(Box<String>, Box<String>)>,
+    pub valuation: HashMap<Box<String>, HashSet<Box<String>>>,
+}
+
+impl Display for Kripke {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let worlds = self
+            .worlds
+            .iter()
+            .map(|w| w.to_string())
+            .collect::<Vec<_>>();
+        writeln!(f, "\t\tworlds: {{{}}}", worlds.join(", "))?;
+        let mut access = self
+            .access
+            .iter()
+            .map(|(from, to)| format!("({} {})", from, to))
+            .collect::<Vec<_>>();
+        access.sort();
+        writeln!(f, "\t\tR: {{{}}}", access.join(", "))?;
+        let mut valuation = self.valuation.iter().collect::<Vec<_>>();
+        valuation.sort_by_key(|(atom, _)| atom.to_string());
+        for (atom, worlds) in valuation {
+            let mut worlds = worlds.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+            worlds.sort();
+            writeln!(f, "\t\t{}: {{{}}}", atom, worlds.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Kripke {
+    pub fn new() -> Kripke {
+        Kripke {
+            worlds: Vec::new(),
+            access: HashSet::new(),
+            valuation: HashMap::new(),
+        }
+    }
+
+    /// Replaces the worlds, dropping the relation and valuation
+    #[allow(clippy::vec_box)]
+    pub fn set_worlds(&mut self, worlds: Vec<Box<String>>) {
+        *self = Kripke::new();
+        self.worlds = worlds;
+    }
+
+    fn check_world(&self, world: &str) -> Result<(), KripkeError> {
+        if self.worlds.is_empty() {
+            Err(KripkeError::NoWorlds)
+        } else if !self.worlds.iter().any(|w| **w == world) {
+            Err(KripkeError::UnknownWorld(Box::new(world.to_string())))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Adds accessibility edges, each given as a pair of worlds
+    #[allow(clippy::vec_box)]
+    pub fn add_access(&mut self, pairs: Vec<Vec<Box<String>>>) -> Result<(), KripkeError> {
+        for pair in &pairs {
+            if pair.len() != 2 {
+                return Err(KripkeError::NotAPair(pair.clone()));
+            }
+            pair.iter().try_for_each(|w| self.check_world(w))?;
+        }
+        for pair in pairs {
+            if let [from, to] = pair.as_slice() {
+                self.access.insert((from.clone(), to.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes an atom true at exactly the given worlds
+    #[allow(clippy::box_collection, clippy::vec_box)]
+    pub fn set_valuation(
+        &mut self,
+        atom: Box<String>,
+        worlds: Vec<Box<String>>,
+    ) -> Result<(), KripkeError> {
+        worlds.iter().try_for_each(|w| self.check_world(w))?;
+        self.valuation.insert(atom, worlds.into_iter().collect());
+        Ok(())
+    }
+
+    pub fn successors<'a>(&'a self, world: &'a str) -> impl Iterator<Item = &'a Box<String>> {
+        self.worlds.iter().filter(move |to| {
+            self.access
+                .contains(&(Box::new(world.to_string()), (*to).clone()))
+        })
+    }
+
+    pub fn eval(&self, expr: &LogExpr, world: &str) -> Result<bool, KripkeError> {
+        self.check_world(world)?;
+        match expr {
+            LogExpr::True => Ok(true),
+            LogExpr::False => Ok(false),
+            LogExpr::Atom(token) => Ok(self
+                .valuation
+                .get(&token.text)
+                .is_some_and(|worlds| worlds.iter().any(|w| **w == world))),
+            LogExpr::UnaryOp(op, inner) => match **op {
+                Operator::Box => {
+                    for to in self.successors(world) {
+                        if !self.eval(inner, to)? {
+                            return Ok(false);
+                        }
+                    }
+                    Ok(true)
+                }
+                Operator::Diamond => {
+                    for to in self.successors(world) {
+                        if self.eval(inner, to)? {
+                            return Ok(true);
+                        }
+                    }
+                    Ok(false)
+                }
+                Operator::Not => Ok(!self.eval(inner, world)?),
+                _ => Err(KripkeError::NotModal(Box::new(expr.clone()))),
+            },
+            LogExpr::BinaryOp(op, exprs) => match **op {
+                Operator::And => {
+                    for e in exprs {
+                        if !self.eval(e, world)? {
+                            return Ok(false);
+                        }
+                    }
+                    Ok(true)
+                }
+                Operator::Or => {
+                    for e in exprs {
+                        if self.eval(e, world)? {
+                            return Ok(true);
+                        }
+                    }
+                    Ok(false)
+                }
+                Operator::Imp => match LogExpr::imp_parts(exprs) {
+                    Some((lhs, rhs)) => Ok(!self.eval(&lhs, world)? || self.eval(&rhs, world)?),
+                    None => match exprs.first() {
+                        Some(e) => self.eval(e, world),
+                        None => Ok(true),
+                    },
+                },
+                _ => Err(KripkeError::NotModal(Box::new(expr.clone()))),
+            },
+            _ => Err(KripkeError::NotModal(Box::new(expr.clone()))),
+        }
+    }
+
+    /// The worlds at which the formula fails; it holds globally when none do
+    #[allow(clippy::vec_box)]
+    pub fn failures(&self, expr: &LogExpr) -> Result<Vec<Box<String>>, KripkeError> {
+        if self.worlds.is_empty() {
+            return Err(KripkeError::NoWorlds);
+        }
+        let mut failures = Vec::new();
+        for world in &self.worlds {
+            if !self.eval(expr, world)? {
+                failures.push(world.clone());
+            }
+        }
+        Ok(failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    #[allow(clippy::vec_box)]
+    fn worlds(names: &[&str]) -> Vec<Box<String>> {
+        names.iter().map(|n| Box::new(n.to_string())).collect()
+    }
+
+    // w1 sees w2 and w3; p holds at w2 only, q at w2 and w3
+    fn model() -> Kripke {
+        let mut kripke = Kripke::new();
+        kripke.set_worlds(worlds(&["w1", "w2", "w3"]));
+        kripke
+            .add_access(vec![worlds(&["w1", "w2"]), worlds(&["w1", "w3"])])
+            .unwrap();
+        let p = Box::new("p".to_string());
+        let q = Box::new("q".to_string());
+        kripke.set_valuation(p, worlds(&["w2"])).unwrap();
+        kripke.set_valuation(q, worlds(&["w2", "w3"])).unwrap();
+        kripke
+    }
+
+    #[test]
+    fn modalities_range_over_successors() {
+        let kripke = model();
+        assert!(kripke.eval(&expr("(box q)"), "w1").unwrap());
+        assert!(!kripke.eval(&expr("(box p)"), "w1").unwrap());
+        assert!(kripke.eval(&expr("(diamond p)"), "w1").unwrap());
+        assert!(kripke.eval(&expr("(L q)"), "w1").unwrap());
+        assert!(!kripke.eval(&expr("(<> (not q))"), "w1").unwrap());
+        // Dead ends make every box true and every diamond false
+        assert!(kripke.eval(&expr("(box false)"), "w2").unwrap());
+        assert!(!kripke.eval(&expr("(M true)"), "w2").unwrap());
+    }
+
+    #[test]
+    fn global_truth_lists_the_failing_worlds() {
+        let kripke = model();
+        assert!(kripke
+            .failures(&expr("(imp (box (imp p q)) (imp (box p) (box q)))"))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            kripke.failures(&expr("(imp (box p) p)")).unwrap(),
+            worlds(&["w3"])
+        );
+        assert_eq!(kripke.failures(&expr("q")).unwrap(), worlds(&["w1"]));
+    }
+
+    #[test]
+    fn bad_declarations_and_formulas_are_refused() {
+        let mut kripke = Kripke::new();
+        assert!(matches!(
+            kripke.failures(&expr("p")),
+            Err(KripkeError::NoWorlds)
+        ));
+        kripke.set_worlds(worlds(&["w1"]));
+        assert!(matches!(
+            kripke.add_access(vec![worlds(&["w1", "w9"])]),
+            Err(KripkeError::UnknownWorld(_))
+        ));
+        assert!(matches!(
+            kripke.add_access(vec![worlds(&["w1"])]),
+            Err(KripkeError::NotAPair(_))
+        ));
+        assert!(matches!(
+            kripke.eval(&expr("(forall x (P x))"), "w1"),
+            Err(KripkeError::NotModal(_))
+        ));
+        assert!(matches!(
+            kripke.eval(&expr("p"), "w2"),
+            Err(KripkeError::UnknownWorld(_))
+        ));
+    }
+
+    #[test]
+    fn new_worlds_drop_the_relation_and_valuation() {
+        let mut kripke = model();
+        kripke.set_worlds(worlds(&["w1"]));
+        assert!(kripke.access.is_empty());
+        assert!(!kripke.eval(&expr("q"), "w1").unwrap());
+    }
+}
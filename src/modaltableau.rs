@@ -0,0 +1,592 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::modal::*;
+use crate::parser::*;
+use crate::resolution::*;
+
+/// Conditions on the accessibility relation that pick out a normal modal logic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub reflexive: bool,
+    pub transitive: bool,
+    pub symmetric: bool,
+    pub euclidean: bool,
+}
+
+impl Frame {
+    pub fn system(name: &str) -> Option<Frame> {
+        let frame = |reflexive, transitive, symmetric, euclidean| Frame {
+            reflexive,
+            transitive,
+            symmetric,
+            euclidean,
+        };
+        match name {
+            "K" => Some(frame(false, false, false, false)),
+            "T" => Some(frame(true, false, false, false)),
+            "B" => Some(frame(true, false, true, false)),
+            "K4" => Some(frame(false, true, false, false)),
+            "S4" => Some(frame(true, true, false, false)),
+            "S5" => Some(frame(true, true, true, true)),
+            _ => None,
+        }
+    }
+
+    // Closes a relation over `worlds` worlds under the frame conditions
+    fn close(&self, edges: &mut HashSet<(usize, usize)>, worlds: usize) {
+        loop {
+            let mut added = Vec::new();
+            for &(a, b) in edges.iter() {
+                if self.reflexive {
+                    added.push((a, a));
+                    added.push((b, b));
+                }
+                if self.symmetric {
+                    added.push((b, a));
+                }
+                for &(c, d) in edges.iter() {
+                    if self.transitive && b == c {
+                        added.push((a, d));
+                    }
+                    if self.euclidean && a == c {
+                        added.push((b, d));
+                    }
+                }
+            }
+            if self.reflexive {
+                added.extend((0..worlds).map(|w| (w, w)));
+            }
+            let before = edges.len();
+            edges.extend(added);
+            if edges.len() == before {
+                return;
+            }
+        }
+    }
+
+    // Successor worlds may repeat their ancestors only on transitive frames;
+    // elsewhere the relation itself keeps the search finite
+    fn needs_blocking(&self) -> bool {
+        self.transitive && !self.symmetric && !self.euclidean
+    }
+}
+
+/// How an entry on a branch came to be there
+#[derive(Debug, Clone)]
+pub enum ModalOrigin {
+    NegatedGoal,
+    Alpha(usize),
+    Beta(usize),
+    Necessity(usize),
+    Possibility(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct ModalEntry {
+    pub number: usize,
+    pub world: usize,
+    pub formula: Box<LogExpr>,
+    pub origin: ModalOrigin,
+}
+
+#[derive(Debug, Clone)]
+pub enum ModalStatus {
+    Closed(usize, usize),
+    Open,
+    Branched,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModalNode {
+    pub entries: Vec<ModalEntry>,
+    pub status: ModalStatus,
+    pub children: Vec<ModalNode>,
+}
+
+// The worlds of a branch, their labels, and the accessibility relation so far
+#[derive(Debug, Clone)]
+struct Branch {
+    labels: Vec<Vec<ModalEntry>>,
+    parents: Vec<Option<usize>>,
+    edges: HashSet<(usize, usize)>,
+}
+
+impl Branch {
+    fn has(&self, world: usize, formula: &LogExpr) -> bool {
+        self.labels[world].iter().any(|e| *e.formula == *formula)
+    }
+
+    fn successors(&self, world: usize) -> Vec<usize> {
+        (0..self.labels.len())
+            .filter(|v| self.edges.contains(&(world, *v)))
+            .collect()
+    }
+
+    fn clash(&self, entry: &ModalEntry) -> Option<ModalStatus> {
+        if *entry.formula == LogExpr::False {
+            return Some(ModalStatus::Closed(entry.number, entry.number));
+        }
+        let complement = match &*entry.formula {
+            LogExpr::UnaryOp(op, inner) if **op == Operator::Not => inner.clone(),
+            _ => entry.formula.negate(),
+        };
+        self.labels[entry.world]
+            .iter()
+            .find(|other| other.formula == complement)
+            .map(|other| ModalStatus::Closed(other.number, entry.number))
+    }
+
+    // A world whose label is contained in an ancestor's needs no successors
+    // of its own; the countermodel lends it the ancestor's
+    fn blocker(&self, world: usize) -> Option<usize> {
+        let mut ancestor = self.parents[world];
+        while let Some(u) = ancestor {
+            if self.labels[world].iter().all(|e| self.has(u, &e.formula)) {
+                return Some(u);
+            }
+            ancestor = self.parents[u];
+        }
+        None
+    }
+
+    fn kripke(&self, frame: &Frame) -> Kripke {
+        let mut edges = self.edges.clone();
+        if frame.needs_blocking() {
+            for world in 0..self.labels.len() {
+                if let Some(u) = self.blocker(world) {
+                    edges.extend(self.successors(u).into_iter().map(|x| (world, x)));
+                }
+            }
+            frame.close(&mut edges, self.labels.len());
+        }
+        let name = |w: usize| Box::new(format!("w{}", w));
+        let mut kripke = Kripke::new();
+        kripke.set_worlds((0..self.labels.len()).map(name).collect());
+        kripke.access = edges.iter().map(|(a, b)| (name(*a), name(*b))).collect();
+        for (world, label) in self.labels.iter().enumerate() {
+            for entry in label {
+                // A predicate literal is valued under its printed form
+                let atom = match &*entry.formula {
+                    LogExpr::Atom(token) | LogExpr::Var(token) => token.text.clone(),
+                    LogExpr::Pred(_, _) => Box::new(entry.formula.to_string()),
+                    _ => continue,
+                };
+                kripke
+                    .valuation
+                    .entry(atom)
+                    .or_default()
+                    .insert(name(world));
+            }
+        }
+        kripke
+    }
+}
+
+#[allow(clippy::vec_box)]
+enum Step {
+    Add(Vec<(usize, Box<LogExpr>, ModalOrigin)>),
+    Branch(usize, Vec<Box<LogExpr>>, usize),
+    NewWorld(usize, Box<LogExpr>, usize),
+}
+
+struct Builder {
+    frame: Frame,
+    counter: usize,
+    model: Option<Kripke>,
+}
+
+impl Builder {
+    // The next rule to apply: non-branching rules first, then disjunctions,
+    // then a fresh world for an unsatisfied possibility
+    fn next_step(&self, branch: &Branch) -> Option<Step> {
+        let mut disjunction = None;
+        let mut possibility = None;
+        for (world, label) in branch.labels.iter().enumerate() {
+            for entry in label {
+                match &*entry.formula {
+                    LogExpr::BinaryOp(op, exprs) if **op == Operator::And => {
+                        let missing = exprs
+                            .iter()
+                            .filter(|e| !branch.has(world, e))
+                            .map(|e| (world, e.clone(), ModalOrigin::Alpha(entry.number)))
+                            .collect::<Vec<_>>();
+                        if !missing.is_empty() {
+                            return Some(Step::Add(missing));
+                        }
+                    }
+                    LogExpr::BinaryOp(op, exprs)
+                        if **op == Operator::Or
+                            && disjunction.is_none()
+                            && !exprs.iter().any(|e| branch.has(world, e)) =>
+                    {
+                        disjunction = Some(Step::Branch(world, exprs.clone(), entry.number));
+                    }
+                    LogExpr::UnaryOp(op, inner) if **op == Operator::Box => {
+                        let mut missing = Vec::new();
+                        for v in branch.successors(world) {
+                            let origin = || ModalOrigin::Necessity(entry.number);
+                            if !branch.has(v, inner) {
+                                missing.push((v, inner.clone(), origin()));
+                            }
+                            // On transitive frames necessity passes itself on
+                            if self.frame.transitive && !branch.has(v, &entry.formula) {
+                                missing.push((v, entry.formula.clone(), origin()));
+                            }
+                        }
+                        if !missing.is_empty() {
+                            return Some(Step::Add(missing));
+                        }
+                    }
+                    LogExpr::UnaryOp(op, inner) if **op == Operator::Diamond => {
+                        let satisfied = branch
+                            .successors(world)
+                            .iter()
+                            .any(|v| branch.has(*v, inner));
+                        let blocked =
+                            self.frame.needs_blocking() && branch.blocker(world).is_some();
+                        if possibility.is_none() && !satisfied && !blocked {
+                            possibility = Some(Step::NewWorld(world, inner.clone(), entry.number));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        disjunction.or(possibility)
+    }
+
+    fn push(
+        &mut self,
+        branch: &mut Branch,
+        entries: &mut Vec<ModalEntry>,
+        (world, formula, origin): (usize, Box<LogExpr>, ModalOrigin),
+    ) -> Option<ModalStatus> {
+        self.counter += 1;
+        let entry = ModalEntry {
+            number: self.counter,
+            world,
+            formula,
+            origin,
+        };
+        let clash = branch.clash(&entry);
+        branch.labels[world].push(entry.clone());
+        entries.push(entry);
+        clash
+    }
+
+    fn build(
+        &mut self,
+        mut branch: Branch,
+        start: Vec<(usize, Box<LogExpr>, ModalOrigin)>,
+    ) -> ModalNode {
+        let mut entries = Vec::new();
+        let node = |entries, status| ModalNode {
+            entries,
+            status,
+            children: Vec::new(),
+        };
+        for item in start {
+            if let Some(status) = self.push(&mut branch, &mut entries, item) {
+                return node(entries, status);
+            }
+        }
+        loop {
+            match self.next_step(&branch) {
+                Some(Step::Add(items)) => {
+                    for item in items {
+                        if let Some(status) = self.push(&mut branch, &mut entries, item) {
+                            return node(entries, status);
+                        }
+                    }
+                }
+                Some(Step::Branch(world, parts, source)) => {
+                    let children = parts
+                        .into_iter()
+                        .map(|part| {
+                            let start = vec![(world, part, ModalOrigin::Beta(source))];
+                            self.build(branch.clone(), start)
+                        })
+                        .collect();
+                    return ModalNode {
+                        entries,
+                        status: ModalStatus::Branched,
+                        children,
+                    };
+                }
+                Some(Step::NewWorld(world, formula, source)) => {
+                    let new = branch.labels.len();
+                    branch.labels.push(Vec::new());
+                    branch.parents.push(Some(world));
+                    branch.edges.insert((world, new));
+                    self.frame.close(&mut branch.edges, branch.labels.len());
+                    let item = (new, formula, ModalOrigin::Possibility(source));
+                    if let Some(status) = self.push(&mut branch, &mut entries, item) {
+                        return node(entries, status);
+                    }
+                }
+                None => {
+                    if self.model.is_none() {
+                        self.model = Some(branch.kripke(&self.frame));
+                    }
+                    return node(entries, ModalStatus::Open);
+                }
+            }
+        }
+    }
+}
+
+// Atomic formulas, the classical connectives and the two modalities are all
+// the tableau has rules for; anything else is refused rather than read as an
+// atom
+fn check_modal(expr: &LogExpr) -> Result<(), KripkeError> {
+    match expr {
+        LogExpr::Atom(_) | LogExpr::Var(_) | LogExpr::Pred(_, _) => Ok(()),
+        LogExpr::True | LogExpr::False => Ok(()),
+        LogExpr::UnaryOp(op, inner)
+            if matches!(**op, Operator::Not | Operator::Box | Operator::Diamond) =>
+        {
+            check_modal(inner)
+        }
+        LogExpr::BinaryOp(op, exprs)
+            if matches!(**op, Operator::And | Operator::Or | Operator::Imp) =>
+        {
+            exprs.iter().try_for_each(|e| check_modal(e))
+        }
+        _ => Err(KripkeError::NotModal(Box::new(expr.clone()))),
+    }
+}
+
+/// A labelled tableau refuting the negation of a formula at a root world `w0`.
+/// It closes when the formula is valid on the frames; otherwise the first open
+/// branch gives a finite countermodel.
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
+pub struct ModalTableau {
+    pub system: Box<String>,
+    pub goal: Box<LogExpr>,
+    pub root: ModalNode,
+    pub countermodel: Option<Kripke>,
+}
+
+impl ModalTableau {
+    pub fn new(system: &str, frame: Frame, goal: &LogExpr) -> Result<ModalTableau, KripkeError> {
+        check_modal(goal)?;
+        let mut builder = Builder {
+            frame: frame.clone(),
+            counter: 0,
+            model: None,
+        };
+        let mut edges = HashSet::new();
+        frame.close(&mut edges, 1);
+        let branch = Branch {
+            labels: vec![Vec::new()],
+            parents: vec![None],
+            edges,
+        };
+        let start = vec![(0, nnf(goal, false), ModalOrigin::NegatedGoal)];
+        let root = builder.build(branch, start);
+        Ok(ModalTableau {
+            system: Box::new(system.to_string()),
+            goal: Box::new(goal.clone()),
+            root,
+            countermodel: builder.model,
+        })
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.countermodel.is_none()
+    }
+
+    fn fmt_node(
+        node: &ModalNode,
+        f: &mut std::fmt::Formatter<'_>,
+        head: &str,
+        prefix: &str,
+    ) -> std::fmt::Result {
+        let mut lead = head;
+        for entry in &node.entries {
+            let origin = match entry.origin {
+                ModalOrigin::NegatedGoal => "negated goal".to_string(),
+                ModalOrigin::Alpha(n) => format!("α {}", n),
+                ModalOrigin::Beta(n) => format!("β {}", n),
+                ModalOrigin::Necessity(n) => format!("□ {}", n),
+                ModalOrigin::Possibility(n) => format!("◇ {}", n),
+            };
+            writeln!(
+                f,
+                "{}{}. w{}: {}  [{}]",
+                lead, entry.number, entry.world, entry.formula, origin
+            )?;
+            lead = prefix;
+        }
+        match node.status {
+            ModalStatus::Closed(i, j) if i == j => writeln!(f, "{}✗ closed by {}", lead, i),
+            ModalStatus::Closed(i, j) => writeln!(f, "{}✗ closed by {} and {}", lead, i, j),
+            ModalStatus::Open => writeln!(f, "{}○ open", lead),
+            ModalStatus::Branched => {
+                let last = node.children.len().saturating_sub(1);
+                for (i, child) in node.children.iter().enumerate() {
+                    let (branch, rest) = if i == last {
+                        ("└── ", "    ")
+                    } else {
+                        ("├── ", "│   ")
+                    };
+                    ModalTableau::fmt_node(
+                        child,
+                        f,
+                        &format!("{}{}", prefix, branch),
+                        &format!("{}{}", prefix, rest),
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for ModalTableau {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.countermodel {
+            None => {
+                ModalTableau::fmt_node(&self.root, f, "\t", "\t")?;
+                write!(f, "\t{} is valid in {}.", self.goal, self.system)
+            }
+            Some(kripke) => {
+                writeln!(
+                    f,
+                    "\t{} is not valid in {}. Countermodel:",
+                    self.goal, self.system
+                )?;
+                write!(f, "{}\t\tfails at w0", kripke)
+            }
+        }
+    }
+}
+
+// Reads the metavariables of a rule as atoms, so validity of the instance
+// means validity of the schema
+pub fn atomize(expr: &LogExpr) -> Box<LogExpr> {
+    match expr {
+        LogExpr::Var(token) => Box::new(LogExpr::Atom(token.clone())),
+        LogExpr::UnaryOp(op, inner) => Box::new(LogExpr::UnaryOp(op.clone(), atomize(inner))),
+        LogExpr::BinaryOp(op, exprs) => Box::new(LogExpr::BinaryOp(
+            op.clone(),
+            exprs.iter().map(|e| atomize(e)).collect(),
+        )),
+        _ => Box::new(expr.clone()),
+    }
+}
+
+/// A rewrite rule is sound when its two sides are equivalent, an axiom when
+/// its schema is valid
+pub fn rule_soundness(rule_set: &RuleSet, name: &str) -> Option<Box<LogExpr>> {
+    let name = Box::new(name.to_string());
+    if let Some(rule) = rule_set.rules.get(&name) {
+        let (lhs, rhs) = (atomize(&rule.lhs), atomize(&rule.rhs));
+        let imp = |a: &LogExpr, b: &LogExpr| {
+            Box::new(LogExpr::BinaryOp(
+                Box::new(Operator::Imp),
+                vec![Box::new(a.clone()), Box::new(b.clone())],
+            ))
+        };
+        Some(Box::new(LogExpr::BinaryOp(
+            Box::new(Operator::And),
+            vec![imp(&lhs, &rhs), imp(&rhs, &lhs)],
+        )))
+    } else {
+        rule_set.axioms.get(&name).map(|schema| atomize(schema))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn valid(system: &str, goal: &str) -> bool {
+        let frame = Frame::system(system).unwrap();
+        let tableau = ModalTableau::new(system, frame, &expr(goal)).unwrap();
+        if let Some(model) = &tableau.countermodel {
+            assert!(!model.failures(&expr(goal)).unwrap().is_empty());
+        }
+        tableau.is_valid()
+    }
+
+    const SYSTEMS: [&str; 6] = ["K", "T", "B", "K4", "S4", "S5"];
+
+    // Each axiom with the systems whose frames validate it
+    fn check(axiom: &str, systems: &[&str]) {
+        for system in SYSTEMS {
+            assert_eq!(
+                valid(system, axiom),
+                systems.contains(&system),
+                "{} in {}",
+                axiom,
+                system
+            );
+        }
+    }
+
+    #[test]
+    fn k_holds_on_every_frame() {
+        check("(imp (box (imp p q)) (imp (box p) (box q)))", &SYSTEMS);
+        check("(imp (diamond p) (not (box (not p))))", &SYSTEMS);
+        check("(imp (not (box (not p))) (diamond p))", &SYSTEMS);
+    }
+
+    #[test]
+    fn t_needs_reflexive_frames() {
+        check("(imp (box p) p)", &["T", "B", "S4", "S5"]);
+    }
+
+    #[test]
+    fn four_needs_transitive_frames() {
+        check("(imp (box p) (box (box p)))", &["K4", "S4", "S5"]);
+    }
+
+    #[test]
+    fn b_needs_symmetric_frames() {
+        check("(imp p (box (diamond p)))", &["B", "S5"]);
+    }
+
+    #[test]
+    fn five_needs_euclidean_frames() {
+        check("(imp (diamond p) (box (diamond p)))", &["S5"]);
+    }
+
+    #[test]
+    fn predicate_literals_and_their_negations_clash() {
+        assert!(valid("K", "(or (P a) (not (P a)))"));
+        assert!(valid("T", "(imp (box (Q a b)) (Q a b))"));
+    }
+
+    #[test]
+    fn goals_outside_modal_logic_are_refused() {
+        let frame = Frame::system("K").unwrap();
+        let goal = expr("(imp (forall x (P x)) (P a))");
+        assert!(matches!(
+            ModalTableau::new("K", frame.clone(), &goal),
+            Err(KripkeError::NotModal(_))
+        ));
+        let goal = expr("(imp (box p) (next p))");
+        assert!(matches!(
+            ModalTableau::new("K", frame.clone(), &goal),
+            Err(KripkeError::NotModal(_))
+        ));
+        let goal = expr("(or unknown (not unknown))");
+        assert!(matches!(
+            ModalTableau::new("K", frame, &goal),
+            Err(KripkeError::NotModal(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_systems_are_not_frames() {
+        assert!(Frame::system("S3").is_none());
+    }
+}
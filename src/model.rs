@@ -0,0 +1,468 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::parser::*;
+use crate::signature::*;
+
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection, clippy::vec_box)]
+pub enum ModelError {
+    EmptyDomain,
+    NotInDomain(Box<String>),
+    Uninterpreted(Box<String>),
+    MissingValue(Box<String>, Vec<Box<String>>),
+    WrongArity(Box<String>),
+    NotEvaluable(Box<LogExpr>),
+}
+
+impl Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::EmptyDomain => write!(f, "no domain has been declared"),
+            ModelError::NotInDomain(elem) => write!(f, "{} is not in the domain", elem),
+            ModelError::Uninterpreted(name) => write!(f, "{} has no interpretation", name),
+            ModelError::MissingValue(name, args) => {
+                let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+                write!(f, "{} has no value at ({})", name, args.join(" "))
+            }
+            ModelError::WrongArity(name) => {
+                write!(f, "{} is interpreted with a different arity", name)
+            }
+            ModelError::NotEvaluable(expr) => write!(f, "cannot evaluate {}", expr),
+        }
+    }
+}
+
+/// Object variables assigned to domain elements
+pub type Env = HashMap<String, Box<String>>;
+
+/// A function table from argument tuples to values
+pub type FunctionTable = HashMap<Vec<Box<String>>, Box<String>>;
+
+/// A finite first-order structure: a domain and interpretations given as tables
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection, clippy::vec_box)]
+pub struct Structure {
+    pub domain: Vec<Box<String>>,
+    pub constants: HashMap<Box<String>, Box<String>>,
+    pub functions: HashMap<Box<String>, FunctionTable>,
+    pub relations: HashMap<Box<String>, HashSet<Vec<Box<String>>>>,
+}
+
+impl Display for Structure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let domain = self
+            .domain
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        writeln!(f, "\t\tdomain: {{{}}}", domain.join(", "))?;
+        let mut constants = self.constants.iter().collect::<Vec<_>>();
+        constants.sort();
+        for (name, elem) in constants {
+            writeln!(f, "\t\t{} = {}", name, elem)?;
+        }
+        let mut functions = self.functions.iter().collect::<Vec<_>>();
+        functions.sort_by_key(|(name, _)| name.to_string());
+        for (name, table) in functions {
+            let mut entries = table
+                .iter()
+                .map(|(args, value)| format!("{} -> {}", Structure::tuple(args), value))
+                .collect::<Vec<_>>();
+            entries.sort();
+            writeln!(f, "\t\t{}: {}", name, entries.join(", "))?;
+        }
+        let mut relations = self.relations.iter().collect::<Vec<_>>();
+        relations.sort_by_key(|(name, _)| name.to_string());
+        for (name, tuples) in relations {
+            let mut entries = tuples
+                .iter()
+                .map(|t| Structure::tuple(t))
+                .collect::<Vec<_>>();
+            entries.sort();
+            writeln!(f, "\t\t{}: {{{}}}", name, entries.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Structure {
+    pub fn new() -> Structure {
+        Structure {
+            domain: Vec::new(),
+            constants: HashMap::new(),
+            functions: HashMap::new(),
+            relations: HashMap::new(),
+        }
+    }
+
+    #[allow(clippy::box_collection)]
+    fn tuple(elems: &[Box<String>]) -> String {
+        let elems = elems.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        format!("({})", elems.join(" "))
+    }
+
+    /// Replaces the domain and clears every interpretation, since tables
+    /// built over the old domain need not fit the new one
+    #[allow(clippy::vec_box)]
+    pub fn set_domain(&mut self, domain: Vec<Box<String>>) {
+        *self = Structure::new();
+        self.domain = domain;
+    }
+
+    #[allow(clippy::box_collection)]
+    fn check_elements(&self, elems: &[Box<String>]) -> Result<(), ModelError> {
+        match elems.iter().find(|e| !self.domain.contains(e)) {
+            Some(elem) => Err(ModelError::NotInDomain(elem.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Interprets a symbol from its table: a single element for a constant,
+    /// argument tuples followed by the value for a function, and the tuples
+    /// that hold for a relation
+    #[allow(clippy::box_collection, clippy::vec_box)]
+    pub fn interpret(
+        &mut self,
+        kind: SymbolKind,
+        name: Box<String>,
+        entries: Vec<Vec<Box<String>>>,
+    ) -> Result<(), ModelError> {
+        if self.domain.is_empty() {
+            return Err(ModelError::EmptyDomain);
+        }
+        for entry in &entries {
+            self.check_elements(entry)?;
+        }
+        if entries.iter().any(|e| e.len() != entries[0].len()) {
+            return Err(ModelError::WrongArity(name));
+        }
+        match kind {
+            SymbolKind::Constant => match entries.as_slice() {
+                [entry] if entry.len() == 1 => {
+                    self.constants.insert(name, entry[0].clone());
+                }
+                _ => return Err(ModelError::WrongArity(name)),
+            },
+            SymbolKind::Function => {
+                let mut table = FunctionTable::new();
+                for mut entry in entries {
+                    let value = entry.pop().ok_or(ModelError::WrongArity(name.clone()))?;
+                    table.insert(entry, value);
+                }
+                self.functions.insert(name, table);
+            }
+            SymbolKind::Predicate => {
+                self.relations.insert(name, entries.into_iter().collect());
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::box_collection)]
+    pub fn eval_term(&self, term: &LogExpr, env: &Env) -> Result<Box<String>, ModelError> {
+        match term {
+            LogExpr::Atom(token) => match env.get(token.text.as_str()) {
+                Some(elem) => Ok(elem.clone()),
+                None => self
+                    .constants
+                    .get(&token.text)
+                    .cloned()
+                    .ok_or(ModelError::Uninterpreted(token.text.clone())),
+            },
+            LogExpr::Func(name, args) => {
+                let table = self
+                    .functions
+                    .get(&name.text)
+                    .ok_or(ModelError::Uninterpreted(name.text.clone()))?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval_term(arg, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                table
+                    .get(&args)
+                    .cloned()
+                    .ok_or(ModelError::MissingValue(name.text.clone(), args))
+            }
+            _ => Err(ModelError::NotEvaluable(Box::new(term.clone()))),
+        }
+    }
+
+    #[allow(clippy::vec_box)]
+    fn eval_relation(&self, name: &str, args: Vec<Box<String>>) -> Result<bool, ModelError> {
+        let name = Box::new(name.to_string());
+        let tuples = self
+            .relations
+            .get(&name)
+            .ok_or(ModelError::Uninterpreted(name.clone()))?;
+        match tuples.iter().next() {
+            Some(tuple) if tuple.len() != args.len() => Err(ModelError::WrongArity(name)),
+            _ => Ok(tuples.contains(&args)),
+        }
+    }
+
+    pub fn eval(&self, expr: &LogExpr, env: &Env) -> Result<bool, ModelError> {
+        if self.domain.is_empty() {
+            return Err(ModelError::EmptyDomain);
+        }
+        match expr {
+            LogExpr::True => Ok(true),
+            LogExpr::False => Ok(false),
+            LogExpr::Atom(token) => self.eval_relation(&token.text, Vec::new()),
+            LogExpr::Pred(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval_term(arg, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.eval_relation(&name.text, args)
+            }
+            LogExpr::UnaryOp(op, inner) if **op == Operator::Not => Ok(!self.eval(inner, env)?),
+            LogExpr::BinaryOp(op, exprs) => match **op {
+                Operator::And => {
+                    for e in exprs {
+                        if !self.eval(e, env)? {
+                            return Ok(false);
+                        }
+                    }
+                    Ok(true)
+                }
+                Operator::Or => {
+                    for e in exprs {
+                        if self.eval(e, env)? {
+                            return Ok(true);
+                        }
+                    }
+                    Ok(false)
+                }
+                Operator::Imp => match LogExpr::imp_parts(exprs) {
+                    Some((lhs, rhs)) => Ok(!self.eval(&lhs, env)? || self.eval(&rhs, env)?),
+                    None => match exprs.first() {
+                        Some(e) => self.eval(e, env),
+                        None => Ok(true),
+                    },
+                },
+                _ => Err(ModelError::NotEvaluable(Box::new(expr.clone()))),
+            },
+            LogExpr::Quant(q, var, body) => {
+                let (_, value) = self.decide(q, var.text.as_str(), body, env)?;
+                Ok(value)
+            }
+            _ => Err(ModelError::NotEvaluable(Box::new(expr.clone()))),
+        }
+    }
+
+    // Evaluates a quantifier, also returning the element that decided it: a
+    // witness for a true `exists`, a counterexample for a false `forall`
+    #[allow(clippy::box_collection)]
+    fn decide(
+        &self,
+        q: &Quantifier,
+        var: &str,
+        body: &LogExpr,
+        env: &Env,
+    ) -> Result<(Option<Box<String>>, bool), ModelError> {
+        let mut env = env.clone();
+        for elem in &self.domain {
+            env.insert(var.to_string(), elem.clone());
+            let value = self.eval(body, &env)?;
+            match (q, value) {
+                (Quantifier::Exists, true) => return Ok((Some(elem.clone()), true)),
+                (Quantifier::Forall, false) => return Ok((Some(elem.clone()), false)),
+                _ => {}
+            }
+        }
+        Ok((None, *q == Quantifier::Forall))
+    }
+
+    fn has_quantifier(expr: &LogExpr) -> bool {
+        match expr {
+            LogExpr::Quant(..) => true,
+            LogExpr::UnaryOp(_, inner) => Structure::has_quantifier(inner),
+            LogExpr::BinaryOp(_, exprs) => exprs.iter().any(|e| Structure::has_quantifier(e)),
+            _ => false,
+        }
+    }
+
+    /// Walks the formula recording, for each quantifier reached, its witness
+    /// or counterexample and then the reasons inside its body
+    pub fn explain(
+        &self,
+        expr: &LogExpr,
+        env: &Env,
+        depth: usize,
+        out: &mut Vec<String>,
+    ) -> Result<(), ModelError> {
+        let indent = "\t".repeat(depth + 2);
+        match expr {
+            LogExpr::Quant(q, var, body) => {
+                let (elem, value) = self.decide(q, var.text.as_str(), body, env)?;
+                match elem {
+                    Some(elem) => {
+                        let role = match **q {
+                            Quantifier::Exists => "witness",
+                            Quantifier::Forall => "counterexample",
+                        };
+                        out.push(format!(
+                            "{}{} {}: {} {} = {}",
+                            indent, q, var.text, role, var.text, elem
+                        ));
+                        let mut env = env.clone();
+                        env.insert(var.text.to_string(), elem);
+                        self.explain(body, &env, depth + 1, out)?;
+                    }
+                    None => {
+                        let reason = match value {
+                            true => "holds for every element",
+                            false => "no witness in the domain",
+                        };
+                        out.push(format!("{}{} {}: {}", indent, q, var.text, reason));
+                        // Every element was needed, so explain the body under each
+                        if Structure::has_quantifier(body) {
+                            for elem in &self.domain {
+                                out.push(format!("{}\t{} = {}", indent, var.text, elem));
+                                let mut env = env.clone();
+                                env.insert(var.text.to_string(), elem.clone());
+                                self.explain(body, &env, depth + 2, out)?;
+                            }
+                        }
+                    }
+                }
+            }
+            LogExpr::UnaryOp(_, inner) => self.explain(inner, env, depth, out)?,
+            LogExpr::BinaryOp(_, exprs) => {
+                for e in exprs {
+                    self.explain(e, env, depth, out)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    #[allow(clippy::vec_box)]
+    fn elems(names: &[&str]) -> Vec<Box<String>> {
+        names.iter().map(|n| Box::new(n.to_string())).collect()
+    }
+
+    #[allow(clippy::box_collection)]
+    fn name(s: &str) -> Box<String> {
+        Box::new(s.to_string())
+    }
+
+    // Successor modulo three, with Lt the strict order
+    fn structure() -> Structure {
+        let mut structure = Structure::new();
+        structure.set_domain(elems(&["0", "1", "2"]));
+        let zero = vec![elems(&["0"])];
+        let s = vec![elems(&["0", "1"]), elems(&["1", "2"]), elems(&["2", "0"])];
+        let lt = vec![elems(&["0", "1"]), elems(&["0", "2"]), elems(&["1", "2"])];
+        structure
+            .interpret(SymbolKind::Constant, name("zero"), zero)
+            .unwrap();
+        structure
+            .interpret(SymbolKind::Function, name("s"), s)
+            .unwrap();
+        structure
+            .interpret(SymbolKind::Predicate, name("Lt"), lt)
+            .unwrap();
+        structure
+    }
+
+    fn eval(structure: &Structure, s: &str) -> Result<bool, ModelError> {
+        structure.eval(&expr(s), &Env::new())
+    }
+
+    #[test]
+    fn formulas_are_evaluated_over_the_tables() {
+        let structure = structure();
+        assert!(eval(&structure, "(Lt zero (s zero))").unwrap());
+        assert!(eval(&structure, "(exists x (Lt zero x))").unwrap());
+        assert!(eval(&structure, "(forall x (not (Lt x x)))").unwrap());
+        assert!(!eval(&structure, "(forall x (Lt x (s x)))").unwrap());
+        assert!(!eval(&structure, "(exists x (Lt x zero))").unwrap());
+        assert!(eval(&structure, "(imp (Lt zero zero) (Lt (s zero) zero))").unwrap());
+    }
+
+    #[test]
+    fn quantifiers_are_explained_by_their_witnesses() {
+        let structure = structure();
+        let mut out = Vec::new();
+        let formula = expr("(forall x (Lt x (s x)))");
+        structure
+            .explain(&formula, &Env::new(), 0, &mut out)
+            .unwrap();
+        assert_eq!(out, vec!["\t\tforall x: counterexample x = 2"]);
+        out.clear();
+        let formula = expr("(exists x (Lt x zero))");
+        structure
+            .explain(&formula, &Env::new(), 0, &mut out)
+            .unwrap();
+        assert_eq!(out, vec!["\t\texists x: no witness in the domain"]);
+    }
+
+    #[test]
+    fn bad_tables_and_formulas_are_refused() {
+        let mut structure = Structure::new();
+        assert!(matches!(
+            structure.interpret(SymbolKind::Constant, name("c"), vec![elems(&["a"])]),
+            Err(ModelError::EmptyDomain)
+        ));
+        structure.set_domain(elems(&["a", "b"]));
+        assert!(matches!(
+            structure.interpret(SymbolKind::Constant, name("c"), vec![elems(&["z"])]),
+            Err(ModelError::NotInDomain(_))
+        ));
+        assert!(matches!(
+            structure.interpret(SymbolKind::Constant, name("c"), vec![elems(&["a", "b"])]),
+            Err(ModelError::WrongArity(_))
+        ));
+        let f = vec![elems(&["a", "b"])];
+        structure
+            .interpret(SymbolKind::Function, name("f"), f)
+            .unwrap();
+        let p = vec![elems(&["a"])];
+        structure
+            .interpret(SymbolKind::Predicate, name("P"), p)
+            .unwrap();
+        assert!(matches!(
+            eval(&structure, "(P c)"),
+            Err(ModelError::Uninterpreted(_))
+        ));
+        assert!(matches!(
+            eval(&structure, "(exists x (P (f x)))"),
+            Err(ModelError::MissingValue(..))
+        ));
+        assert!(matches!(
+            eval(&structure, "(exists x (P x x))"),
+            Err(ModelError::WrongArity(_))
+        ));
+        assert!(matches!(
+            eval(&structure, "(box (P a))"),
+            Err(ModelError::NotEvaluable(_))
+        ));
+    }
+
+    #[test]
+    fn a_new_domain_clears_the_interpretations() {
+        let mut structure = structure();
+        structure.set_domain(elems(&["a"]));
+        assert!(structure.constants.is_empty());
+        assert!(structure.functions.is_empty());
+        assert!(structure.relations.is_empty());
+        assert!(matches!(
+            eval(&structure, "(Lt zero zero)"),
+            Err(ModelError::Uninterpreted(_))
+        ));
+    }
+}
@@ -0,0 +1,513 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::model::*;
+use crate::parser::*;
+use crate::resolution::*;
+
+#[allow(clippy::vec_box)]
+fn and_of(parts: Vec<Box<LogExpr>>) -> Box<LogExpr> {
+    let mut kept = Vec::new();
+    for part in parts {
+        match *part {
+            LogExpr::True => {}
+            LogExpr::False => return Box::new(LogExpr::False),
+            _ => kept.push(part),
+        }
+    }
+    match kept.len() {
+        0 => Box::new(LogExpr::True),
+        1 => kept.pop().unwrap(),
+        _ => Box::new(LogExpr::BinaryOp(Box::new(Operator::And), kept)),
+    }
+}
+
+#[allow(clippy::vec_box)]
+fn or_of(parts: Vec<Box<LogExpr>>) -> Box<LogExpr> {
+    let mut kept = Vec::new();
+    for part in parts {
+        match *part {
+            LogExpr::False => {}
+            LogExpr::True => return Box::new(LogExpr::True),
+            _ => kept.push(part),
+        }
+    }
+    match kept.len() {
+        0 => Box::new(LogExpr::False),
+        1 => kept.pop().unwrap(),
+        _ => Box::new(LogExpr::BinaryOp(Box::new(Operator::Or), kept)),
+    }
+}
+
+fn not_of(expr: &LogExpr) -> Box<LogExpr> {
+    match expr {
+        LogExpr::True => Box::new(LogExpr::False),
+        LogExpr::False => Box::new(LogExpr::True),
+        _ => expr.negate(),
+    }
+}
+
+// Every tuple of `arity` elements drawn from a domain of `size`
+fn tuples(size: usize, arity: usize) -> Vec<Vec<usize>> {
+    let mut result = vec![Vec::new()];
+    for _ in 0..arity {
+        result = result
+            .into_iter()
+            .flat_map(|t| {
+                (0..size).map(move |e| {
+                    let mut t = t.clone();
+                    t.push(e);
+                    t
+                })
+            })
+            .collect();
+    }
+    result
+}
+
+fn element(e: usize) -> String {
+    format!("d{}", e)
+}
+
+fn ground_name(name: &str, args: &[usize]) -> String {
+    if args.is_empty() {
+        name.to_string()
+    } else {
+        let args = args.iter().map(|e| element(*e)).collect::<Vec<_>>();
+        format!("{}({})", name, args.join(","))
+    }
+}
+
+// The propositional atom stating that a function maps `args` to `value`
+fn cell(name: &str, args: &[usize], value: usize) -> Box<LogExpr> {
+    LogExpr::atom(&format!("{}={}", ground_name(name, args), element(value)))
+}
+
+/// Searches for finite models by grounding over growing domains and handing
+/// the definitional clause form to a DPLL solver
+#[derive(Debug, Clone)]
+pub struct ModelFinder {
+    pub predicates: Vec<(String, usize)>,
+    // Constants are the nullary functions, in order of first appearance
+    pub functions: Vec<(String, usize)>,
+}
+
+impl ModelFinder {
+    pub fn new(formulas: &[Box<LogExpr>]) -> ModelFinder {
+        let mut finder = ModelFinder {
+            predicates: Vec::new(),
+            functions: Vec::new(),
+        };
+        for formula in formulas {
+            finder.collect_formula(formula, &HashSet::new());
+        }
+        finder
+    }
+
+    fn note(symbols: &mut Vec<(String, usize)>, name: &str, arity: usize) {
+        if !symbols.iter().any(|(n, _)| n == name) {
+            symbols.push((name.to_string(), arity));
+        }
+    }
+
+    fn collect_term(&mut self, term: &LogExpr, bound: &HashSet<String>) {
+        match term {
+            LogExpr::Atom(token) | LogExpr::Var(token) if !bound.contains(token.text.as_str()) => {
+                ModelFinder::note(&mut self.functions, &token.text, 0);
+            }
+            LogExpr::Func(name, args) => {
+                ModelFinder::note(&mut self.functions, &name.text, args.len());
+                args.iter().for_each(|arg| self.collect_term(arg, bound));
+            }
+            _ => {}
+        }
+    }
+
+    fn collect_formula(&mut self, expr: &LogExpr, bound: &HashSet<String>) {
+        match expr {
+            LogExpr::Atom(token) | LogExpr::Var(token) => {
+                ModelFinder::note(&mut self.predicates, &token.text, 0)
+            }
+            LogExpr::Pred(name, args) => {
+                ModelFinder::note(&mut self.predicates, &name.text, args.len());
+                args.iter().for_each(|arg| self.collect_term(arg, bound));
+            }
+            LogExpr::UnaryOp(_, inner) => self.collect_formula(inner, bound),
+            LogExpr::BinaryOp(_, exprs) => {
+                exprs.iter().for_each(|e| self.collect_formula(e, bound))
+            }
+            LogExpr::Quant(_, var, body) => {
+                let mut bound = bound.clone();
+                bound.insert(var.text.to_string());
+                self.collect_formula(body, &bound);
+            }
+            _ => {}
+        }
+    }
+
+    // The possible values of a term, each guarded by the function cells it relies on
+    #[allow(clippy::vec_box)]
+    fn ground_term(
+        &self,
+        term: &LogExpr,
+        env: &HashMap<String, usize>,
+        size: usize,
+    ) -> Vec<(Vec<Box<LogExpr>>, usize)> {
+        let (name, args) = match term {
+            LogExpr::Atom(token) | LogExpr::Var(token) => match env.get(token.text.as_str()) {
+                Some(e) => return vec![(Vec::new(), *e)],
+                None => (token.text.as_str(), Vec::new()),
+            },
+            LogExpr::Func(name, args) => (name.text.as_str(), args.clone()),
+            _ => return Vec::new(),
+        };
+        let mut alternatives = Vec::new();
+        for (conditions, values) in self.ground_args(&args, env, size) {
+            for value in 0..size {
+                let mut conditions = conditions.clone();
+                conditions.push(cell(name, &values, value));
+                alternatives.push((conditions, value));
+            }
+        }
+        alternatives
+    }
+
+    #[allow(clippy::vec_box)]
+    fn ground_args(
+        &self,
+        args: &[Box<LogExpr>],
+        env: &HashMap<String, usize>,
+        size: usize,
+    ) -> Vec<(Vec<Box<LogExpr>>, Vec<usize>)> {
+        let mut combos = vec![(Vec::new(), Vec::new())];
+        for arg in args {
+            let alternatives = self.ground_term(arg, env, size);
+            combos = combos
+                .into_iter()
+                .flat_map(|(conditions, values): (Vec<Box<LogExpr>>, Vec<usize>)| {
+                    alternatives.iter().map(move |(extra, value)| {
+                        let mut conditions = conditions.clone();
+                        conditions.extend(extra.iter().cloned());
+                        let mut values = values.clone();
+                        values.push(*value);
+                        (conditions, values)
+                    })
+                })
+                .collect();
+        }
+        combos
+    }
+
+    fn ground(&self, expr: &LogExpr, env: &HashMap<String, usize>, size: usize) -> Box<LogExpr> {
+        match expr {
+            LogExpr::True | LogExpr::False => Box::new(expr.clone()),
+            LogExpr::Unknown | LogExpr::Both => LogExpr::atom(&expr.to_string()),
+            LogExpr::Atom(token) | LogExpr::Var(token) => LogExpr::atom(&token.text),
+            LogExpr::Pred(name, args) => or_of(
+                self.ground_args(args, env, size)
+                    .into_iter()
+                    .map(|(mut conditions, values)| {
+                        let atom = if *name.text == "=" {
+                            match values.as_slice() {
+                                [lhs, rhs] if lhs == rhs => Box::new(LogExpr::True),
+                                _ => Box::new(LogExpr::False),
+                            }
+                        } else {
+                            LogExpr::atom(&ground_name(&name.text, &values))
+                        };
+                        conditions.push(atom);
+                        and_of(conditions)
+                    })
+                    .collect(),
+            ),
+            LogExpr::UnaryOp(op, inner) if **op == Operator::Not => {
+                not_of(&self.ground(inner, env, size))
+            }
+            LogExpr::BinaryOp(op, exprs) => match **op {
+                Operator::And => and_of(exprs.iter().map(|e| self.ground(e, env, size)).collect()),
+                Operator::Or => or_of(exprs.iter().map(|e| self.ground(e, env, size)).collect()),
+                Operator::Imp => match LogExpr::imp_parts(exprs) {
+                    Some((lhs, rhs)) => or_of(vec![
+                        not_of(&self.ground(&lhs, env, size)),
+                        self.ground(&rhs, env, size),
+                    ]),
+                    None => and_of(exprs.iter().map(|e| self.ground(e, env, size)).collect()),
+                },
+                _ => LogExpr::atom(&expr.to_string()),
+            },
+            LogExpr::Quant(q, var, body) => {
+                let parts = (0..size)
+                    .map(|e| {
+                        let mut env = env.clone();
+                        env.insert(var.text.to_string(), e);
+                        self.ground(body, &env, size)
+                    })
+                    .collect();
+                match **q {
+                    Quantifier::Forall => and_of(parts),
+                    Quantifier::Exists => or_of(parts),
+                }
+            }
+            // Modal and temporal formulas are opaque to first-order grounding
+            LogExpr::UnaryOp(..) => LogExpr::atom(&expr.to_string()),
+            LogExpr::Func(..) => Box::new(LogExpr::False),
+        }
+    }
+
+    // Each function maps every argument tuple to exactly one element. The k-th
+    // constant may only name one of the first k+1 elements, which breaks the
+    // symmetry between isomorphic models.
+    fn function_clauses(&self, size: usize) -> Vec<Clause> {
+        let positive = |atom| Literal {
+            atom,
+            positive: true,
+        };
+        let mut clauses = Vec::new();
+        let mut constants = 0;
+        for (name, arity) in &self.functions {
+            let limit = if *arity == 0 {
+                constants += 1;
+                constants.min(size)
+            } else {
+                size
+            };
+            for args in tuples(size, *arity) {
+                clauses.push(Clause::new(
+                    (0..limit).map(|v| positive(cell(name, &args, v))).collect(),
+                ));
+                for v in 0..size {
+                    for w in v + 1..size {
+                        clauses.push(Clause::new(vec![
+                            positive(cell(name, &args, v)).complement(),
+                            positive(cell(name, &args, w)).complement(),
+                        ]));
+                    }
+                }
+            }
+        }
+        clauses
+    }
+
+    fn structure(&self, size: usize, truth: &HashMap<String, bool>) -> Structure {
+        let holds = |atom: &LogExpr| truth.get(&atom.to_string()).copied().unwrap_or(false);
+        let mut structure = Structure::new();
+        structure.set_domain((0..size).map(|e| Box::new(element(e))).collect());
+        for (name, arity) in &self.functions {
+            let mut table = FunctionTable::new();
+            for args in tuples(size, *arity) {
+                if let Some(v) = (0..size).find(|v| holds(&cell(name, &args, *v))) {
+                    if *arity == 0 {
+                        structure
+                            .constants
+                            .insert(Box::new(name.clone()), Box::new(element(v)));
+                    }
+                    let args = args.iter().map(|e| Box::new(element(*e))).collect();
+                    table.insert(args, Box::new(element(v)));
+                }
+            }
+            if *arity > 0 {
+                structure.functions.insert(Box::new(name.clone()), table);
+            }
+        }
+        for (name, arity) in &self.predicates {
+            // Equality was grounded as identity, so that is how it is read back
+            let tuples = tuples(size, *arity)
+                .into_iter()
+                .filter(|args| match name.as_str() {
+                    "=" => matches!(args.as_slice(), [lhs, rhs] if lhs == rhs),
+                    _ => holds(&LogExpr::atom(&ground_name(name, args))),
+                })
+                .map(|args| args.iter().map(|e| Box::new(element(*e))).collect())
+                .collect();
+            structure.relations.insert(Box::new(name.clone()), tuples);
+        }
+        structure
+    }
+
+    /// Tries domains of size 1 up to `max_size`, returning the first model
+    pub fn find(
+        &self,
+        formulas: &[Box<LogExpr>],
+        max_size: usize,
+    ) -> Result<Option<Structure>, ModelError> {
+        if let Some(part) = formulas.iter().find_map(|f| f.modal_part()) {
+            return Err(ModelError::NotEvaluable(Box::new(part.clone())));
+        }
+        for size in 1..=max_size {
+            let ground = and_of(
+                formulas
+                    .iter()
+                    .map(|f| self.ground(f, &HashMap::new(), size))
+                    .collect(),
+            );
+            let mut clauses = clausify_definitional(&ground);
+            clauses.extend(self.function_clauses(size));
+            if let Some(truth) = dpll(&clauses) {
+                return Ok(Some(self.structure(size, &truth)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+// Unit propagation to a fixpoint; false when some clause is falsified
+fn propagate(clauses: &[Vec<(usize, bool)>], assignment: &mut [Option<bool>]) -> bool {
+    loop {
+        let mut changed = false;
+        for clause in clauses {
+            let mut unassigned = None;
+            let mut open = 0;
+            let mut satisfied = false;
+            for &(var, positive) in clause {
+                match assignment[var] {
+                    Some(value) if value == positive => {
+                        satisfied = true;
+                        break;
+                    }
+                    Some(_) => {}
+                    None => {
+                        open += 1;
+                        unassigned = Some((var, positive));
+                    }
+                }
+            }
+            if satisfied {
+                continue;
+            }
+            match (open, unassigned) {
+                (0, _) => return false,
+                (1, Some((var, positive))) => {
+                    assignment[var] = Some(positive);
+                    changed = true;
+                }
+                _ => {}
+            }
+        }
+        if !changed {
+            return true;
+        }
+    }
+}
+
+fn search(
+    clauses: &[Vec<(usize, bool)>],
+    mut assignment: Vec<Option<bool>>,
+) -> Option<Vec<Option<bool>>> {
+    if !propagate(clauses, &mut assignment) {
+        return None;
+    }
+    // Branch on a variable of the first clause not yet satisfied
+    let branch = clauses.iter().find_map(|clause| {
+        if clause.iter().any(|&(v, p)| assignment[v] == Some(p)) {
+            None
+        } else {
+            clause
+                .iter()
+                .find(|&&(v, _)| assignment[v].is_none())
+                .copied()
+        }
+    });
+    match branch {
+        None => Some(assignment),
+        Some((var, positive)) => {
+            let mut first = assignment.clone();
+            first[var] = Some(positive);
+            search(clauses, first).or_else(|| {
+                assignment[var] = Some(!positive);
+                search(clauses, assignment)
+            })
+        }
+    }
+}
+
+/// A satisfying assignment for the clause set, keyed by atom text
+pub fn dpll(clauses: &[Clause]) -> Option<HashMap<String, bool>> {
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let numbered = clauses
+        .iter()
+        .map(|clause| {
+            clause
+                .literals
+                .iter()
+                .map(|l| {
+                    let next = index.len();
+                    (*index.entry(l.atom.to_string()).or_insert(next), l.positive)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let assignment = search(&numbered, vec![None; index.len()])?;
+    Some(
+        index
+            .into_iter()
+            .map(|(atom, i)| (atom, assignment[i].unwrap_or(false)))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn find(formulas: &[&str], max_size: usize) -> Result<Option<Structure>, ModelError> {
+        let formulas = formulas.iter().map(|f| expr(f)).collect::<Vec<_>>();
+        ModelFinder::new(&formulas).find(&formulas, max_size)
+    }
+
+    #[test]
+    fn models_are_found_at_the_smallest_size() {
+        let formulas = ["(exists x (P x))", "(exists x (not (P x)))"];
+        let model = find(&formulas, 3).unwrap().unwrap();
+        assert_eq!(model.domain.len(), 2);
+        for formula in formulas {
+            assert!(model.eval(&expr(formula), &Env::new()).unwrap());
+        }
+    }
+
+    #[test]
+    fn function_tables_are_total() {
+        let formulas = ["(forall x (and (or (P x) (P (f x))) (or (not (P x)) (not (P (f x))))))"];
+        let model = find(&formulas, 3).unwrap().unwrap();
+        assert_eq!(model.domain.len(), 2);
+        assert!(model.eval(&expr(formulas[0]), &Env::new()).unwrap());
+    }
+
+    #[test]
+    fn equality_is_read_back_as_identity() {
+        let formulas = ["(not (= a b))", "(forall x (or (= x a) (= x b)))"];
+        let model = find(&formulas, 3).unwrap().unwrap();
+        assert_eq!(model.domain.len(), 2);
+        for formula in formulas {
+            assert!(model.eval(&expr(formula), &Env::new()).unwrap());
+        }
+        assert!(!model.eval(&expr("(= a b)"), &Env::new()).unwrap());
+    }
+
+    #[test]
+    fn contradictions_have_no_model() {
+        let formulas = ["(forall x (P x))", "(exists x (not (P x)))"];
+        assert!(find(&formulas, 3).unwrap().is_none());
+        // Needs three elements, one more than allowed
+        let formulas = [
+            "(P a)",
+            "(Q b)",
+            "(R c)",
+            "(forall x (or (not (P x)) (not (Q x))))",
+        ];
+        assert!(find(&formulas, 1).unwrap().is_none());
+        assert!(find(&formulas, 2).unwrap().is_some());
+    }
+
+    #[test]
+    fn modal_formulas_are_refused() {
+        assert!(matches!(
+            find(&["(box (P a))"], 2),
+            Err(ModelError::NotEvaluable(_))
+        ));
+    }
+}
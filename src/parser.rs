@@ -2,7 +2,15 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Display;
 
+use crate::bdd::*;
+use crate::circuit::*;
+use crate::espresso::*;
+use crate::fuzzy::*;
+use crate::intuitionistic::*;
 use crate::lexer::*;
+use crate::manyvalued::*;
+use crate::modaltableau::*;
+use crate::signature::*;
 
 #[derive(Debug)]
 pub enum ParserError {
@@ -10,9 +18,22 @@ pub enum ParserError {
     ExpectedFilePath,
     ExpectedRuleName,
     ExpectedExpression,
+    ExpectedLineNumber,
+    ExpectedSymbolKind,
+    ExpectedArity,
+    ExpectedSymbolName,
+    ExpectedElement,
+    ExpectedNumber,
+    ExpectedModalSystem,
+    ExpectedLogic,
+    ExpectedSemantics,
+    ExpectedTNorm,
+    ExpectedDegree,
+    UnexpectedToken,
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::box_collection, clippy::vec_box)]
 pub enum Command {
     QuitRepl,
     DeleteRule {
@@ -36,6 +57,174 @@ pub enum Command {
     Eval {
         expr: Box<LogExpr>,
     },
+    Tableau {
+        exprs: Vec<Box<LogExpr>>,
+    },
+    Resolve {
+        premises: Vec<Box<LogExpr>>,
+        goal: Option<Box<LogExpr>>,
+    },
+    DefineAxiom {
+        name: Box<String>,
+        schema: Box<LogExpr>,
+    },
+    DefineInference {
+        name: Box<String>,
+        premises: Vec<Box<LogExpr>>,
+        conclusion: Box<LogExpr>,
+    },
+    StartProof {
+        goal: Option<Box<LogExpr>>,
+    },
+    ProofStep {
+        expr: Box<LogExpr>,
+        rule: Box<String>,
+        refs: Vec<usize>,
+    },
+    BindSymbol {
+        kind: SymbolKind,
+        name: Box<String>,
+        arity: usize,
+    },
+    ShowSignature,
+    SetDomain {
+        elements: Vec<Box<String>>,
+    },
+    Interpret {
+        kind: SymbolKind,
+        name: Box<String>,
+        entries: Vec<Vec<Box<String>>>,
+    },
+    EvaluateModel {
+        expr: Box<LogExpr>,
+    },
+    FindModel {
+        max_size: usize,
+        premises: Vec<Box<LogExpr>>,
+        goal: Option<Box<LogExpr>>,
+    },
+    Prenex {
+        expr: Box<LogExpr>,
+    },
+    Skolemize {
+        expr: Box<LogExpr>,
+    },
+    Herbrand {
+        depth: usize,
+        expr: Box<LogExpr>,
+    },
+    GroundInstances {
+        depth: usize,
+        expr: Box<LogExpr>,
+    },
+    Congruence {
+        premises: Vec<Box<LogExpr>>,
+        goal: Option<Box<LogExpr>>,
+    },
+    FreeVariables {
+        expr: Box<LogExpr>,
+    },
+    SetWorlds {
+        worlds: Vec<Box<String>>,
+    },
+    ShowKripke,
+    AddAccess {
+        pairs: Vec<Vec<Box<String>>>,
+    },
+    SetValuation {
+        atom: Box<String>,
+        worlds: Vec<Box<String>>,
+    },
+    EvaluateAt {
+        world: Box<String>,
+        expr: Box<LogExpr>,
+    },
+    EvaluateGlobally {
+        expr: Box<LogExpr>,
+    },
+    ModalValidity {
+        system: Box<String>,
+        expr: Box<LogExpr>,
+    },
+    ModalRule {
+        system: Box<String>,
+        name: Box<String>,
+    },
+    CheckTrace {
+        file_path: Box<String>,
+        expr: Box<LogExpr>,
+    },
+    ShowBuchi {
+        expr: Box<LogExpr>,
+    },
+    LtlSatisfiable {
+        expr: Box<LogExpr>,
+    },
+    LtlValidity {
+        expr: Box<LogExpr>,
+    },
+    ShowSystem {
+        file_path: Box<String>,
+    },
+    CheckCtl {
+        file_path: Box<String>,
+        expr: Box<LogExpr>,
+    },
+    SetLogic {
+        logic: Option<Logic>,
+    },
+    SetSemantics {
+        semantics: Option<Semantics>,
+    },
+    TruthTable {
+        expr: Box<LogExpr>,
+    },
+    Consequence {
+        premises: Vec<Box<LogExpr>>,
+        goal: Box<LogExpr>,
+    },
+    SetTNorm {
+        tnorm: Option<TNorm>,
+    },
+    ShowDegrees,
+    AssignDegree {
+        atom: Box<String>,
+        degree: f64,
+    },
+    EvaluateDegree {
+        expr: Box<LogExpr>,
+    },
+    ShowBdd {
+        order: BddOrder,
+        expr: Box<LogExpr>,
+    },
+    BddEquivalent {
+        lhs: Box<LogExpr>,
+        rhs: Box<LogExpr>,
+    },
+    WriteBddDot {
+        file_path: Box<String>,
+        order: BddOrder,
+        expr: Box<LogExpr>,
+    },
+    Minimize {
+        expr: Box<LogExpr>,
+        dont_care: Option<Box<LogExpr>>,
+    },
+    Espresso {
+        form: NormalForm,
+        expr: Box<LogExpr>,
+        dont_care: Option<Box<LogExpr>>,
+    },
+    KarnaughMap {
+        groups: bool,
+        expr: Box<LogExpr>,
+        dont_care: Option<Box<LogExpr>>,
+    },
+    ShowCircuit {
+        form: CircuitForm,
+        expr: Box<LogExpr>,
+    },
 }
 
 impl Display for Command {
@@ -50,6 +239,186 @@ impl Display for Command {
             Command::LoadFile { file_path } => write!(f, "load {}", file_path),
             Command::SaveFile { file_path } => write!(f, "save {}", file_path),
             Command::Eval { expr } => write!(f, "{}", expr),
+            Command::Tableau { exprs } => {
+                write!(f, "tableau")?;
+                for expr in exprs {
+                    write!(f, " {}", expr)?;
+                }
+                Ok(())
+            }
+            Command::Resolve { premises, goal } | Command::Congruence { premises, goal } => {
+                let name = match self {
+                    Command::Resolve { .. } => "resolve",
+                    _ => "congruence",
+                };
+                write!(f, "{}", name)?;
+                for premise in premises {
+                    write!(f, " {}", premise)?;
+                }
+                match goal {
+                    Some(goal) => write!(f, " |- {}", goal),
+                    None => Ok(()),
+                }
+            }
+            Command::DefineAxiom { name, schema } => write!(f, "axiom {} {}", name, schema),
+            Command::DefineInference {
+                name,
+                premises,
+                conclusion,
+            } => {
+                write!(f, "inference {}", name)?;
+                for premise in premises {
+                    write!(f, " {}", premise)?;
+                }
+                write!(f, " |- {}", conclusion)
+            }
+            Command::StartProof { goal } => match goal {
+                Some(goal) => write!(f, "proof {}", goal),
+                None => write!(f, "proof"),
+            },
+            Command::ProofStep { expr, rule, refs } => {
+                write!(f, "step {} {}", expr, rule)?;
+                for n in refs {
+                    write!(f, " {}", n)?;
+                }
+                Ok(())
+            }
+            Command::BindSymbol { kind, name, arity } => {
+                write!(f, "bind {} {} {}", kind, arity, name)
+            }
+            Command::ShowSignature => write!(f, "bind"),
+            Command::SetDomain { elements } => {
+                write!(f, "domain")?;
+                for elem in elements {
+                    write!(f, " {}", elem)?;
+                }
+                Ok(())
+            }
+            Command::Interpret {
+                kind,
+                name,
+                entries,
+            } => {
+                write!(f, "interpret {} {}", kind, name)?;
+                for entry in entries {
+                    let elems = entry.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                    write!(f, " ({})", elems.join(" "))?;
+                }
+                Ok(())
+            }
+            Command::EvaluateModel { expr } => write!(f, "eval {}", expr),
+            Command::Prenex { expr } => write!(f, "prenex {}", expr),
+            Command::Skolemize { expr } => write!(f, "skolem {}", expr),
+            Command::FreeVariables { expr } => write!(f, "free {}", expr),
+            Command::SetWorlds { worlds } => {
+                write!(f, "worlds")?;
+                for world in worlds {
+                    write!(f, " {}", world)?;
+                }
+                Ok(())
+            }
+            Command::ShowKripke => write!(f, "worlds"),
+            Command::AddAccess { pairs } => {
+                write!(f, "access")?;
+                for pair in pairs {
+                    let worlds = pair.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+                    write!(f, " ({})", worlds.join(" "))?;
+                }
+                Ok(())
+            }
+            Command::SetValuation { atom, worlds } => {
+                write!(f, "valuation {}", atom)?;
+                for world in worlds {
+                    write!(f, " {}", world)?;
+                }
+                Ok(())
+            }
+            Command::EvaluateAt { world, expr } => write!(f, "at {} {}", world, expr),
+            Command::EvaluateGlobally { expr } => write!(f, "global {}", expr),
+            Command::ModalValidity { system, expr } => write!(f, "modal {} {}", system, expr),
+            Command::ModalRule { system, name } => write!(f, "modalrule {} {}", system, name),
+            Command::CheckTrace { file_path, expr } => write!(f, "trace {} {}", file_path, expr),
+            Command::ShowBuchi { expr } => write!(f, "buchi {}", expr),
+            Command::LtlSatisfiable { expr } => write!(f, "ltlsat {}", expr),
+            Command::LtlValidity { expr } => write!(f, "ltlvalid {}", expr),
+            Command::ShowSystem { file_path } => write!(f, "system {}", file_path),
+            Command::CheckCtl { file_path, expr } => write!(f, "ctl {} {}", file_path, expr),
+            Command::SetLogic { logic: None } => write!(f, "logic"),
+            Command::SetLogic { logic: Some(logic) } => write!(f, "logic {}", logic),
+            Command::SetSemantics { semantics: None } => write!(f, "semantics"),
+            Command::SetSemantics {
+                semantics: Some(semantics),
+            } => write!(f, "semantics {}", semantics),
+            Command::TruthTable { expr } => write!(f, "truthtable {}", expr),
+            Command::SetTNorm { tnorm: None } => write!(f, "tnorm"),
+            Command::SetTNorm { tnorm: Some(tnorm) } => write!(f, "tnorm {}", tnorm),
+            Command::ShowDegrees => write!(f, "assign"),
+            Command::AssignDegree { atom, degree } => write!(f, "assign {} {}", atom, degree),
+            Command::EvaluateDegree { expr } => write!(f, "degree {}", expr),
+            Command::ShowBdd { order, expr } => write!(f, "bdd {} {}", order, expr),
+            Command::BddEquivalent { lhs, rhs } => write!(f, "bddequiv {} {}", lhs, rhs),
+            Command::WriteBddDot {
+                file_path,
+                order,
+                expr,
+            } => write!(f, "bdddot {} {} {}", file_path, order, expr),
+            Command::Minimize {
+                expr,
+                dont_care: None,
+            } => write!(f, "minimize {}", expr),
+            Command::Minimize {
+                expr,
+                dont_care: Some(dont_care),
+            } => write!(f, "minimize {} {}", expr, dont_care),
+            Command::Espresso {
+                form,
+                expr,
+                dont_care: None,
+            } => write!(f, "espresso {} {}", form, expr),
+            Command::Espresso {
+                form,
+                expr,
+                dont_care: Some(dont_care),
+            } => write!(f, "espresso {} {} {}", form, expr, dont_care),
+            Command::ShowCircuit { form, expr } => write!(f, "circuit {} {}", form, expr),
+            Command::KarnaughMap {
+                groups,
+                expr,
+                dont_care,
+            } => {
+                write!(f, "kmap")?;
+                if *groups {
+                    write!(f, " groups")?;
+                }
+                write!(f, " {}", expr)?;
+                match dont_care {
+                    Some(dont_care) => write!(f, " {}", dont_care),
+                    None => Ok(()),
+                }
+            }
+            Command::Consequence { premises, goal } => {
+                write!(f, "valid")?;
+                for premise in premises {
+                    write!(f, " {}", premise)?;
+                }
+                write!(f, " |- {}", goal)
+            }
+            Command::Herbrand { depth, expr } => write!(f, "herbrand {} {}", depth, expr),
+            Command::GroundInstances { depth, expr } => write!(f, "ground {} {}", depth, expr),
+            Command::FindModel {
+                max_size,
+                premises,
+                goal,
+            } => {
+                write!(f, "findmodel {}", max_size)?;
+                for premise in premises {
+                    write!(f, " {}", premise)?;
+                }
+                match goal {
+                    Some(goal) => write!(f, " |- {}", goal),
+                    None => Ok(()),
+                }
+            }
         }
     }
 }
@@ -106,9 +475,446 @@ impl Command {
                     .next()
                     .ok_or(ParserError::ExpectedRuleName)?
                     .text
-                    .clone();
+                    .clone();
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::ApplyRule { name, expr }))
+            }
+            TokenKind::Tableau => {
+                lexer.next();
+                let exprs = Command::parse_exprs(lexer)?;
+                Ok(Box::new(Command::Tableau { exprs }))
+            }
+            TokenKind::Resolve => {
+                lexer.next();
+                let (premises, goal) = Command::parse_sequent(lexer)?;
+                Ok(Box::new(Command::Resolve { premises, goal }))
+            }
+            TokenKind::Congruence => {
+                lexer.next();
+                let (premises, goal) = Command::parse_sequent(lexer)?;
+                Ok(Box::new(Command::Congruence { premises, goal }))
+            }
+            TokenKind::Axiom => {
+                lexer.next();
+                let name = lexer
+                    .next()
+                    .ok_or(ParserError::ExpectedRuleName)?
+                    .text
+                    .clone();
+                let schema = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::DefineAxiom { name, schema }))
+            }
+            TokenKind::Inference => {
+                lexer.next();
+                let name = lexer
+                    .next()
+                    .ok_or(ParserError::ExpectedRuleName)?
+                    .text
+                    .clone();
+                match Command::parse_sequent(lexer)? {
+                    (premises, Some(conclusion)) => Ok(Box::new(Command::DefineInference {
+                        name,
+                        premises,
+                        conclusion,
+                    })),
+                    (_, None) => Err(ParserError::ExpectedExpression),
+                }
+            }
+            TokenKind::Proof => {
+                lexer.next();
+                let goal = if *lexer.peek_token().kind == TokenKind::End {
+                    None
+                } else {
+                    Some(LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?)
+                };
+                Ok(Box::new(Command::StartProof { goal }))
+            }
+            TokenKind::Step => {
+                lexer.next();
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                let rule = lexer.next_token();
+                if *rule.kind != TokenKind::String {
+                    return Err(ParserError::ExpectedRuleName);
+                }
+                let mut refs = Vec::new();
+                while *lexer.peek_token().kind != TokenKind::End {
+                    let n = lexer
+                        .next_token()
+                        .text
+                        .parse::<usize>()
+                        .map_err(|_| ParserError::ExpectedLineNumber)?;
+                    refs.push(n);
+                }
+                Ok(Box::new(Command::ProofStep {
+                    expr,
+                    rule: rule.text.clone(),
+                    refs,
+                }))
+            }
+            TokenKind::Bind => {
+                lexer.next();
+                if *lexer.peek_token().kind == TokenKind::End {
+                    return Ok(Box::new(Command::ShowSignature));
+                }
+                let kind = SymbolKind::from_str(&lexer.next_token().text)
+                    .ok_or(ParserError::ExpectedSymbolKind)?;
+                let arity = if kind == SymbolKind::Constant {
+                    0
+                } else {
+                    lexer
+                        .next_token()
+                        .text
+                        .parse::<usize>()
+                        .map_err(|_| ParserError::ExpectedArity)?
+                };
+                let name = lexer.next_token();
+                // A predicate named like an operator would always parse as the
+                // operator, so it could never be applied
+                let operator = Operator::from_str(&name.text, false).is_some();
+                if *name.kind != TokenKind::String || kind == SymbolKind::Predicate && operator {
+                    return Err(ParserError::ExpectedSymbolName);
+                }
+                Ok(Box::new(Command::BindSymbol {
+                    kind,
+                    name: name.text.clone(),
+                    arity,
+                }))
+            }
+            TokenKind::Domain => {
+                lexer.next();
+                let elements = Command::parse_elements(lexer)?;
+                Ok(Box::new(Command::SetDomain { elements }))
+            }
+            TokenKind::Worlds => {
+                lexer.next();
+                let worlds = Command::parse_elements(lexer)?;
+                if worlds.is_empty() {
+                    Ok(Box::new(Command::ShowKripke))
+                } else {
+                    Ok(Box::new(Command::SetWorlds { worlds }))
+                }
+            }
+            TokenKind::Access => {
+                lexer.next();
+                let mut pairs = Vec::new();
+                while *lexer.peek_token().kind != TokenKind::End {
+                    pairs.push(Command::parse_tuple(lexer)?);
+                }
+                Ok(Box::new(Command::AddAccess { pairs }))
+            }
+            TokenKind::Valuation => {
+                lexer.next();
+                let atom = lexer.next_token();
+                if *atom.kind != TokenKind::String {
+                    return Err(ParserError::ExpectedSymbolName);
+                }
+                let worlds = Command::parse_elements(lexer)?;
+                Ok(Box::new(Command::SetValuation {
+                    atom: atom.text.clone(),
+                    worlds,
+                }))
+            }
+            TokenKind::At => {
+                lexer.next();
+                let world = lexer.next_token();
+                if *world.kind != TokenKind::String {
+                    return Err(ParserError::ExpectedElement);
+                }
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::EvaluateAt {
+                    world: world.text.clone(),
+                    expr,
+                }))
+            }
+            TokenKind::Buchi | TokenKind::LtlSat | TokenKind::LtlValid => {
+                let kind = lexer.next_token().kind;
+                lexer.temporal = true;
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                match *kind {
+                    TokenKind::Buchi => Ok(Box::new(Command::ShowBuchi { expr })),
+                    TokenKind::LtlSat => Ok(Box::new(Command::LtlSatisfiable { expr })),
+                    _ => Ok(Box::new(Command::LtlValidity { expr })),
+                }
+            }
+            TokenKind::Logic => {
+                lexer.next();
+                let token = lexer.next_token();
+                match *token.kind {
+                    TokenKind::End => Ok(Box::new(Command::SetLogic { logic: None })),
+                    _ => {
+                        let logic =
+                            Logic::from_str(&token.text).ok_or(ParserError::ExpectedLogic)?;
+                        Ok(Box::new(Command::SetLogic { logic: Some(logic) }))
+                    }
+                }
+            }
+            TokenKind::Semantics => {
+                lexer.next();
+                let token = lexer.next_token();
+                match *token.kind {
+                    TokenKind::End => Ok(Box::new(Command::SetSemantics { semantics: None })),
+                    _ => {
+                        let semantics = Semantics::from_str(&token.text)
+                            .ok_or(ParserError::ExpectedSemantics)?;
+                        Ok(Box::new(Command::SetSemantics {
+                            semantics: Some(semantics),
+                        }))
+                    }
+                }
+            }
+            TokenKind::TNorm => {
+                lexer.next();
+                let token = lexer.next_token();
+                match *token.kind {
+                    TokenKind::End => Ok(Box::new(Command::SetTNorm { tnorm: None })),
+                    _ => {
+                        let tnorm =
+                            TNorm::from_str(&token.text).ok_or(ParserError::ExpectedTNorm)?;
+                        Ok(Box::new(Command::SetTNorm { tnorm: Some(tnorm) }))
+                    }
+                }
+            }
+            TokenKind::Assign => {
+                lexer.next();
+                let atom = lexer.next_token();
+                match *atom.kind {
+                    TokenKind::End => return Ok(Box::new(Command::ShowDegrees)),
+                    TokenKind::String => {}
+                    _ => return Err(ParserError::ExpectedSymbolName),
+                }
+                // Names such as t or both read as constants, never as the atom
+                match LogExpr::from_token(atom.clone()).as_deref() {
+                    Some(LogExpr::Atom(_) | LogExpr::Var(_)) => {}
+                    _ => return Err(ParserError::ExpectedSymbolName),
+                }
+                let degree = lexer
+                    .next_token()
+                    .text
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|d| (0.0..=1.0).contains(d))
+                    .ok_or(ParserError::ExpectedDegree)?;
+                if *lexer.peek_token().kind != TokenKind::End {
+                    return Err(ParserError::UnexpectedToken);
+                }
+                Ok(Box::new(Command::AssignDegree {
+                    atom: atom.text.clone(),
+                    degree,
+                }))
+            }
+            TokenKind::Degree => {
+                lexer.next();
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::EvaluateDegree { expr }))
+            }
+            TokenKind::Bdd => {
+                lexer.next();
+                let order = Command::parse_bdd_order(lexer);
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::ShowBdd { order, expr }))
+            }
+            TokenKind::BddEquiv => {
+                lexer.next();
+                let lhs = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                let rhs = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::BddEquivalent { lhs, rhs }))
+            }
+            TokenKind::BddDot => {
+                lexer.next();
+                let file_path = lexer
+                    .next()
+                    .ok_or(ParserError::ExpectedFilePath)?
+                    .text
+                    .clone();
+                let order = Command::parse_bdd_order(lexer);
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::WriteBddDot {
+                    file_path,
+                    order,
+                    expr,
+                }))
+            }
+            // An optional second formula holds where the value does not matter
+            TokenKind::Minimize => {
+                lexer.next();
+                let (expr, dont_care) = Command::parse_dont_care(lexer)?;
+                Ok(Box::new(Command::Minimize { expr, dont_care }))
+            }
+            TokenKind::Espresso => {
+                lexer.next();
+                let token = lexer.peek_token();
+                let form = match NormalForm::from_str(&token.text) {
+                    Some(form) if *token.kind == TokenKind::String => {
+                        lexer.next();
+                        form
+                    }
+                    _ => NormalForm::Dnf,
+                };
+                let (expr, dont_care) = Command::parse_dont_care(lexer)?;
+                Ok(Box::new(Command::Espresso {
+                    form,
+                    expr,
+                    dont_care,
+                }))
+            }
+            TokenKind::Circuit => {
+                lexer.next();
+                let token = lexer.peek_token();
+                let form = match CircuitForm::from_str(&token.text) {
+                    Some(form) if *token.kind == TokenKind::String => {
+                        lexer.next();
+                        form
+                    }
+                    _ => CircuitForm::Gates,
+                };
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::ShowCircuit { form, expr }))
+            }
+            TokenKind::KarnaughMap => {
+                lexer.next();
+                let token = lexer.peek_token();
+                let groups = *token.kind == TokenKind::String && *token.text == "groups";
+                if groups {
+                    lexer.next();
+                }
+                let (expr, dont_care) = Command::parse_dont_care(lexer)?;
+                Ok(Box::new(Command::KarnaughMap {
+                    groups,
+                    expr,
+                    dont_care,
+                }))
+            }
+            TokenKind::TruthTable => {
+                lexer.next();
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::TruthTable { expr }))
+            }
+            // A lone formula is a goal without premises
+            TokenKind::Valid => {
+                lexer.next();
+                match Command::parse_sequent(lexer)? {
+                    (premises, Some(goal)) => Ok(Box::new(Command::Consequence { premises, goal })),
+                    (mut premises, None) if premises.len() == 1 => {
+                        let goal = premises.pop().ok_or(ParserError::ExpectedExpression)?;
+                        Ok(Box::new(Command::Consequence { premises, goal }))
+                    }
+                    _ => Err(ParserError::ExpectedExpression),
+                }
+            }
+            TokenKind::System => {
+                lexer.next();
+                let file_path = lexer
+                    .next()
+                    .ok_or(ParserError::ExpectedFilePath)?
+                    .text
+                    .clone();
+                Ok(Box::new(Command::ShowSystem { file_path }))
+            }
+            TokenKind::Ctl => {
+                lexer.next();
+                let file_path = lexer
+                    .next()
+                    .ok_or(ParserError::ExpectedFilePath)?
+                    .text
+                    .clone();
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::CheckCtl { file_path, expr }))
+            }
+            TokenKind::Trace => {
+                lexer.next();
+                let file_path = lexer
+                    .next()
+                    .ok_or(ParserError::ExpectedFilePath)?
+                    .text
+                    .clone();
+                lexer.temporal = true;
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::CheckTrace { file_path, expr }))
+            }
+            TokenKind::Modal | TokenKind::ModalRule => {
+                let kind = lexer.next_token().kind;
+                let system = lexer.next_token().text;
+                if Frame::system(&system).is_none() {
+                    return Err(ParserError::ExpectedModalSystem);
+                }
+                if *kind == TokenKind::Modal {
+                    let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                    Ok(Box::new(Command::ModalValidity { system, expr }))
+                } else {
+                    let name = lexer.next().ok_or(ParserError::ExpectedRuleName)?.text;
+                    Ok(Box::new(Command::ModalRule { system, name }))
+                }
+            }
+            TokenKind::Global => {
+                lexer.next();
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::EvaluateGlobally { expr }))
+            }
+            TokenKind::Interpret => {
+                lexer.next();
+                let kind = SymbolKind::from_str(&lexer.next_token().text)
+                    .ok_or(ParserError::ExpectedSymbolKind)?;
+                let name = lexer.next_token();
+                if *name.kind != TokenKind::String {
+                    return Err(ParserError::ExpectedSymbolName);
+                }
+                let mut entries = Vec::new();
+                while *lexer.peek_token().kind != TokenKind::End {
+                    entries.push(Command::parse_tuple(lexer)?);
+                }
+                Ok(Box::new(Command::Interpret {
+                    kind,
+                    name: name.text.clone(),
+                    entries,
+                }))
+            }
+            TokenKind::Evaluate => {
+                lexer.next();
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::EvaluateModel { expr }))
+            }
+            TokenKind::FindModel => {
+                lexer.next();
+                let max_size = lexer
+                    .next_token()
+                    .text
+                    .parse::<usize>()
+                    .map_err(|_| ParserError::ExpectedNumber)?;
+                let (premises, goal) = Command::parse_sequent(lexer)?;
+                Ok(Box::new(Command::FindModel {
+                    max_size,
+                    premises,
+                    goal,
+                }))
+            }
+            TokenKind::Prenex => {
+                lexer.next();
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::Prenex { expr }))
+            }
+            TokenKind::Skolem => {
+                lexer.next();
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::Skolemize { expr }))
+            }
+            TokenKind::Free => {
+                lexer.next();
+                let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                Ok(Box::new(Command::FreeVariables { expr }))
+            }
+            TokenKind::Herbrand | TokenKind::Ground => {
+                let kind = lexer.next_token().kind;
+                let depth = lexer
+                    .next_token()
+                    .text
+                    .parse::<usize>()
+                    .map_err(|_| ParserError::ExpectedNumber)?;
                 let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
-                Ok(Box::new(Command::ApplyRule { name, expr }))
+                if *kind == TokenKind::Herbrand {
+                    Ok(Box::new(Command::Herbrand { depth, expr }))
+                } else {
+                    Ok(Box::new(Command::GroundInstances { depth, expr }))
+                }
             }
             TokenKind::End => {
                 lexer.next();
@@ -120,6 +926,166 @@ impl Command {
             }
         }
     }
+
+    /// Every expression the command carries, for checks that apply to all of them
+    pub fn exprs(&self) -> Vec<&LogExpr> {
+        match self {
+            Command::DefineRule { lhs, rhs, .. } | Command::BddEquivalent { lhs, rhs } => {
+                vec![lhs, rhs]
+            }
+            Command::ApplyRule { expr, .. }
+            | Command::Eval { expr }
+            | Command::DefineAxiom { schema: expr, .. }
+            | Command::ProofStep { expr, .. }
+            | Command::EvaluateModel { expr }
+            | Command::Prenex { expr }
+            | Command::Skolemize { expr }
+            | Command::FreeVariables { expr }
+            | Command::EvaluateAt { expr, .. }
+            | Command::EvaluateGlobally { expr }
+            | Command::ModalValidity { expr, .. }
+            | Command::CheckTrace { expr, .. }
+            | Command::ShowBuchi { expr }
+            | Command::LtlSatisfiable { expr }
+            | Command::LtlValidity { expr }
+            | Command::CheckCtl { expr, .. }
+            | Command::TruthTable { expr }
+            | Command::EvaluateDegree { expr }
+            | Command::ShowBdd { expr, .. }
+            | Command::WriteBddDot { expr, .. }
+            | Command::ShowCircuit { expr, .. }
+            | Command::Herbrand { expr, .. }
+            | Command::GroundInstances { expr, .. } => vec![expr],
+            Command::Tableau { exprs } => exprs.iter().map(|e| &**e).collect(),
+            Command::Resolve { premises, goal }
+            | Command::Congruence { premises, goal }
+            | Command::FindModel { premises, goal, .. } => {
+                premises.iter().chain(goal.iter()).map(|e| &**e).collect()
+            }
+            Command::Consequence { premises, goal } => premises
+                .iter()
+                .chain(std::iter::once(goal))
+                .map(|e| &**e)
+                .collect(),
+            Command::DefineInference {
+                premises,
+                conclusion,
+                ..
+            } => premises
+                .iter()
+                .chain(std::iter::once(conclusion))
+                .map(|e| &**e)
+                .collect(),
+            Command::StartProof { goal } => goal.iter().map(|e| &**e).collect(),
+            Command::Minimize { expr, dont_care }
+            | Command::Espresso {
+                expr, dont_care, ..
+            }
+            | Command::KarnaughMap {
+                expr, dont_care, ..
+            } => std::iter::once(expr)
+                .chain(dont_care.iter())
+                .map(|e| &**e)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Parses bare element names up to the end of the input
+    #[allow(clippy::vec_box)]
+    fn parse_elements(lexer: &mut Lexer) -> Result<Vec<Box<String>>, ParserError> {
+        let mut elements = Vec::new();
+        while *lexer.peek_token().kind != TokenKind::End {
+            let token = lexer.next_token();
+            if *token.kind != TokenKind::String {
+                return Err(ParserError::ExpectedElement);
+            }
+            elements.push(token.text.clone());
+        }
+        Ok(elements)
+    }
+
+    // Parses a parenthesised tuple of elements, or a bare element as a 1-tuple
+    #[allow(clippy::vec_box)]
+    fn parse_tuple(lexer: &mut Lexer) -> Result<Vec<Box<String>>, ParserError> {
+        let token = lexer.next_token();
+        match *token.kind {
+            TokenKind::String => Ok(vec![token.text.clone()]),
+            TokenKind::LParen => {
+                let mut elems = Vec::new();
+                loop {
+                    let token = lexer.next_token();
+                    match *token.kind {
+                        TokenKind::RParen => return Ok(elems),
+                        TokenKind::String => elems.push(token.text.clone()),
+                        _ => return Err(ParserError::ExpectedElement),
+                    }
+                }
+            }
+            _ => Err(ParserError::ExpectedElement),
+        }
+    }
+
+    // Parses an optional atom ordering keyword, defaulting to frequency order
+    fn parse_bdd_order(lexer: &mut Lexer) -> BddOrder {
+        let token = lexer.peek_token();
+        match BddOrder::from_str(&token.text) {
+            Some(order) if *token.kind == TokenKind::String => {
+                lexer.next();
+                order
+            }
+            _ => BddOrder::Frequency,
+        }
+    }
+
+    // Parses a formula and an optional don't-care condition after it
+    fn parse_dont_care(lexer: &mut Lexer) -> Result<DontCare, ParserError> {
+        let expr = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+        let dont_care = match *lexer.peek_token().kind {
+            TokenKind::End => None,
+            _ => Some(LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?),
+        };
+        Ok((expr, dont_care))
+    }
+
+    // Parses one or more expressions up to the end of the input
+    #[allow(clippy::vec_box)]
+    fn parse_exprs(lexer: &mut Lexer) -> Result<Vec<Box<LogExpr>>, ParserError> {
+        let mut exprs = Vec::new();
+        while *lexer.peek_token().kind != TokenKind::End {
+            exprs.push(LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?);
+        }
+        if exprs.is_empty() {
+            Err(ParserError::ExpectedExpression)
+        } else {
+            Ok(exprs)
+        }
+    }
+
+    // Parses `premise ... |- goal`, where both sides are optional but not both empty
+    fn parse_sequent(lexer: &mut Lexer) -> Result<Sequent, ParserError> {
+        let mut premises = Vec::new();
+        loop {
+            match *lexer.peek_token().kind {
+                TokenKind::End => break,
+                TokenKind::Turnstile => {
+                    lexer.next();
+                    let goal = LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?;
+                    // The goal is a single formula and ends the sequent
+                    if *lexer.peek_token().kind != TokenKind::End {
+                        return Err(ParserError::UnexpectedToken);
+                    }
+                    return Ok((premises, Some(goal)));
+                }
+                _ => premises.push(LogExpr::parse(lexer).ok_or(ParserError::ExpectedExpression)?),
+            }
+        }
+        if premises.is_empty() {
+            Err(ParserError::ExpectedExpression)
+        } else {
+            Ok((premises, None))
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -128,26 +1094,149 @@ pub enum Operator {
     And,
     Or,
     Imp,
+    // Modal necessity and possibility, unary like `Not`
+    Box,
+    Diamond,
+    // Temporal next, eventually and always are unary; until and release
+    // take exactly two arguments
+    Next,
+    Eventually,
+    Always,
+    Until,
+    Release,
+    // CTL pairs a path quantifier with a temporal operator; the until forms
+    // take exactly two arguments
+    ExistsNext,
+    AllNext,
+    ExistsEventually,
+    AllEventually,
+    ExistsAlways,
+    AllAlways,
+    ExistsUntil,
+    AllUntil,
 }
 
 impl Display for Operator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Operator::Not { .. } => write!(f, "not"),
-            Operator::And { .. } => write!(f, "and"),
-            Operator::Or { .. } => write!(f, "or"),
-            Operator::Imp { .. } => write!(f, "imp"),
+            Operator::Not => write!(f, "not"),
+            Operator::And => write!(f, "and"),
+            Operator::Or => write!(f, "or"),
+            Operator::Imp => write!(f, "imp"),
+            Operator::Box => write!(f, "box"),
+            Operator::Diamond => write!(f, "diamond"),
+            // Spelled out, so the output reads back the same outside
+            // temporal commands
+            Operator::Next => write!(f, "next"),
+            Operator::Eventually => write!(f, "eventually"),
+            Operator::Always => write!(f, "always"),
+            Operator::Until => write!(f, "until"),
+            Operator::Release => write!(f, "release"),
+            Operator::ExistsNext => write!(f, "EX"),
+            Operator::AllNext => write!(f, "AX"),
+            Operator::ExistsEventually => write!(f, "EF"),
+            Operator::AllEventually => write!(f, "AF"),
+            Operator::ExistsAlways => write!(f, "EG"),
+            Operator::AllAlways => write!(f, "AG"),
+            Operator::ExistsUntil => write!(f, "EU"),
+            Operator::AllUntil => write!(f, "AU"),
         }
     }
 }
 
 impl Operator {
-    fn from_str(s: &str) -> Option<Operator> {
+    // The one-letter temporal operators would take over predicates named X,
+    // F, G, U or R, so they are only read where `temporal` is set
+    fn from_str(s: &str, temporal: bool) -> Option<Operator> {
         match s {
             "not" | "~" | "N" | "[-]" | "!" => Some(Operator::Not),
             "and" | "&" | "K" | "[*]" | "/\\" => Some(Operator::And),
             "or" | "||" | "A" | "[+}" | "\\/" => Some(Operator::Or),
             "imp" | "=>" | "C" => Some(Operator::Imp),
+            "box" | "[]" | "L" => Some(Operator::Box),
+            "diamond" | "<>" | "M" => Some(Operator::Diamond),
+            "next" => Some(Operator::Next),
+            "eventually" => Some(Operator::Eventually),
+            "always" => Some(Operator::Always),
+            "until" => Some(Operator::Until),
+            "release" => Some(Operator::Release),
+            "X" if temporal => Some(Operator::Next),
+            "F" if temporal => Some(Operator::Eventually),
+            "G" if temporal => Some(Operator::Always),
+            "U" if temporal => Some(Operator::Until),
+            "R" if temporal => Some(Operator::Release),
+            "EX" => Some(Operator::ExistsNext),
+            "AX" => Some(Operator::AllNext),
+            "EF" => Some(Operator::ExistsEventually),
+            "AF" => Some(Operator::AllEventually),
+            "EG" => Some(Operator::ExistsAlways),
+            "AG" => Some(Operator::AllAlways),
+            "EU" => Some(Operator::ExistsUntil),
+            "AU" => Some(Operator::AllUntil),
+            _ => None,
+        }
+    }
+
+    pub fn is_unary(&self) -> bool {
+        matches!(
+            self,
+            Operator::Not
+                | Operator::Box
+                | Operator::Diamond
+                | Operator::Next
+                | Operator::Eventually
+                | Operator::Always
+                | Operator::ExistsNext
+                | Operator::AllNext
+                | Operator::ExistsEventually
+                | Operator::AllEventually
+                | Operator::ExistsAlways
+                | Operator::AllAlways
+        )
+    }
+
+    /// The operator that, applied to negated arguments, gives the negation
+    pub fn dual(&self) -> Operator {
+        match self {
+            Operator::And => Operator::Or,
+            Operator::Or => Operator::And,
+            Operator::Box => Operator::Diamond,
+            Operator::Diamond => Operator::Box,
+            Operator::Eventually => Operator::Always,
+            Operator::Always => Operator::Eventually,
+            Operator::Until => Operator::Release,
+            Operator::Release => Operator::Until,
+            Operator::ExistsNext => Operator::AllNext,
+            Operator::AllNext => Operator::ExistsNext,
+            Operator::ExistsEventually => Operator::AllAlways,
+            Operator::AllAlways => Operator::ExistsEventually,
+            Operator::AllEventually => Operator::ExistsAlways,
+            Operator::ExistsAlways => Operator::AllEventually,
+            op => op.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Quantifier {
+    Forall,
+    Exists,
+}
+
+impl Display for Quantifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Quantifier::Forall => write!(f, "forall"),
+            Quantifier::Exists => write!(f, "exists"),
+        }
+    }
+}
+
+impl Quantifier {
+    fn from_str(s: &str) -> Option<Quantifier> {
+        match s {
+            "forall" | "all" | "∀" => Some(Quantifier::Forall),
+            "exists" | "some" | "∃" => Some(Quantifier::Exists),
             _ => None,
         }
     }
@@ -159,52 +1248,129 @@ pub struct Rule {
     pub rhs: Box<LogExpr>,
 }
 
+/// A schematic inference rule deriving the conclusion from instances of the premises
+#[derive(Debug, Clone)]
+#[allow(clippy::vec_box)]
+pub struct Inference {
+    pub premises: Vec<Box<LogExpr>>,
+    pub conclusion: Box<LogExpr>,
+}
+
 #[derive(Debug)]
+#[allow(clippy::box_collection)]
 pub struct RuleSet {
     pub rules: HashMap<Box<String>, Box<Rule>>,
+    pub axioms: HashMap<Box<String>, Box<LogExpr>>,
+    pub inferences: HashMap<Box<String>, Box<Inference>>,
 }
 
 impl Display for RuleSet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut rules_iter = self.rules.iter();
         if let Some((name, rule)) = rules_iter.next() {
-            write!(f, "\t\t{}: {} => {}\n", name, rule.lhs, rule.rhs)?;
+            writeln!(f, "\t\t{}: {} => {}", name, rule.lhs, rule.rhs)?;
             for (name, rule) in rules_iter {
-                write!(f, "\t\t{}: {} => {}\n", name, rule.lhs, rule.rhs)?;
+                writeln!(f, "\t\t{}: {} => {}", name, rule.lhs, rule.rhs)?;
             }
         }
+        for (name, schema) in &self.axioms {
+            writeln!(f, "\t\t{}: |- {}", name, schema)?;
+        }
+        for (name, inference) in &self.inferences {
+            let premises = inference
+                .premises
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>();
+            writeln!(
+                f,
+                "\t\t{}: {} |- {}",
+                name,
+                premises.join(" "),
+                inference.conclusion
+            )?;
+        }
         Ok(())
     }
 }
 
+#[allow(clippy::box_collection)]
 impl RuleSet {
     pub fn new() -> RuleSet {
         RuleSet {
             rules: HashMap::new(),
+            axioms: HashMap::new(),
+            inferences: HashMap::new(),
         }
     }
 
+    // Rules, axioms and inference rules share one namespace, so defining a
+    // name replaces whatever it named before
+    pub fn add_axiom(&mut self, name: Box<String>, schema: Box<LogExpr>) {
+        self.delete_rule(name.clone());
+        self.axioms.insert(name, schema);
+    }
+
+    #[allow(clippy::vec_box)]
+    pub fn add_inference(
+        &mut self,
+        name: Box<String>,
+        premises: Vec<Box<LogExpr>>,
+        conclusion: Box<LogExpr>,
+    ) {
+        self.delete_rule(name.clone());
+        self.inferences.insert(
+            name,
+            Box::new(Inference {
+                premises,
+                conclusion,
+            }),
+        );
+    }
+
     pub fn add_rule(&mut self, name: Box<String>, lhs: Box<LogExpr>, rhs: Box<LogExpr>) {
+        self.delete_rule(name.clone());
         self.rules.insert(name, Box::new(Rule { lhs, rhs }));
     }
 
-    pub fn get_rule(&self, name: Box<String>) -> Option<&Box<Rule>> {
-        self.rules.get(&name)
+    pub fn get_rule(&self, name: Box<String>) -> Option<&Rule> {
+        self.rules.get(&name).map(|rule| rule.as_ref())
     }
 
     pub fn delete_rule(&mut self, name: Box<String>) -> Option<Box<Rule>> {
+        // Axioms and inference rules share the rule namespace
+        self.axioms.remove(&name);
+        self.inferences.remove(&name);
         self.rules.remove(&name)
     }
 }
 
+/// Premises and an optional goal, as written `premise ... |- goal`
+pub type Sequent = (Vec<Box<LogExpr>>, Option<Box<LogExpr>>);
+
+/// Metavariable bindings produced by matching a rule against an expression
+pub type Bindings = HashMap<Box<String>, Box<LogExpr>>;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[allow(clippy::vec_box)]
 pub enum LogExpr {
     Atom(Box<Token>),
+    // Schematic metavariable of rules and axioms, never an object variable
     Var(Box<Token>),
     UnaryOp(Box<Operator>, Box<LogExpr>),
     BinaryOp(Box<Operator>, Vec<Box<LogExpr>>),
     True,
     False,
+    // The gap and the glut of many-valued semantics; classical evaluation
+    // rejects them
+    Unknown,
+    Both,
+    // First-order formulas: a binder over an object variable, and a predicate
+    // applied to terms
+    Quant(Box<Quantifier>, Box<Token>, Box<LogExpr>),
+    Pred(Box<Token>, Vec<Box<LogExpr>>),
+    // Function term; constants and object variables are `Atom`s in term position
+    Func(Box<Token>, Vec<Box<LogExpr>>),
 }
 
 impl Display for LogExpr {
@@ -214,16 +1380,25 @@ impl Display for LogExpr {
             LogExpr::Var(token) => write!(f, "{}", token.text),
             LogExpr::UnaryOp(op, expr) => write!(f, "({} {})", op, expr),
             LogExpr::BinaryOp(op, exprs) => {
-                let mut exprs_iter = exprs.iter();
-                let first_expr = exprs_iter.next().unwrap();
-                write!(f, "({} {}", op, first_expr)?;
-                for expr in exprs_iter {
+                // An empty connective prints as `(and)`, like it was written
+                write!(f, "({}", op)?;
+                for expr in exprs {
                     write!(f, " {}", expr)?;
                 }
                 write!(f, ")")
             }
             LogExpr::True => write!(f, "true"),
+            LogExpr::Unknown => write!(f, "unknown"),
+            LogExpr::Both => write!(f, "both"),
             LogExpr::False => write!(f, "false"),
+            LogExpr::Quant(q, var, body) => write!(f, "({} {} {})", q, var.text, body),
+            LogExpr::Pred(name, args) | LogExpr::Func(name, args) => {
+                write!(f, "({}", name.text)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
@@ -237,14 +1412,32 @@ impl LogExpr {
         matches!(s.to_lowercase().as_str(), "0" | "f" | "false")
     }
 
+    fn string_unknown(s: &str) -> bool {
+        matches!(s.to_lowercase().as_str(), "unknown" | "neither")
+    }
+
+    fn string_both(s: &str) -> bool {
+        s.to_lowercase() == "both"
+    }
+
     pub fn parse(lexer: &mut Lexer) -> Option<Box<LogExpr>> {
         let token = lexer.next_token();
         match *token.kind {
             TokenKind::LParen => {
                 let next_token = lexer.next_token();
                 match *next_token.kind {
+                    TokenKind::String if Quantifier::from_str(&next_token.text).is_some() => {
+                        let q = Quantifier::from_str(&next_token.text)?;
+                        LogExpr::parse_quantified(lexer, q)
+                    }
+                    TokenKind::String
+                        if Operator::from_str(&next_token.text, lexer.temporal).is_none() =>
+                    {
+                        let args = LogExpr::parse_terms(lexer)?;
+                        Some(Box::new(LogExpr::Pred(next_token, args)))
+                    }
                     TokenKind::String => {
-                        let op = Operator::from_str(next_token.text.as_str())?;
+                        let op = Operator::from_str(next_token.text.as_str(), lexer.temporal)?;
                         let mut args: Vec<Box<LogExpr>> = Vec::new();
                         while *lexer.peek_token().kind != TokenKind::RParen {
                             if let Some(arg) = LogExpr::parse(lexer) {
@@ -257,49 +1450,393 @@ impl LogExpr {
                         lexer.next();
 
                         match op {
-                            Operator::Not { .. } => {
+                            _ if op.is_unary() => {
                                 if args.len() == 1 {
                                     Some(Box::new(LogExpr::UnaryOp(Box::new(op), args.pop()?)))
                                 } else {
-                                    None // Return None if "not" has an invalid number of arguments
+                                    None // Return None if a unary operator has an invalid number of arguments
                                 }
                             }
+                            Operator::Until
+                            | Operator::Release
+                            | Operator::ExistsUntil
+                            | Operator::AllUntil
+                                if args.len() != 2 =>
+                            {
+                                None
+                            }
                             _ => Some(Box::new(LogExpr::BinaryOp(Box::new(op), args))),
                         }
                     }
                     _ => None,
                 }
             }
+            TokenKind::String => LogExpr::from_token(token),
+            _ => None, // Return None for any other unexpected token
+        }
+    }
+
+    // A constant, atom or metavariable written as a single name
+    fn from_token(token: Box<Token>) -> Option<Box<LogExpr>> {
+        let text = token.text.as_str();
+        if LogExpr::string_false(text) {
+            Some(Box::new(LogExpr::False))
+        } else if LogExpr::string_true(text) {
+            Some(Box::new(LogExpr::True))
+        } else if LogExpr::string_unknown(text) {
+            Some(Box::new(LogExpr::Unknown))
+        } else if LogExpr::string_both(text) {
+            Some(Box::new(LogExpr::Both))
+        } else {
+            let first_char = text.chars().next()?;
+            if first_char.is_lowercase() || first_char.is_ascii_digit() {
+                Some(Box::new(LogExpr::Atom(token)))
+            } else if first_char.is_uppercase() {
+                Some(Box::new(LogExpr::Var(token)))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// A token for a generated symbol, which has no place in the input
+    pub fn token(name: &str) -> Box<Token> {
+        Box::new(Token {
+            kind: Box::new(TokenKind::String),
+            text: Box::new(name.to_string()),
+            loc: Box::new(Loc::ReplLoc { col: Box::new(0) }),
+        })
+    }
+
+    /// An atom built outside the lexer, e.g. for ground or generated symbols
+    pub fn atom(name: &str) -> Box<LogExpr> {
+        Box::new(LogExpr::Atom(LogExpr::token(name)))
+    }
+
+    /// Wraps the expression in a negation
+    pub fn negate(&self) -> Box<LogExpr> {
+        Box::new(LogExpr::UnaryOp(
+            Box::new(Operator::Not),
+            Box::new(self.clone()),
+        ))
+    }
+
+    /// Splits the arguments of an `imp` into antecedent and consequent,
+    /// reading `(imp a b c)` as `(imp a (imp b c))`
+    pub fn imp_parts(exprs: &[Box<LogExpr>]) -> Option<(Box<LogExpr>, Box<LogExpr>)> {
+        match exprs {
+            [] | [_] => None,
+            [lhs, rhs] => Some((lhs.clone(), rhs.clone())),
+            [lhs, rest @ ..] => Some((
+                lhs.clone(),
+                Box::new(LogExpr::BinaryOp(Box::new(Operator::Imp), rest.to_vec())),
+            )),
+        }
+    }
+
+    // Parses `x ... body)` after a quantifier, nesting one binder per variable
+    fn parse_quantified(lexer: &mut Lexer, q: Quantifier) -> Option<Box<LogExpr>> {
+        // Binders are read as raw names, so that even t or f can be bound
+        let mut vars = Vec::new();
+        while *lexer.peek_token().kind == TokenKind::String {
+            vars.push(lexer.next_token());
+        }
+        let mut expr = match *lexer.peek_token().kind {
+            // A body that is a single name was read as the last binder. It may
+            // only follow one binder, so that `(forall x p q)` is not taken
+            // for two binders and the body `q`
+            TokenKind::RParen if vars.len() == 2 => LogExpr::from_token(vars.pop()?)?,
+            TokenKind::RParen => return None,
+            _ => LogExpr::parse(lexer)?,
+        };
+        // Skip closing RParen
+        if vars.is_empty() || *lexer.next_token().kind != TokenKind::RParen {
+            return None;
+        }
+        for var in vars.into_iter().rev() {
+            expr = Box::new(LogExpr::Quant(Box::new(q.clone()), var, expr));
+        }
+        Some(expr)
+    }
+
+    // Parses terms up to and including the closing RParen of an application
+    #[allow(clippy::vec_box)]
+    fn parse_terms(lexer: &mut Lexer) -> Option<Vec<Box<LogExpr>>> {
+        let mut args = Vec::new();
+        while *lexer.peek_token().kind != TokenKind::RParen {
+            args.push(LogExpr::parse_term(lexer)?);
+        }
+        // Skip closing RParen
+        lexer.next();
+        Some(args)
+    }
+
+    pub fn parse_term(lexer: &mut Lexer) -> Option<Box<LogExpr>> {
+        let token = lexer.next_token();
+        match *token.kind {
+            TokenKind::LParen => {
+                let name = lexer.next_token();
+                if *name.kind != TokenKind::String {
+                    return None;
+                }
+                let args = LogExpr::parse_terms(lexer)?;
+                Some(Box::new(LogExpr::Func(name, args)))
+            }
             TokenKind::String => {
-                let text = token.text.as_str();
-                if LogExpr::string_false(text) {
-                    Some(Box::new(LogExpr::False))
-                } else if LogExpr::string_true(text) {
-                    Some(Box::new(LogExpr::True))
+                let first_char = token.text.chars().next()?;
+                if first_char.is_uppercase() {
+                    Some(Box::new(LogExpr::Var(token)))
                 } else {
-                    let first_char = text.chars().next()?;
-                    if first_char.is_lowercase() || first_char.is_digit(10) {
-                        Some(Box::new(LogExpr::Atom(token)))
-                    } else if first_char.is_uppercase() {
-                        Some(Box::new(LogExpr::Var(token)))
-                    } else {
-                        None
+                    Some(Box::new(LogExpr::Atom(token)))
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the expression uses quantifiers, predicates or function terms
+    pub fn is_first_order(&self) -> bool {
+        match self {
+            LogExpr::Quant(..) | LogExpr::Pred(..) | LogExpr::Func(..) => true,
+            LogExpr::UnaryOp(_, inner) => inner.is_first_order(),
+            LogExpr::BinaryOp(_, exprs) => exprs.iter().any(|e| e.is_first_order()),
+            _ => false,
+        }
+    }
+
+    /// The first subformula built with a modal or temporal operator, which
+    /// the classical and first-order engines cannot take apart
+    pub fn modal_part(&self) -> Option<&LogExpr> {
+        match self {
+            LogExpr::UnaryOp(op, inner) if **op == Operator::Not => inner.modal_part(),
+            LogExpr::BinaryOp(op, exprs)
+                if matches!(**op, Operator::And | Operator::Or | Operator::Imp) =>
+            {
+                exprs.iter().find_map(|e| e.modal_part())
+            }
+            LogExpr::UnaryOp(..) | LogExpr::BinaryOp(..) => Some(self),
+            LogExpr::Quant(_, _, body) => body.modal_part(),
+            _ => None,
+        }
+    }
+
+    /// The first `unknown` or `both`, which only many-valued semantics give
+    /// a meaning to
+    pub fn many_valued_part(&self) -> Option<&LogExpr> {
+        match self {
+            LogExpr::Unknown | LogExpr::Both => Some(self),
+            LogExpr::UnaryOp(_, inner) | LogExpr::Quant(_, _, inner) => inner.many_valued_part(),
+            LogExpr::BinaryOp(_, exprs) => exprs.iter().find_map(|e| e.many_valued_part()),
+            _ => None,
+        }
+    }
+
+    /// Whether `name` occurs free as an object variable
+    pub fn occurs_free(&self, name: &str) -> bool {
+        match self {
+            LogExpr::Atom(token) => *token.text == name,
+            LogExpr::UnaryOp(_, expr) => expr.occurs_free(name),
+            LogExpr::BinaryOp(_, exprs) | LogExpr::Pred(_, exprs) | LogExpr::Func(_, exprs) => {
+                exprs.iter().any(|e| e.occurs_free(name))
+            }
+            LogExpr::Quant(_, var, body) => *var.text != name && body.occurs_free(name),
+            _ => false,
+        }
+    }
+
+    /// Object variables are written `u` to `z`, optionally followed by digits
+    /// or primes; other names in term position are constants unless bound
+    pub fn is_variable_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        matches!(chars.next(), Some('u'..='z')) && chars.all(|c| c.is_ascii_digit() || c == '\'')
+    }
+
+    fn collect_free(&self, bound: &mut Vec<String>, out: &mut Vec<String>) {
+        match self {
+            LogExpr::UnaryOp(_, expr) => expr.collect_free(bound, out),
+            LogExpr::BinaryOp(_, exprs) => exprs.iter().for_each(|e| e.collect_free(bound, out)),
+            LogExpr::Pred(_, args) | LogExpr::Func(_, args) => {
+                for arg in args {
+                    match &**arg {
+                        LogExpr::Atom(token)
+                            if LogExpr::is_variable_name(&token.text)
+                                && !bound.contains(&token.text)
+                                && !out.contains(&token.text) =>
+                        {
+                            out.push(token.text.to_string())
+                        }
+                        _ => arg.collect_free(bound, out),
                     }
                 }
             }
-            _ => None, // Return None for any other unexpected token
+            LogExpr::Quant(_, var, body) => {
+                bound.push(var.text.to_string());
+                body.collect_free(bound, out);
+                bound.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// The object variables with a free occurrence, in order of appearance
+    pub fn free_variables(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        self.collect_free(&mut Vec::new(), &mut out);
+        out
+    }
+
+    /// The variables bound by some quantifier, in order of appearance
+    pub fn bound_variables(&self) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        let mut stack = vec![self];
+        while let Some(expr) = stack.pop() {
+            match expr {
+                LogExpr::Quant(_, var, body) => {
+                    if !out.contains(&var.text) {
+                        out.push(var.text.to_string());
+                    }
+                    stack.push(body);
+                }
+                LogExpr::UnaryOp(_, inner) => stack.push(inner),
+                LogExpr::BinaryOp(_, exprs) => stack.extend(exprs.iter().rev().map(|e| &**e)),
+                _ => {}
+            }
+        }
+        out
+    }
+
+    /// The schematic metavariables, which only rules and axioms may contain
+    pub fn metavariables(&self) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        let mut stack = vec![self];
+        while let Some(expr) = stack.pop() {
+            match expr {
+                LogExpr::Var(token) if !out.contains(&token.text) => {
+                    out.push(token.text.to_string())
+                }
+                LogExpr::Quant(_, _, inner) | LogExpr::UnaryOp(_, inner) => stack.push(inner),
+                LogExpr::BinaryOp(_, exprs) | LogExpr::Pred(_, exprs) | LogExpr::Func(_, exprs) => {
+                    stack.extend(exprs.iter().rev().map(|e| &**e))
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.free_variables().is_empty()
+    }
+
+    /// Binds every free variable universally, the first one outermost
+    pub fn universal_closure(&self) -> Box<LogExpr> {
+        self.free_variables()
+            .iter()
+            .rev()
+            .fold(Box::new(self.clone()), |body, var| {
+                Box::new(LogExpr::Quant(
+                    Box::new(Quantifier::Forall),
+                    LogExpr::token(var),
+                    body,
+                ))
+            })
+    }
+
+    /// Every symbol name used anywhere in the expression, bound or not
+    pub fn symbols(&self, out: &mut HashSet<String>) {
+        match self {
+            LogExpr::Atom(token) | LogExpr::Var(token) => {
+                out.insert(token.text.to_string());
+            }
+            LogExpr::UnaryOp(_, expr) => expr.symbols(out),
+            LogExpr::BinaryOp(_, exprs) => exprs.iter().for_each(|e| e.symbols(out)),
+            LogExpr::Pred(name, args) | LogExpr::Func(name, args) => {
+                out.insert(name.text.to_string());
+                args.iter().for_each(|e| e.symbols(out));
+            }
+            LogExpr::Quant(_, var, body) => {
+                out.insert(var.text.to_string());
+                body.symbols(out);
+            }
+            LogExpr::True | LogExpr::False | LogExpr::Unknown | LogExpr::Both => {}
+        }
+    }
+
+    /// Primes `base` until it clashes with no symbol of the given expressions
+    pub fn fresh_name(base: &str, avoid: &[&LogExpr]) -> String {
+        let mut used = HashSet::new();
+        for expr in avoid {
+            expr.symbols(&mut used);
+        }
+        let mut name = base.to_string();
+        while used.contains(&name) {
+            name.push('\'');
+        }
+        name
+    }
+
+    // A copy of `token` carrying a different name
+    pub fn renamed(token: &Token, name: &str) -> Box<Token> {
+        Box::new(Token {
+            kind: token.kind.clone(),
+            text: Box::new(name.to_string()),
+            loc: token.loc.clone(),
+        })
+    }
+
+    /// Replaces the free occurrences of object variable `name` by `term`,
+    /// renaming binders that would capture a variable of `term`
+    pub fn substitute_term(&self, name: &str, term: &LogExpr) -> Box<LogExpr> {
+        match self {
+            LogExpr::Atom(token) if *token.text == name => Box::new(term.clone()),
+            LogExpr::UnaryOp(op, expr) => Box::new(LogExpr::UnaryOp(
+                op.clone(),
+                expr.substitute_term(name, term),
+            )),
+            LogExpr::BinaryOp(op, exprs) => Box::new(LogExpr::BinaryOp(
+                op.clone(),
+                exprs
+                    .iter()
+                    .map(|e| e.substitute_term(name, term))
+                    .collect(),
+            )),
+            LogExpr::Pred(pred, args) => Box::new(LogExpr::Pred(
+                pred.clone(),
+                args.iter().map(|e| e.substitute_term(name, term)).collect(),
+            )),
+            LogExpr::Func(func, args) => Box::new(LogExpr::Func(
+                func.clone(),
+                args.iter().map(|e| e.substitute_term(name, term)).collect(),
+            )),
+            LogExpr::Quant(_, var, _) if *var.text == name => Box::new(self.clone()),
+            LogExpr::Quant(q, var, body)
+                if term.occurs_free(&var.text) && body.occurs_free(name) =>
+            {
+                let fresh = LogExpr::fresh_name(&var.text, &[body, term]);
+                let fresh_var = LogExpr::renamed(var, &fresh);
+                let body = body.substitute_term(&var.text, &LogExpr::Atom(fresh_var.clone()));
+                Box::new(LogExpr::Quant(
+                    q.clone(),
+                    fresh_var,
+                    body.substitute_term(name, term),
+                ))
+            }
+            LogExpr::Quant(q, var, body) => Box::new(LogExpr::Quant(
+                q.clone(),
+                var.clone(),
+                body.substitute_term(name, term),
+            )),
+            _ => Box::new(self.clone()),
         }
     }
 
-    pub fn match_with(
-        &self,
-        other: &LogExpr,
-        bindings: &mut HashMap<Box<String>, Box<LogExpr>>,
-    ) -> bool {
+    pub fn match_with(&self, other: &LogExpr, bindings: &mut Bindings) -> bool {
         match (self, other) {
             // Atoms should directly match
             (LogExpr::Atom(token1), LogExpr::Atom(token2)) => token1 == token2,
 
+            (LogExpr::True, LogExpr::True) | (LogExpr::False, LogExpr::False) => true,
+
             // Variables can bind to expressions
             (LogExpr::Var(var_token), expr) => {
                 let var_name = var_token.text.clone();
@@ -317,14 +1854,48 @@ impl LogExpr {
             }
 
             // Binary operations must match their operation type and subexpressions
-            (LogExpr::BinaryOp(op1, exprs1), LogExpr::BinaryOp(op2, exprs2)) => {
-                if op1 == op2 && exprs1.len() == exprs2.len() {
-                    exprs1
-                        .iter()
-                        .zip(exprs2.iter())
-                        .all(|(e1, e2)| e1.match_with(e2, bindings))
+            (LogExpr::BinaryOp(op1, exprs1), LogExpr::BinaryOp(op2, exprs2))
+                if op1 == op2 && exprs1.len() == exprs2.len() =>
+            {
+                exprs1
+                    .iter()
+                    .zip(exprs2.iter())
+                    .all(|(e1, e2)| e1.match_with(e2, bindings))
+            }
+
+            // Predicates and function terms must agree on symbol and arity
+            (LogExpr::Pred(name1, args1), LogExpr::Pred(name2, args2))
+            | (LogExpr::Func(name1, args1), LogExpr::Func(name2, args2))
+                if name1 == name2 && args1.len() == args2.len() =>
+            {
+                args1
+                    .iter()
+                    .zip(args2.iter())
+                    .all(|(e1, e2)| e1.match_with(e2, bindings))
+            }
+
+            // A metavariable binder binds to the other side's object variable
+            (LogExpr::Quant(q1, var1, body1), LogExpr::Quant(q2, var2, body2))
+                if q1 == q2 && var1.text.starts_with(char::is_uppercase) =>
+            {
+                let var = LogExpr::Var(var1.clone());
+                var.match_with(&LogExpr::Atom(var2.clone()), bindings)
+                    && body1.match_with(body2, bindings)
+            }
+
+            // Otherwise binders match up to renaming of the bound variable
+            (LogExpr::Quant(q1, var1, body1), LogExpr::Quant(q2, var2, body2)) if q1 == q2 => {
+                if var1.text == var2.text {
+                    body1.match_with(body2, bindings)
+                } else if !body2.occurs_free(&var1.text) {
+                    let renamed = body2.substitute_term(&var2.text, &LogExpr::Atom(var1.clone()));
+                    body1.match_with(&renamed, bindings)
                 } else {
-                    false
+                    let fresh = LogExpr::fresh_name(&var1.text, &[body1, body2]);
+                    let fresh = LogExpr::Atom(LogExpr::renamed(var1, &fresh));
+                    body1
+                        .substitute_term(&var1.text, &fresh)
+                        .match_with(&body2.substitute_term(&var2.text, &fresh), bindings)
                 }
             }
 
@@ -333,11 +1904,7 @@ impl LogExpr {
         }
     }
 
-    pub fn apply_rule(
-        &self,
-        rule_set: &RuleSet,
-        rule_name: &str,
-    ) -> Vec<(Box<LogExpr>, HashMap<Box<String>, Box<LogExpr>>)> {
+    pub fn apply_rule(&self, rule_set: &RuleSet, rule_name: &str) -> Vec<(Box<LogExpr>, Bindings)> {
         let mut results = Vec::new();
         let mut all_bindings = HashMap::new();
         let mut unique_results = HashSet::new(); // Set to ensure uniqueness
@@ -366,6 +1933,16 @@ impl LogExpr {
                         all_bindings.extend(sub_bindings);
                     }
                 }
+                LogExpr::Quant(q, var, body) => {
+                    let sub_results = body.apply_rule(rule_set, rule_name);
+                    for (sub_expr, sub_bindings) in sub_results {
+                        let new_expr = Box::new(LogExpr::Quant(q.clone(), var.clone(), sub_expr));
+                        if unique_results.insert(new_expr.clone()) {
+                            results.push((new_expr, sub_bindings.clone()));
+                        }
+                        all_bindings.extend(sub_bindings);
+                    }
+                }
                 LogExpr::BinaryOp(op, exprs) => {
                     for (i, expr) in exprs.iter().enumerate() {
                         let other_exprs = exprs
@@ -418,7 +1995,7 @@ impl LogExpr {
         results
     }
 
-    fn substitute(&self, bindings: &HashMap<Box<String>, Box<LogExpr>>) -> Box<LogExpr> {
+    fn substitute(&self, bindings: &Bindings) -> Box<LogExpr> {
         match self {
             LogExpr::Atom(token) => {
                 if let Some(subst) = bindings.get(&token.text) {
@@ -441,7 +2018,175 @@ impl LogExpr {
                 op.clone(),
                 exprs.iter().map(|e| e.substitute(bindings)).collect(),
             )),
+            LogExpr::Pred(name, args) => Box::new(LogExpr::Pred(
+                name.clone(),
+                args.iter().map(|e| e.substitute(bindings)).collect(),
+            )),
+            LogExpr::Func(name, args) => Box::new(LogExpr::Func(
+                name.clone(),
+                args.iter().map(|e| e.substitute(bindings)).collect(),
+            )),
+            LogExpr::Quant(q, var, body) => {
+                // A metavariable binder takes the name of the variable it matched
+                let var = match bindings.get(&var.text).map(|e| &**e) {
+                    Some(LogExpr::Atom(bound)) | Some(LogExpr::Var(bound)) => bound.clone(),
+                    _ => var.clone(),
+                };
+                Box::new(LogExpr::Quant(q.clone(), var, body.substitute(bindings)))
+            }
             _ => Box::new(self.clone()),
         }
     }
 }
+
+/// A formula and the condition under which its value does not matter
+pub type DontCare = (Box<LogExpr>, Option<Box<LogExpr>>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn command(s: &str) -> Result<Box<Command>, ParserError> {
+        Command::parse(&mut Lexer::new(s, None))
+    }
+
+    #[test]
+    fn display_reads_back() {
+        for s in ["(imp (and p q) (or (not r) s))", "(and)", "(or)", "(and p)"] {
+            assert_eq!(expr(s).to_string(), s);
+            assert_eq!(expr(&expr(s).to_string()), expr(s));
+        }
+    }
+
+    #[test]
+    fn aliases_parse_as_operators() {
+        assert_eq!(expr("(C (K p q) (N p))"), expr("(imp (and p q) (not p))"));
+        assert_eq!(expr("(=> t f)").to_string(), "(imp true false)");
+    }
+
+    #[test]
+    fn tableau_takes_one_or_more_formulas() {
+        match *command(":tableau p (not p)").unwrap() {
+            Command::Tableau { exprs } => assert_eq!(exprs.len(), 2),
+            other => panic!("parsed as {:?}", other),
+        }
+        assert!(command(":tableau").is_err());
+        assert!(command(":tableau (and p").is_err());
+    }
+
+    #[test]
+    fn sequents_end_at_the_goal() {
+        match *command(":resolve p (imp p q) |- q").unwrap() {
+            Command::Resolve { premises, goal } => {
+                assert_eq!(premises.len(), 2);
+                assert_eq!(goal, Some(expr("q")));
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+        assert!(command(":resolve p |- q r").is_err());
+        assert!(command(":resolve |-").is_err());
+    }
+
+    #[test]
+    fn quantifiers_bind_each_name_in_turn() {
+        assert_eq!(
+            expr("(forall x y (P x y))"),
+            expr("(forall x (forall y (P x y)))")
+        );
+        match *expr("(exists x (P (f x) a))") {
+            LogExpr::Quant(q, var, body) => {
+                assert_eq!(*q, Quantifier::Exists);
+                assert_eq!(*var.text, "x");
+                assert!(
+                    matches!(*body, LogExpr::Pred(_, ref args) if matches!(*args[0], LogExpr::Func(..)))
+                );
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+        assert!(expr("(forall x q)").is_first_order());
+        assert!(!expr("(and p q)").is_first_order());
+    }
+
+    #[test]
+    fn constant_names_can_be_bound() {
+        match *expr("(forall t (Lt t t))") {
+            LogExpr::Quant(_, var, body) => {
+                assert_eq!(*var.text, "t");
+                assert_eq!(body.to_string(), "(Lt t t)");
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+        assert_eq!(
+            *expr("(forall x t)"),
+            LogExpr::Quant(
+                Box::new(Quantifier::Forall),
+                LogExpr::token("x"),
+                Box::new(LogExpr::True)
+            )
+        );
+    }
+
+    #[test]
+    fn malformed_quantifiers_are_rejected() {
+        let parse = |s: &str| LogExpr::parse(&mut Lexer::new(s, None));
+        assert!(parse("(forall x)").is_none());
+        assert!(parse("(forall (P x) q)").is_none());
+        assert!(parse("(exists x (P x) (P x))").is_none());
+        assert!(parse("(forall x p q)").is_none());
+        assert!(parse("(forall x (P x) q)").is_none());
+    }
+
+    #[test]
+    fn free_and_bound_variables_are_told_apart() {
+        let formula = expr("(and (forall x (R x y)) (exists z (P (f z) x c)))");
+        assert_eq!(formula.free_variables(), ["y", "x"]);
+        assert_eq!(formula.bound_variables(), ["x", "z"]);
+        assert!(formula.occurs_free("y"));
+        assert!(!formula.occurs_free("z"));
+        assert!(!formula.is_closed());
+        assert!(expr("(forall y (P y c))").is_closed());
+    }
+
+    #[test]
+    fn metavariables_are_not_object_variables() {
+        let formula = expr("(imp A (P x B))");
+        assert_eq!(formula.metavariables(), ["A", "B"]);
+        assert_eq!(formula.free_variables(), ["x"]);
+    }
+
+    #[test]
+    fn closure_binds_the_first_free_variable_outermost() {
+        assert_eq!(
+            expr("(imp (R x y) (R y x))")
+                .universal_closure()
+                .to_string(),
+            "(forall x (forall y (imp (R x y) (R y x))))"
+        );
+        let closed = expr("(exists x (P x))");
+        assert_eq!(closed.universal_closure(), closed);
+    }
+
+    #[test]
+    fn temporal_letters_are_operators_only_in_temporal_commands() {
+        assert!(matches!(*expr("(G a)"), LogExpr::Pred(..)));
+        assert!(matches!(*expr("(U a b)"), LogExpr::Pred(..)));
+        assert_eq!(expr("(always a)").to_string(), "(always a)");
+        let mut lexer = Lexer::new("(G (X a))", None);
+        lexer.temporal = true;
+        assert_eq!(
+            LogExpr::parse(&mut lexer).unwrap().to_string(),
+            "(always (next a))"
+        );
+        let command = Command::parse(&mut Lexer::new(":trace run.txt (F (U a b))", None));
+        match *command.unwrap() {
+            Command::CheckTrace { expr, .. } => {
+                assert_eq!(expr.to_string(), "(eventually (until a b))")
+            }
+            other => panic!("parsed as {:?}", other),
+        }
+    }
+}
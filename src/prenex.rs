@@ -0,0 +1,294 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::lexer::*;
+use crate::parser::*;
+use crate::resolution::*;
+
+#[derive(Debug, Clone)]
+pub enum PrenexError {
+    Modal(Box<LogExpr>),
+}
+
+impl Display for PrenexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrenexError::Modal(expr) => {
+                write!(
+                    f,
+                    "{} is modal or temporal, beyond first-order normal forms",
+                    expr
+                )
+            }
+        }
+    }
+}
+
+/// A quantifier prefix, outermost first
+pub type Prefix = Vec<(Quantifier, Box<Token>)>;
+
+fn fresh(base: &str, used: &mut HashSet<String>) -> String {
+    let mut name = base.to_string();
+    while used.contains(&name) {
+        name.push('\'');
+    }
+    used.insert(name.clone());
+    name
+}
+
+// Gives every binder a name of its own, so quantifiers can be pulled out
+// without capturing anything
+fn rename_apart(
+    expr: &LogExpr,
+    used: &mut HashSet<String>,
+    seen: &mut HashSet<String>,
+) -> Box<LogExpr> {
+    match expr {
+        LogExpr::Quant(q, var, body) => {
+            if seen.insert(var.text.to_string()) {
+                let body = rename_apart(body, used, seen);
+                return Box::new(LogExpr::Quant(q.clone(), var.clone(), body));
+            }
+            let name = fresh(&var.text, used);
+            seen.insert(name.clone());
+            let renamed = LogExpr::renamed(var, &name);
+            let body = body.substitute_term(&var.text, &LogExpr::Atom(renamed.clone()));
+            Box::new(LogExpr::Quant(
+                q.clone(),
+                renamed,
+                rename_apart(&body, used, seen),
+            ))
+        }
+        LogExpr::UnaryOp(op, inner) => Box::new(LogExpr::UnaryOp(
+            op.clone(),
+            rename_apart(inner, used, seen),
+        )),
+        LogExpr::BinaryOp(op, exprs) => Box::new(LogExpr::BinaryOp(
+            op.clone(),
+            exprs.iter().map(|e| rename_apart(e, used, seen)).collect(),
+        )),
+        _ => Box::new(expr.clone()),
+    }
+}
+
+// Strips the quantifiers of a renamed-apart NNF formula, collecting them in order
+fn strip(expr: &LogExpr, prefix: &mut Prefix) -> Box<LogExpr> {
+    match expr {
+        LogExpr::Quant(q, var, body) => {
+            prefix.push(((**q).clone(), var.clone()));
+            strip(body, prefix)
+        }
+        LogExpr::UnaryOp(op, inner) => Box::new(LogExpr::UnaryOp(op.clone(), strip(inner, prefix))),
+        LogExpr::BinaryOp(op, exprs) => Box::new(LogExpr::BinaryOp(
+            op.clone(),
+            exprs.iter().map(|e| strip(e, prefix)).collect(),
+        )),
+        _ => Box::new(expr.clone()),
+    }
+}
+
+fn quantify(prefix: &Prefix, matrix: Box<LogExpr>) -> Box<LogExpr> {
+    prefix.iter().rev().fold(matrix, |body, (q, var)| {
+        Box::new(LogExpr::Quant(Box::new(q.clone()), var.clone(), body))
+    })
+}
+
+// Moving a quantifier out of a box, diamond or temporal operator is the
+// Barcan step, which is unsound, so such formulas are refused
+fn prenex_parts(expr: &LogExpr) -> Result<(Prefix, Box<LogExpr>), PrenexError> {
+    if let Some(part) = expr.modal_part() {
+        return Err(PrenexError::Modal(Box::new(part.clone())));
+    }
+    let mut used = HashSet::new();
+    expr.symbols(&mut used);
+    // Binders must also avoid the names that occur free
+    let mut seen = used
+        .iter()
+        .filter(|name| expr.occurs_free(name))
+        .cloned()
+        .collect();
+    let renamed = rename_apart(&nnf(expr, true), &mut used, &mut seen);
+    let mut prefix = Vec::new();
+    let matrix = strip(&renamed, &mut prefix);
+    Ok((prefix, matrix))
+}
+
+/// An equivalent formula in prenex negation normal form, with every bound
+/// variable renamed apart from the others and from the free symbols
+pub fn prenex(expr: &LogExpr) -> Result<Box<LogExpr>, PrenexError> {
+    let (prefix, matrix) = prenex_parts(expr)?;
+    Ok(quantify(&prefix, matrix))
+}
+
+/// A Skolem symbol introduced for an existential variable
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
+pub struct SkolemSymbol {
+    pub name: Box<String>,
+    pub arity: usize,
+    pub replaces: Box<String>,
+}
+
+/// A universal prenex formula equisatisfiable with the original
+#[derive(Debug, Clone)]
+pub struct Skolemized {
+    pub formula: Box<LogExpr>,
+    pub symbols: Vec<SkolemSymbol>,
+}
+
+impl Skolemized {
+    /// Replaces each existential of the prenex form by a fresh function of
+    /// the universals before it, or a constant when there are none
+    pub fn new(expr: &LogExpr) -> Result<Skolemized, PrenexError> {
+        Skolemized::with_used(expr, &mut HashSet::new())
+    }
+
+    /// Skolemizes while avoiding, and then recording, the names in `used`, so
+    /// several formulas can be given disjoint Skolem symbols
+    pub fn with_used(
+        expr: &LogExpr,
+        used: &mut HashSet<String>,
+    ) -> Result<Skolemized, PrenexError> {
+        let (prefix, mut matrix) = prenex_parts(expr)?;
+        matrix.symbols(used);
+        for (_, var) in &prefix {
+            used.insert(var.text.to_string());
+        }
+
+        let mut universals: Prefix = Vec::new();
+        let mut symbols = Vec::new();
+        for (q, var) in prefix {
+            match q {
+                Quantifier::Forall => universals.push((q, var)),
+                Quantifier::Exists => {
+                    let name = (1..)
+                        .map(|n| format!("sk{}", n))
+                        .find(|name| !used.contains(name))
+                        .unwrap();
+                    used.insert(name.clone());
+                    let token = LogExpr::renamed(&var, &name);
+                    let term = if universals.is_empty() {
+                        LogExpr::Atom(token)
+                    } else {
+                        let args = universals
+                            .iter()
+                            .map(|(_, u)| Box::new(LogExpr::Atom(u.clone())))
+                            .collect();
+                        LogExpr::Func(token, args)
+                    };
+                    matrix = matrix.substitute_term(&var.text, &term);
+                    symbols.push(SkolemSymbol {
+                        name: Box::new(name),
+                        arity: universals.len(),
+                        replaces: var.text.clone(),
+                    });
+                }
+            }
+        }
+        Ok(Skolemized {
+            formula: quantify(&universals, matrix),
+            symbols,
+        })
+    }
+}
+
+impl Display for Skolemized {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\t{}", self.formula)?;
+        for symbol in &self.symbols {
+            let kind = if symbol.arity == 0 {
+                "constant"
+            } else {
+                "function"
+            };
+            write!(
+                f,
+                "\n\t\t{} {}/{} replaces {}",
+                kind, symbol.name, symbol.arity, symbol.replaces
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn prenexed(s: &str) -> String {
+        prenex(&expr(s)).unwrap().to_string()
+    }
+
+    #[test]
+    fn quantifiers_move_out_with_their_polarity() {
+        assert_eq!(
+            prenexed("(imp (forall x (P x)) (exists x (Q x)))"),
+            "(exists x (exists x' (or (not (P x)) (Q x'))))"
+        );
+        assert_eq!(
+            prenexed("(and (forall x (P x)) (exists x (Q x)))"),
+            "(forall x (exists x' (and (P x) (Q x'))))"
+        );
+        assert_eq!(prenexed("(not (exists y (R y)))"), "(forall y (not (R y)))");
+    }
+
+    #[test]
+    fn binders_avoid_free_names() {
+        assert_eq!(
+            prenexed("(and (P x) (forall x (Q x)))"),
+            "(forall x' (and (P x) (Q x')))"
+        );
+    }
+
+    #[test]
+    fn existentials_become_functions_of_the_universals_before_them() {
+        let skolemized = Skolemized::new(&expr(
+            "(forall x (exists y (forall z (exists w (R x y z w)))))",
+        ))
+        .unwrap();
+        assert_eq!(
+            skolemized.formula.to_string(),
+            "(forall x (forall z (R x (sk1 x) z (sk2 x z))))"
+        );
+        let arities = skolemized
+            .symbols
+            .iter()
+            .map(|s| (s.name.to_string(), s.arity, s.replaces.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            arities,
+            vec![
+                ("sk1".to_string(), 1, "y".to_string()),
+                ("sk2".to_string(), 2, "w".to_string())
+            ]
+        );
+        let skolemized = Skolemized::new(&expr("(exists x (P x))")).unwrap();
+        assert_eq!(skolemized.formula.to_string(), "(P sk1)");
+        assert_eq!(skolemized.symbols[0].arity, 0);
+    }
+
+    #[test]
+    fn skolem_names_stay_disjoint_across_formulas() {
+        let mut used = HashSet::new();
+        let first = Skolemized::with_used(&expr("(exists x (P x sk1))"), &mut used).unwrap();
+        let second = Skolemized::with_used(&expr("(exists x (Q x))"), &mut used).unwrap();
+        assert_eq!(first.formula.to_string(), "(P sk2 sk1)");
+        assert_eq!(second.formula.to_string(), "(Q sk3)");
+    }
+
+    #[test]
+    fn modal_formulas_are_refused() {
+        assert!(matches!(
+            prenex(&expr("(box (forall x (P x)))")),
+            Err(PrenexError::Modal(_))
+        ));
+        assert!(matches!(
+            Skolemized::new(&expr("(exists x (always (P x)))")),
+            Err(PrenexError::Modal(_))
+        ));
+    }
+}
@@ -0,0 +1,602 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::parser::*;
+use crate::prenex::*;
+
+/// Clauses in a formula's clause form, or resolvents generated, before giving up
+const CLAUSE_LIMIT: usize = 2000;
+
+#[derive(Debug, Clone)]
+pub enum ResolutionError {
+    NotClassical(Box<LogExpr>),
+    Modal(Box<LogExpr>),
+    Prenex(PrenexError),
+    TooManyClauses,
+}
+
+impl Display for ResolutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolutionError::NotClassical(expr) => {
+                write!(f, "{} is not a classical truth value", expr)
+            }
+            ResolutionError::Modal(expr) => {
+                write!(
+                    f,
+                    "{} is modal or temporal, beyond classical clause form",
+                    expr
+                )
+            }
+            ResolutionError::Prenex(e) => write!(f, "{}", e),
+            ResolutionError::TooManyClauses => {
+                write!(
+                    f,
+                    "clause form would have more than {} clauses",
+                    CLAUSE_LIMIT
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Literal {
+    pub atom: Box<LogExpr>,
+    pub positive: bool,
+}
+
+impl Literal {
+    pub fn complement(&self) -> Literal {
+        Literal {
+            atom: self.atom.clone(),
+            positive: !self.positive,
+        }
+    }
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.positive {
+            write!(f, "{}", self.atom)
+        } else {
+            write!(f, "~{}", self.atom)
+        }
+    }
+}
+
+/// A disjunction of literals, kept sorted and free of duplicates
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Clause {
+    pub literals: Vec<Literal>,
+}
+
+impl Clause {
+    pub fn new(mut literals: Vec<Literal>) -> Clause {
+        literals.sort_by_key(|l| (l.atom.to_string(), l.positive));
+        literals.dedup();
+        Clause { literals }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.literals.is_empty()
+    }
+
+    pub fn is_tautology(&self) -> bool {
+        self.literals
+            .iter()
+            .any(|l| self.literals.contains(&l.complement()))
+    }
+
+    pub fn subsumes(&self, other: &Clause) -> bool {
+        self.literals.iter().all(|l| other.literals.contains(l))
+    }
+
+    // Every clause obtainable by resolving the two on a single complementary pair
+    pub fn resolvents(&self, other: &Clause) -> Vec<Clause> {
+        let mut results = Vec::new();
+        for literal in &self.literals {
+            let complement = literal.complement();
+            if other.literals.contains(&complement) {
+                let literals = self
+                    .literals
+                    .iter()
+                    .filter(|l| *l != literal)
+                    .chain(other.literals.iter().filter(|l| **l != complement))
+                    .cloned()
+                    .collect();
+                results.push(Clause::new(literals));
+            }
+        }
+        results
+    }
+}
+
+impl Display for Clause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let literals = self
+            .literals
+            .iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{{{}}}", literals.join(", "))
+    }
+}
+
+/// Pushes negations down to the atoms, through quantifiers, modal and
+/// temporal operators, and eliminates implications
+pub fn nnf(expr: &LogExpr, positive: bool) -> Box<LogExpr> {
+    match expr {
+        LogExpr::UnaryOp(op, inner) if **op == Operator::Not => nnf(inner, !positive),
+        LogExpr::UnaryOp(op, inner) => {
+            let op = if positive { (**op).clone() } else { op.dual() };
+            Box::new(LogExpr::UnaryOp(Box::new(op), nnf(inner, positive)))
+        }
+        LogExpr::BinaryOp(op, exprs) => match **op {
+            Operator::And | Operator::Or => {
+                let op = match (&**op, positive) {
+                    (Operator::And, true) | (Operator::Or, false) => Operator::And,
+                    _ => Operator::Or,
+                };
+                Box::new(LogExpr::BinaryOp(
+                    Box::new(op),
+                    exprs.iter().map(|e| nnf(e, positive)).collect(),
+                ))
+            }
+            Operator::Imp => match LogExpr::imp_parts(exprs) {
+                Some((lhs, rhs)) => {
+                    let disjunction =
+                        LogExpr::BinaryOp(Box::new(Operator::Or), vec![lhs.negate(), rhs]);
+                    nnf(&disjunction, positive)
+                }
+                None => match exprs.first() {
+                    Some(expr) => nnf(expr, positive),
+                    None => nnf(&LogExpr::True, positive),
+                },
+            },
+            Operator::Until | Operator::Release => {
+                let op = if positive { (**op).clone() } else { op.dual() };
+                Box::new(LogExpr::BinaryOp(
+                    Box::new(op),
+                    exprs.iter().map(|e| nnf(e, positive)).collect(),
+                ))
+            }
+            _ if !positive => expr.negate(),
+            _ => Box::new(expr.clone()),
+        },
+        LogExpr::Quant(q, var, body) => {
+            let q = match (&**q, positive) {
+                (Quantifier::Forall, true) | (Quantifier::Exists, false) => Quantifier::Forall,
+                _ => Quantifier::Exists,
+            };
+            Box::new(LogExpr::Quant(
+                Box::new(q),
+                var.clone(),
+                nnf(body, positive),
+            ))
+        }
+        LogExpr::True if !positive => Box::new(LogExpr::False),
+        LogExpr::False if !positive => Box::new(LogExpr::True),
+        _ if !positive => expr.negate(),
+        _ => Box::new(expr.clone()),
+    }
+}
+
+// Clause form of a formula already in negation normal form
+fn cnf(expr: &LogExpr) -> Result<Vec<Clause>, ResolutionError> {
+    match expr {
+        LogExpr::True => Ok(Vec::new()),
+        LogExpr::False => Ok(vec![Clause::new(Vec::new())]),
+        LogExpr::BinaryOp(op, exprs) if **op == Operator::And => {
+            let mut clauses = Vec::new();
+            for expr in exprs {
+                clauses.extend(cnf(expr)?);
+            }
+            Ok(clauses)
+        }
+        LogExpr::BinaryOp(op, exprs) if **op == Operator::Or => {
+            // Distribute: pick one clause from each disjunct in every possible way
+            let mut product = vec![Clause::new(Vec::new())];
+            for expr in exprs {
+                let clauses = cnf(expr)?;
+                if product.len() * clauses.len() > CLAUSE_LIMIT {
+                    return Err(ResolutionError::TooManyClauses);
+                }
+                product = product
+                    .iter()
+                    .flat_map(|acc| {
+                        clauses.iter().map(move |clause| {
+                            let mut literals = acc.literals.clone();
+                            literals.extend(clause.literals.iter().cloned());
+                            Clause::new(literals)
+                        })
+                    })
+                    .collect();
+            }
+            Ok(product)
+        }
+        LogExpr::UnaryOp(op, inner) if **op == Operator::Not => {
+            Ok(vec![Clause::new(vec![Literal {
+                atom: inner.clone(),
+                positive: false,
+            }])])
+        }
+        _ => Ok(vec![Clause::new(vec![Literal {
+            atom: Box::new(expr.clone()),
+            positive: true,
+        }])]),
+    }
+}
+
+/// Converts a formula to a set of clauses, dropping tautologies. Distributing
+/// disjunctions can blow up, so a clause form beyond the limit is refused.
+pub fn clausify(expr: &LogExpr) -> Result<Vec<Clause>, ResolutionError> {
+    let mut seen = HashSet::new();
+    Ok(cnf(&nnf(expr, true))?
+        .into_iter()
+        .filter(|c| !c.is_tautology() && seen.insert(c.clone()))
+        .collect())
+}
+
+// Plaisted-Greenbaum encoding of an NNF formula: each connective gets a fresh
+// atom that implies its subformulas, returning the literal standing for `expr`
+fn define(expr: &LogExpr, clauses: &mut Vec<Clause>, counter: &mut usize) -> Literal {
+    match expr {
+        LogExpr::BinaryOp(op, exprs) if matches!(**op, Operator::And | Operator::Or) => {
+            let parts = exprs
+                .iter()
+                .map(|e| define(e, clauses, counter))
+                .collect::<Vec<_>>();
+            *counter += 1;
+            let name = Literal {
+                atom: LogExpr::atom(&format!("_d{}", counter)),
+                positive: true,
+            };
+            if **op == Operator::And {
+                for part in parts {
+                    clauses.push(Clause::new(vec![name.complement(), part]));
+                }
+            } else {
+                let mut literals = parts;
+                literals.push(name.complement());
+                clauses.push(Clause::new(literals));
+            }
+            name
+        }
+        LogExpr::UnaryOp(op, inner) if **op == Operator::Not => Literal {
+            atom: inner.clone(),
+            positive: false,
+        },
+        _ => Literal {
+            atom: Box::new(expr.clone()),
+            positive: true,
+        },
+    }
+}
+
+/// Definitional clause form: equisatisfiable with the formula rather than
+/// equivalent, but linear in its size. Fresh atoms are named `_d1`, `_d2`, ...
+pub fn clausify_definitional(expr: &LogExpr) -> Vec<Clause> {
+    let expr = nnf(expr, true);
+    match *expr {
+        LogExpr::True => Vec::new(),
+        LogExpr::False => vec![Clause::new(Vec::new())],
+        _ => {
+            let mut clauses = Vec::new();
+            let mut counter = 0;
+            let root = define(&expr, &mut clauses, &mut counter);
+            clauses.push(Clause::new(vec![root]));
+            clauses
+        }
+    }
+}
+
+/// Refuses `unknown` and `both`, which clause form would take for atoms
+pub fn classical<'a>(mut exprs: impl Iterator<Item = &'a LogExpr>) -> Result<(), ResolutionError> {
+    match exprs.find_map(|e| e.many_valued_part()) {
+        Some(part) => Err(ResolutionError::NotClassical(Box::new(part.clone()))),
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Source {
+    Premise,
+    NegatedGoal,
+    Resolvent(usize, usize),
+    Factor(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Step {
+    pub number: usize,
+    pub clause: Clause,
+    pub source: Source,
+}
+
+/// Outcome of a set-of-support resolution run
+#[derive(Debug, Clone)]
+pub struct Resolution {
+    pub steps: Vec<Step>,
+    // Index into `steps` of the empty clause, if one was derived
+    pub refutation: Option<usize>,
+    // Whether the search stopped at its resource limit rather than saturating
+    pub gave_up: bool,
+}
+
+impl Resolution {
+    /// Refutes the premises together with the negated goal; without a goal the
+    /// premises themselves form the set of support
+    pub fn prove(
+        premises: &[Box<LogExpr>],
+        goal: Option<&LogExpr>,
+    ) -> Result<Resolution, ResolutionError> {
+        // Negation normal form would push negations through modal operators
+        // and leave literals that never clash
+        let exprs = premises.iter().map(|e| &**e).chain(goal);
+        if let Some(part) = exprs.clone().find_map(|e| e.modal_part()) {
+            return Err(ResolutionError::Modal(Box::new(part.clone())));
+        }
+        classical(exprs)?;
+        Resolution::refute(premises, goal)
+    }
+
+    fn refute(
+        premises: &[Box<LogExpr>],
+        goal: Option<&LogExpr>,
+    ) -> Result<Resolution, ResolutionError> {
+        let mut steps: Vec<Step> = Vec::new();
+        let mut usable: Vec<usize> = Vec::new();
+        let mut support: Vec<usize> = Vec::new();
+
+        let mut input = Vec::new();
+        for premise in premises {
+            input.extend(clausify(premise)?.into_iter().map(|c| (c, Source::Premise)));
+        }
+        if let Some(goal) = goal {
+            input.extend(
+                clausify(&goal.negate())?
+                    .into_iter()
+                    .map(|c| (c, Source::NegatedGoal)),
+            );
+        }
+
+        for (clause, source) in input {
+            if steps.iter().any(|s| s.clause.subsumes(&clause)) {
+                continue;
+            }
+            let index = steps.len();
+            let is_support = goal.is_none() || matches!(source, Source::NegatedGoal);
+            steps.push(Step {
+                number: index + 1,
+                clause,
+                source,
+            });
+            if steps[index].clause.is_empty() {
+                return Ok(Resolution {
+                    steps,
+                    refutation: Some(index),
+                    gave_up: false,
+                });
+            }
+            if is_support {
+                support.push(index);
+            } else {
+                usable.push(index);
+            }
+        }
+
+        // Given-clause loop: the shortest supported clause is resolved against
+        // everything already processed, and its resolvents join the support
+        let mut removed: HashSet<usize> = HashSet::new();
+        let mut generated = 0;
+        while let Some(position) = support
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, &i)| steps[i].clause.literals.len())
+            .map(|(position, _)| position)
+        {
+            let given = support.remove(position);
+            if removed.contains(&given) {
+                continue;
+            }
+            let partners = usable.clone();
+            usable.push(given);
+            for partner in partners.into_iter().chain(std::iter::once(given)) {
+                if removed.contains(&partner) {
+                    continue;
+                }
+                for resolvent in steps[given].clause.resolvents(&steps[partner].clause) {
+                    generated += 1;
+                    if generated > CLAUSE_LIMIT {
+                        return Ok(Resolution {
+                            steps,
+                            refutation: None,
+                            gave_up: true,
+                        });
+                    }
+                    if resolvent.is_tautology()
+                        || steps
+                            .iter()
+                            .enumerate()
+                            .any(|(i, s)| !removed.contains(&i) && s.clause.subsumes(&resolvent))
+                    {
+                        continue;
+                    }
+                    let index = steps.len();
+                    // Backward subsumption: retire clauses the resolvent makes redundant
+                    for (i, step) in steps.iter().enumerate() {
+                        if resolvent.subsumes(&step.clause) {
+                            removed.insert(i);
+                        }
+                    }
+                    steps.push(Step {
+                        number: index + 1,
+                        clause: resolvent,
+                        source: Source::Resolvent(steps[given].number, steps[partner].number),
+                    });
+                    if steps[index].clause.is_empty() {
+                        return Ok(Resolution {
+                            steps,
+                            refutation: Some(index),
+                            gave_up: false,
+                        });
+                    }
+                    support.push(index);
+                }
+            }
+        }
+
+        Ok(Resolution {
+            steps,
+            refutation: None,
+            gave_up: false,
+        })
+    }
+
+    // The steps the empty clause actually depends on, in derivation order
+    fn derivation(&self, index: usize) -> Vec<&Step> {
+        let mut needed = HashSet::new();
+        let mut stack = vec![index];
+        while let Some(i) = stack.pop() {
+            if needed.insert(i) {
+                match self.steps[i].source {
+                    Source::Resolvent(a, b) => {
+                        stack.push(a - 1);
+                        stack.push(b - 1);
+                    }
+                    Source::Factor(a) => stack.push(a - 1),
+                    _ => {}
+                }
+            }
+        }
+        let mut steps = needed
+            .into_iter()
+            .map(|i| &self.steps[i])
+            .collect::<Vec<_>>();
+        steps.sort_by_key(|s| s.number);
+        steps
+    }
+}
+
+impl Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.refutation {
+            Some(index) => {
+                writeln!(f, "\tRefutation found:")?;
+                for step in self.derivation(index) {
+                    let source = match step.source {
+                        Source::Premise => "premise".to_string(),
+                        Source::NegatedGoal => "negated goal".to_string(),
+                        Source::Resolvent(a, b) => format!("resolve {}, {}", a, b),
+                        Source::Factor(a) => format!("factor {}", a),
+                    };
+                    writeln!(f, "\t\t{}. {}  [{}]", step.number, step.clause, source)?;
+                }
+                write!(f, "\tProved.")
+            }
+            None if self.gave_up => write!(
+                f,
+                "\tGave up: no refutation within {} clauses.",
+                self.steps.len()
+            ),
+            None => write!(
+                f,
+                "\tNo refutation: clause set saturated after {} clauses.",
+                self.steps.len()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn proves(premises: &[&str], goal: &str) -> bool {
+        let premises = premises.iter().map(|s| expr(s)).collect::<Vec<_>>();
+        let resolution = Resolution::prove(&premises, Some(&expr(goal))).unwrap();
+        assert!(!resolution.gave_up);
+        resolution.refutation.is_some()
+    }
+
+    #[test]
+    fn entailments_are_refuted() {
+        assert!(proves(&["(imp p q)", "p"], "q"));
+        assert!(proves(&["(imp p q)", "(not q)"], "(not p)"));
+        assert!(proves(&["(or p q)", "(imp p r)", "(imp q r)"], "r"));
+        assert!(proves(&[], "(imp (imp (imp p q) p) p)"));
+    }
+
+    #[test]
+    fn non_entailments_saturate() {
+        assert!(!proves(&["(imp p q)", "q"], "p"));
+        assert!(!proves(&["(or p q)"], "p"));
+        assert!(!proves(&[], "(imp p q)"));
+    }
+
+    #[test]
+    fn clause_form_drops_tautologies() {
+        assert!(clausify(&expr("(or p (not p))")).unwrap().is_empty());
+        assert_eq!(clausify(&expr("(and p (or q r))")).unwrap().len(), 2);
+        assert_eq!(
+            clausify(&expr("false")).unwrap(),
+            vec![Clause::new(Vec::new())]
+        );
+    }
+
+    #[test]
+    fn clause_form_is_capped() {
+        // Distributing twelve two-literal conjunctions gives 4096 clauses
+        let conjunctions = (1..=12)
+            .map(|i| format!("(and p{} q{})", i, i))
+            .collect::<Vec<_>>();
+        let goal = expr(&format!("(or {})", conjunctions.join(" ")));
+        assert!(matches!(
+            clausify(&goal),
+            Err(ResolutionError::TooManyClauses)
+        ));
+        assert!(Resolution::prove(&[], Some(&goal.negate())).is_err());
+        assert!(clausify(&goal.negate()).is_ok());
+    }
+
+    #[test]
+    fn hard_refutations_give_up() {
+        // Four pigeons in three holes, beyond the resolvent limit
+        let mut premises = Vec::new();
+        for i in 0..4 {
+            let holes = (0..3).map(|j| format!("p{}h{}", i, j)).collect::<Vec<_>>();
+            premises.push(expr(&format!("(or {})", holes.join(" "))));
+        }
+        for j in 0..3 {
+            for a in 0..4 {
+                for b in a + 1..4 {
+                    premises.push(expr(&format!("(not (and p{}h{} p{}h{}))", a, j, b, j)));
+                }
+            }
+        }
+        let resolution = Resolution::prove(&premises, None).unwrap();
+        assert!(resolution.gave_up);
+        assert!(resolution.refutation.is_none());
+    }
+
+    #[test]
+    fn definitional_clauses_name_subformulas() {
+        let clauses = clausify_definitional(&expr("(or (and p q) (and r s))"));
+        assert!(clauses
+            .iter()
+            .flat_map(|c| &c.literals)
+            .any(|l| l.atom.to_string() == "_d1"));
+    }
+
+    #[test]
+    fn modal_and_many_valued_formulas_are_refused() {
+        assert!(Resolution::prove(&[expr("(box p)")], Some(&expr("(box p)"))).is_err());
+        assert!(Resolution::prove(&[expr("unknown")], Some(&expr("p"))).is_err());
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::lexer::*;
+use crate::parser::*;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    Predicate,
+    Function,
+    Constant,
+}
+
+impl Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymbolKind::Predicate => write!(f, "predicate"),
+            SymbolKind::Function => write!(f, "function"),
+            SymbolKind::Constant => write!(f, "constant"),
+        }
+    }
+}
+
+impl SymbolKind {
+    pub fn from_str(s: &str) -> Option<SymbolKind> {
+        match s {
+            "predicate" | "pred" | "relation" => Some(SymbolKind::Predicate),
+            "function" | "func" => Some(SymbolKind::Function),
+            "constant" | "const" => Some(SymbolKind::Constant),
+            _ => None,
+        }
+    }
+
+    // Constants are nullary function symbols as far as terms are concerned
+    fn is_term(&self) -> bool {
+        matches!(self, SymbolKind::Function | SymbolKind::Constant)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SignatureError {
+    Undeclared(Box<Token>),
+    WrongKind {
+        token: Box<Token>,
+        declared: SymbolKind,
+    },
+    WrongArity {
+        token: Box<Token>,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::Undeclared(token) => {
+                write!(f, "{}: undeclared symbol `{}`", token.loc, token.text)
+            }
+            SignatureError::WrongKind { token, declared } => write!(
+                f,
+                "{}: `{}` is declared as a {}",
+                token.loc, token.text, declared
+            ),
+            SignatureError::WrongArity {
+                token,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: `{}` takes {} arguments, {} given",
+                token.loc, token.text, expected, found
+            ),
+        }
+    }
+}
+
+/// The non-logical symbols declared in a session, with their arities
+#[derive(Debug, Clone)]
+#[allow(clippy::box_collection)]
+pub struct Signature {
+    pub symbols: HashMap<Box<String>, (SymbolKind, usize)>,
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut symbols = self.symbols.iter().collect::<Vec<_>>();
+        symbols.sort_by_key(|(name, _)| name.to_string());
+        for (name, (kind, arity)) in symbols {
+            writeln!(f, "\t\t{} {} {}", kind, arity, name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Signature {
+    pub fn new() -> Signature {
+        Signature {
+            symbols: HashMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    #[allow(clippy::box_collection)]
+    pub fn bind(&mut self, kind: SymbolKind, name: Box<String>, arity: usize) {
+        self.symbols.insert(name, (kind, arity));
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&(SymbolKind, usize)> {
+        self.symbols.get(&Box::new(name.to_string()))
+    }
+
+    fn check_symbol(
+        &self,
+        token: &Token,
+        arity: usize,
+        accepts: impl Fn(&SymbolKind) -> bool,
+    ) -> Result<(), SignatureError> {
+        match self.lookup(&token.text) {
+            None => Err(SignatureError::Undeclared(Box::new(token.clone()))),
+            Some((kind, _)) if !accepts(kind) => Err(SignatureError::WrongKind {
+                token: Box::new(token.clone()),
+                declared: kind.clone(),
+            }),
+            Some((_, expected)) if *expected != arity => Err(SignatureError::WrongArity {
+                token: Box::new(token.clone()),
+                expected: *expected,
+                found: arity,
+            }),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn check_term(&self, term: &LogExpr, bound: &HashSet<String>) -> Result<(), SignatureError> {
+        match term {
+            LogExpr::Atom(token) if bound.contains(token.text.as_str()) => Ok(()),
+            // Free object variables need no declaration
+            LogExpr::Atom(token)
+                if LogExpr::is_variable_name(&token.text) && self.lookup(&token.text).is_none() =>
+            {
+                Ok(())
+            }
+            LogExpr::Atom(token) => self.check_symbol(token, 0, SymbolKind::is_term),
+            LogExpr::Func(name, args) => {
+                self.check_symbol(name, args.len(), SymbolKind::is_term)?;
+                args.iter().try_for_each(|arg| self.check_term(arg, bound))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_formula(&self, expr: &LogExpr, bound: &HashSet<String>) -> Result<(), SignatureError> {
+        match expr {
+            // Propositional atoms are nullary predicates
+            LogExpr::Atom(token) => {
+                self.check_symbol(token, 0, |kind| *kind == SymbolKind::Predicate)
+            }
+            // Equality is built in and always binary
+            LogExpr::Pred(name, args) if *name.text == "=" => {
+                if args.len() != 2 {
+                    return Err(SignatureError::WrongArity {
+                        token: name.clone(),
+                        expected: 2,
+                        found: args.len(),
+                    });
+                }
+                args.iter().try_for_each(|arg| self.check_term(arg, bound))
+            }
+            LogExpr::Pred(name, args) => {
+                self.check_symbol(name, args.len(), |kind| *kind == SymbolKind::Predicate)?;
+                args.iter().try_for_each(|arg| self.check_term(arg, bound))
+            }
+            LogExpr::UnaryOp(_, inner) => self.check_formula(inner, bound),
+            LogExpr::BinaryOp(_, exprs) => {
+                exprs.iter().try_for_each(|e| self.check_formula(e, bound))
+            }
+            LogExpr::Quant(_, var, body) => {
+                let mut bound = bound.clone();
+                bound.insert(var.text.to_string());
+                self.check_formula(body, &bound)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Rejects undeclared symbols and symbols used with the wrong arity. An
+    /// empty signature accepts everything, and metavariables are never checked.
+    pub fn check(&self, expr: &LogExpr) -> Result<(), SignatureError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        self.check_formula(expr, &HashSet::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn signature() -> Signature {
+        let mut signature = Signature::new();
+        signature.bind(SymbolKind::Predicate, Box::new("Lt".to_string()), 2);
+        signature.bind(SymbolKind::Function, Box::new("s".to_string()), 1);
+        signature.bind(SymbolKind::Constant, Box::new("zero".to_string()), 0);
+        signature
+    }
+
+    #[test]
+    fn declared_symbols_are_accepted() {
+        let signature = signature();
+        assert!(signature.check(&expr("(forall x (Lt x (s x)))")).is_ok());
+        assert!(signature.check(&expr("(Lt zero (s zero))")).is_ok());
+        assert!(Signature::new().check(&expr("(P q)")).is_ok());
+    }
+
+    #[test]
+    fn misused_symbols_are_located() {
+        let signature = signature();
+        match signature.check(&expr("(Lt zero one)")) {
+            Err(e @ SignatureError::Undeclared(_)) => {
+                assert_eq!(e.to_string(), "col 9: undeclared symbol `one`")
+            }
+            other => panic!("checked as {:?}", other),
+        }
+        assert!(matches!(
+            signature.check(&expr("(Lt zero)")),
+            Err(SignatureError::WrongArity {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+        assert!(matches!(
+            signature.check(&expr("(s zero)")),
+            Err(SignatureError::WrongKind {
+                declared: SymbolKind::Function,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn equality_needs_no_declaration() {
+        let signature = signature();
+        assert!(signature.check(&expr("(= (s zero) zero)")).is_ok());
+        assert!(matches!(
+            signature.check(&expr("(= zero)")),
+            Err(SignatureError::WrongArity {
+                expected: 2,
+                found: 1,
+                ..
+            })
+        ));
+        assert!(matches!(
+            signature.check(&expr("(= zero one)")),
+            Err(SignatureError::Undeclared(_))
+        ));
+    }
+
+    #[test]
+    fn predicates_cannot_be_named_like_operators() {
+        let bind = |s: &str| Command::parse(&mut Lexer::new(s, None));
+        assert!(bind(":bind predicate 2 L").is_err());
+        assert!(bind(":bind predicate 1 and").is_err());
+        assert!(bind(":bind predicate 1 G").is_ok());
+        assert!(bind(":bind function 1 K").is_ok());
+    }
+}
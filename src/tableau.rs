@@ -0,0 +1,369 @@
+use std::fmt::Display;
+
+use crate::parser::*;
+
+#[derive(Debug, Clone)]
+pub enum TableauError {
+    NotClassical(Box<LogExpr>),
+}
+
+impl Display for TableauError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TableauError::NotClassical(expr) => {
+                write!(f, "{} is not a classical truth value", expr)
+            }
+        }
+    }
+}
+
+/// How an entry on a branch came to be there
+#[derive(Debug, Clone)]
+pub enum Origin {
+    Premise,
+    Alpha(usize),
+    Beta(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub number: usize,
+    pub formula: Box<LogExpr>,
+    pub origin: Origin,
+}
+
+#[derive(Debug, Clone)]
+pub enum Status {
+    // Closed by the two entries holding complementary literals (equal for `false`)
+    Closed(usize, usize),
+    Open,
+    Branched,
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub entries: Vec<Entry>,
+    pub status: Status,
+    pub children: Vec<Node>,
+}
+
+/// A semantic tableau for a set of propositional formulas
+#[derive(Debug, Clone)]
+pub struct Tableau {
+    pub root: Node,
+    pub model: Option<Vec<(Box<LogExpr>, bool)>>,
+}
+
+#[allow(clippy::vec_box)]
+enum Expansion {
+    Literal,
+    Alpha(Vec<Box<LogExpr>>),
+    Beta(Vec<Box<LogExpr>>),
+}
+
+fn expansion(expr: &LogExpr) -> Expansion {
+    match expr {
+        LogExpr::BinaryOp(op, exprs) => match **op {
+            Operator::And => Expansion::Alpha(exprs.clone()),
+            Operator::Or if exprs.is_empty() => Expansion::Alpha(vec![Box::new(LogExpr::False)]),
+            Operator::Or => Expansion::Beta(exprs.clone()),
+            Operator::Imp => match LogExpr::imp_parts(exprs) {
+                Some((lhs, rhs)) => Expansion::Beta(vec![lhs.negate(), rhs]),
+                None => Expansion::Alpha(exprs.clone()),
+            },
+            _ => Expansion::Literal,
+        },
+        LogExpr::UnaryOp(op, inner) if **op == Operator::Not => match &**inner {
+            LogExpr::UnaryOp(op, expr) if **op == Operator::Not => {
+                Expansion::Alpha(vec![expr.clone()])
+            }
+            LogExpr::BinaryOp(op, exprs) => match **op {
+                Operator::And if exprs.is_empty() => {
+                    Expansion::Alpha(vec![Box::new(LogExpr::False)])
+                }
+                Operator::And => Expansion::Beta(exprs.iter().map(|e| e.negate()).collect()),
+                Operator::Or => Expansion::Alpha(exprs.iter().map(|e| e.negate()).collect()),
+                Operator::Imp => match LogExpr::imp_parts(exprs) {
+                    Some((lhs, rhs)) => Expansion::Alpha(vec![lhs, rhs.negate()]),
+                    None if exprs.is_empty() => Expansion::Alpha(vec![Box::new(LogExpr::False)]),
+                    None => Expansion::Alpha(exprs.iter().map(|e| e.negate()).collect()),
+                },
+                _ => Expansion::Literal,
+            },
+            LogExpr::True => Expansion::Alpha(vec![Box::new(LogExpr::False)]),
+            LogExpr::False => Expansion::Alpha(Vec::new()),
+            _ => Expansion::Literal,
+        },
+        _ => Expansion::Literal,
+    }
+}
+
+fn complement(expr: &LogExpr) -> Box<LogExpr> {
+    match expr {
+        LogExpr::UnaryOp(op, inner) if **op == Operator::Not => inner.clone(),
+        _ => expr.negate(),
+    }
+}
+
+struct Builder {
+    counter: usize,
+    model: Option<Vec<(Box<LogExpr>, bool)>>,
+}
+
+impl Builder {
+    fn push(&mut self, entries: &mut Vec<Entry>, formula: Box<LogExpr>, origin: Origin) -> Entry {
+        self.counter += 1;
+        let entry = Entry {
+            number: self.counter,
+            formula,
+            origin,
+        };
+        entries.push(entry.clone());
+        entry
+    }
+
+    fn closure(branch: &[Entry], entry: &Entry) -> Option<Status> {
+        if *entry.formula == LogExpr::False {
+            return Some(Status::Closed(entry.number, entry.number));
+        }
+        if !matches!(expansion(&entry.formula), Expansion::Literal) {
+            return None;
+        }
+        let complement = complement(&entry.formula);
+        branch
+            .iter()
+            .find(|other| other.formula == complement)
+            .map(|other| Status::Closed(other.number, entry.number))
+    }
+
+    // Expands a branch given everything above this node and the entries it starts with
+    fn build(
+        &mut self,
+        mut branch: Vec<Entry>,
+        mut pending: Vec<Entry>,
+        start: Vec<Entry>,
+    ) -> Node {
+        let mut entries = Vec::new();
+        for entry in start {
+            let closed = Builder::closure(&branch, &entry);
+            branch.push(entry.clone());
+            entries.push(entry);
+            if let Some(status) = closed {
+                return Node {
+                    entries,
+                    status,
+                    children: Vec::new(),
+                };
+            }
+        }
+
+        // Alpha formulas are always expanded before any branching
+        while let Some(i) = pending
+            .iter()
+            .position(|e| matches!(expansion(&e.formula), Expansion::Alpha(_)))
+        {
+            let source = pending.remove(i);
+            if let Expansion::Alpha(parts) = expansion(&source.formula) {
+                for part in parts {
+                    let entry = self.push(&mut entries, part, Origin::Alpha(source.number));
+                    let closed = Builder::closure(&branch, &entry);
+                    branch.push(entry.clone());
+                    pending.push(entry);
+                    if let Some(status) = closed {
+                        return Node {
+                            entries,
+                            status,
+                            children: Vec::new(),
+                        };
+                    }
+                }
+            }
+        }
+
+        match pending
+            .iter()
+            .position(|e| matches!(expansion(&e.formula), Expansion::Beta(_)))
+        {
+            Some(i) => {
+                let source = pending.remove(i);
+                let mut children = Vec::new();
+                if let Expansion::Beta(parts) = expansion(&source.formula) {
+                    for part in parts {
+                        let mut start = Vec::new();
+                        let entry = self.push(&mut start, part, Origin::Beta(source.number));
+                        let mut child_pending = pending.clone();
+                        child_pending.push(entry);
+                        children.push(self.build(branch.clone(), child_pending, start));
+                    }
+                }
+                Node {
+                    entries,
+                    status: Status::Branched,
+                    children,
+                }
+            }
+            None => {
+                if self.model.is_none() {
+                    self.model = Some(Tableau::branch_model(&branch));
+                }
+                Node {
+                    entries,
+                    status: Status::Open,
+                    children: Vec::new(),
+                }
+            }
+        }
+    }
+}
+
+impl Tableau {
+    pub fn new(premises: &[Box<LogExpr>]) -> Result<Tableau, TableauError> {
+        if let Some(part) = premises.iter().find_map(|p| p.many_valued_part()) {
+            return Err(TableauError::NotClassical(Box::new(part.clone())));
+        }
+        let mut builder = Builder {
+            counter: 0,
+            model: None,
+        };
+        let mut start = Vec::new();
+        for premise in premises {
+            builder.push(&mut start, premise.clone(), Origin::Premise);
+        }
+        let root = builder.build(Vec::new(), start.clone(), start);
+        Ok(Tableau {
+            root,
+            model: builder.model,
+        })
+    }
+
+    // Reads the truth assignment off the literals of a fully expanded open branch
+    fn branch_model(branch: &[Entry]) -> Vec<(Box<LogExpr>, bool)> {
+        let mut model: Vec<(Box<LogExpr>, bool)> = Vec::new();
+        for entry in branch {
+            let (atom, value) = match &*entry.formula {
+                LogExpr::UnaryOp(op, inner) if **op == Operator::Not => (inner.clone(), false),
+                LogExpr::True | LogExpr::False => continue,
+                other => (Box::new(other.clone()), true),
+            };
+            if matches!(*atom, LogExpr::True | LogExpr::False)
+                || !matches!(expansion(&atom), Expansion::Literal)
+            {
+                continue;
+            }
+            if !model.iter().any(|(seen, _)| *seen == atom) {
+                model.push((atom, value));
+            }
+        }
+        model
+    }
+
+    // The first line of a node hangs off its branch connector, the rest align under it
+    fn fmt_node(
+        node: &Node,
+        f: &mut std::fmt::Formatter<'_>,
+        head: &str,
+        prefix: &str,
+    ) -> std::fmt::Result {
+        let mut lead = head;
+        for entry in &node.entries {
+            let origin = match entry.origin {
+                Origin::Premise => "premise".to_string(),
+                Origin::Alpha(n) => format!("α {}", n),
+                Origin::Beta(n) => format!("β {}", n),
+            };
+            writeln!(
+                f,
+                "{}{}. {}  [{}]",
+                lead, entry.number, entry.formula, origin
+            )?;
+            lead = prefix;
+        }
+        match node.status {
+            Status::Closed(i, j) if i == j => writeln!(f, "{}✗ closed by {}", lead, i),
+            Status::Closed(i, j) => writeln!(f, "{}✗ closed by {} and {}", lead, i, j),
+            Status::Open => writeln!(f, "{}○ open", lead),
+            Status::Branched => {
+                let last = node.children.len().saturating_sub(1);
+                for (i, child) in node.children.iter().enumerate() {
+                    let (branch, rest) = if i == last {
+                        ("└── ", "    ")
+                    } else {
+                        ("├── ", "│   ")
+                    };
+                    Tableau::fmt_node(
+                        child,
+                        f,
+                        &format!("{}{}", prefix, branch),
+                        &format!("{}{}", prefix, rest),
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for Tableau {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Tableau::fmt_node(&self.root, f, "\t", "\t")?;
+        match &self.model {
+            None => write!(f, "\tAll branches closed: unsatisfiable."),
+            Some(model) => {
+                write!(f, "\tOpen branch model:")?;
+                if model.is_empty() {
+                    return write!(f, " (any valuation)");
+                }
+                let assignments = model
+                    .iter()
+                    .map(|(atom, value)| format!("{} = {}", atom, value))
+                    .collect::<Vec<_>>();
+                write!(f, " {}", assignments.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::*;
+
+    fn expr(s: &str) -> Box<LogExpr> {
+        LogExpr::parse(&mut Lexer::new(s, None)).unwrap()
+    }
+
+    fn closes(exprs: &[&str]) -> bool {
+        let exprs = exprs.iter().map(|s| expr(s)).collect::<Vec<_>>();
+        Tableau::new(&exprs).unwrap().model.is_none()
+    }
+
+    #[test]
+    fn negated_tautologies_close() {
+        assert!(closes(&["(not (or p (not p)))"]));
+        assert!(closes(&["(not (imp (imp (imp p q) p) p))"]));
+        assert!(closes(&["(not (imp (and (imp p q) (imp q r)) (imp p r)))"]));
+        assert!(closes(&["(and p (not p))"]));
+        assert!(closes(&["(or)"]));
+    }
+
+    #[test]
+    fn satisfiable_sets_stay_open() {
+        assert!(!closes(&["(not (imp (or p q) p))"]));
+        assert!(!closes(&["(imp p q)", "(not (imp q p))"]));
+        assert!(!closes(&["(and)"]));
+    }
+
+    #[test]
+    fn open_branch_gives_a_model() {
+        let tableau = Tableau::new(&[expr("(imp p q)"), expr("(not q)")]).unwrap();
+        let model = tableau.model.unwrap();
+        assert!(model.contains(&(expr("p"), false)));
+        assert!(model.contains(&(expr("q"), false)));
+    }
+
+    #[test]
+    fn many_valued_constants_are_refused() {
+        assert!(Tableau::new(&[expr("(or p unknown)")]).is_err());
+        assert!(Tableau::new(&[expr("(not both)")]).is_err());
+    }
+}